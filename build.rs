@@ -0,0 +1,47 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Preset directories compiled into the binary as GResources under
+/// `/com/github/waybarconf/presets/<dir>`.
+const PRESET_DIRS: &[&str] = &["bricks", "colors", "layouts", "scripts", "templates"];
+
+fn collect(root: &Path, dir: &Path, out: &mut Vec<String>) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    let mut paths: Vec<PathBuf> = entries.flatten().map(|e| e.path()).collect();
+    paths.sort();
+    for path in paths {
+        if path.is_dir() {
+            collect(root, &path, out);
+        } else if let Ok(rel) = path.strip_prefix(root) {
+            out.push(rel.to_string_lossy().replace('\\', "/"));
+        }
+    }
+}
+
+fn main() {
+    let root = Path::new("presets");
+    println!("cargo:rerun-if-changed=presets");
+
+    let mut files = Vec::new();
+    for dir in PRESET_DIRS {
+        collect(root, &root.join(dir), &mut files);
+    }
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<gresources>\n  <gresource prefix=\"/com/github/waybarconf/presets\">\n");
+    for f in &files {
+        xml.push_str(&format!("    <file>{}</file>\n", f.replace('&', "&amp;").replace('<', "&lt;")));
+    }
+    xml.push_str("  </gresource>\n</gresources>\n");
+
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR").unwrap());
+    let manifest = out_dir.join("presets.gresource.xml");
+    fs::write(&manifest, xml).expect("failed to write the preset resource manifest");
+
+    let status = Command::new("glib-compile-resources")
+        .arg("--sourcedir").arg(root)
+        .arg("--target").arg(out_dir.join("presets.gresource"))
+        .arg(&manifest)
+        .status()
+        .expect("glib-compile-resources not found (it ships with the GLib development tools)");
+    assert!(status.success(), "glib-compile-resources failed");
+}
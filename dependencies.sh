@@ -18,6 +18,7 @@ echo "Installing dependencies..."
 # git: for cloning if needed
 # base-devel: common build tools
 # gtk4, libadwaita: GUI toolkit libraries
+# glib2-devel: glib-compile-resources, which build.rs uses to bundle the presets
 # waybar: the target application
 
 # modern-labwc dependencies:
@@ -42,6 +43,7 @@ sudo pacman -S --needed --noconfirm \
     git \
     base-devel \
     gtk4 \
+    glib2-devel \
     libadwaita \
     waybar \
     imagemagick \
@@ -22,8 +22,8 @@ mkdir -p "$DATA_DIR"
 echo "Installing binary..."
 cp target/release/"$BIN_NAME" "$INSTALL_DIR/"
 
-echo "Installing presets..."
-cp -r presets "$DATA_DIR/"
+# Presets (bricks, layouts, colors, scripts, templates) are compiled into the binary.
+# Files placed in $DATA_DIR/<kind> override the built-in ones of the same name.
 
 echo "Creating desktop entry..."
 cat <<EOF > "$APP_DIR/waybarconf.desktop"
@@ -0,0 +1,275 @@
+/// The `@keyframes` every session stylesheet gets, shared by the default layout and
+/// `ensure_builtin` so the two cannot drift apart.
+macro_rules! builtin_keyframes {
+    () => {
+        r#"@keyframes blink {
+    to {
+        background-color: rgba(255, 255, 255, 0.1);
+        color: @module_fg;
+    }
+}
+
+@keyframes glow_pulse {
+    0% { background-color: @module_bg; }
+    50% { background-color: @hover_bg; }
+    100% { background-color: @module_bg; }
+}
+
+@keyframes lift {
+    to { margin-top: -2px; }
+}
+
+@keyframes bounce {
+    0% { margin-top: 0; }
+    50% { margin-top: -5px; }
+    100% { margin-top: 0; }
+}
+
+@keyframes wobble {
+    0% { margin-left: 0; }
+    25% { margin-left: -3px; }
+    75% { margin-left: 3px; }
+    100% { margin-left: 0; }
+}
+
+@keyframes shiver {
+    0% { margin-left: 0; }
+    25% { margin-left: -2px; }
+    75% { margin-left: 2px; }
+    100% { margin-left: 0; }
+}
+
+@keyframes rainbow {
+    0% { color: #ff0000; }
+    16% { color: #ff7f00; }
+    33% { color: #ffff00; }
+    50% { color: #00ff00; }
+    66% { color: #0000ff; }
+    83% { color: #4b0082; }
+    100% { color: #9400d3; }
+}
+
+@keyframes spin {
+    from { margin-left: 0; }
+    to { margin-left: 0.1px; }
+}
+
+@keyframes shake {
+    0% { margin-left: 0; }
+    10% { margin-left: -4px; }
+    30% { margin-left: 4px; }
+    50% { margin-left: -4px; }
+    70% { margin-left: 4px; }
+    90% { margin-left: -4px; }
+    100% { margin-left: 0; }
+}
+"#
+    };
+}
+
+pub const BUILTIN_KEYFRAMES: &str = builtin_keyframes!();
+
+pub const EASINGS: &[&str] = &["ease", "linear", "ease-in", "ease-out", "ease-in-out", "step-start", "step-end"];
+pub const DIRECTIONS: &[&str] = &["normal", "reverse", "alternate", "alternate-reverse"];
+
+/// One `@keyframes` rule: stops in source order, each with its declarations.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keyframes {
+    pub name: String,
+    pub stops: Vec<Stop>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stop {
+    /// `0%`, `50%`, `from`, `to`, or a list such as `0%, 100%`.
+    pub offset: String,
+    pub decls: Vec<(String, String)>,
+}
+
+/// Whether `text` is a valid keyframe selector: `from`, `to` or a percentage, or a
+/// comma-separated list of them.
+pub fn is_offset(text: &str) -> bool {
+    text.split(',').map(|p| p.trim()).all(|p| {
+        p == "from" || p == "to" || p.strip_suffix('%').and_then(|n| n.parse::<f64>().ok()).is_some_and(|n| (0.0..=100.0).contains(&n))
+    })
+}
+
+pub fn parse_decls(text: &str) -> Vec<(String, String)> {
+    text.split(';')
+        .filter_map(|d| d.split_once(':'))
+        .map(|(p, v)| (p.trim().to_string(), v.trim().to_string()))
+        .filter(|(p, v)| !p.is_empty() && !v.is_empty())
+        .collect()
+}
+
+pub fn decls_text(decls: &[(String, String)]) -> String {
+    decls.iter().map(|(p, v)| format!("{}: {};", p, v)).collect::<Vec<_>>().join(" ")
+}
+
+/// Byte range of every `@keyframes` block in `css`, with its name.
+fn keyframe_spans(css: &str) -> Vec<(String, usize, usize)> {
+    let mut spans = Vec::new();
+    let mut from = 0;
+    while let Some(rel) = css[from..].find("@keyframes") {
+        let start = from + rel;
+        let Some(open_rel) = css[start..].find('{') else { break };
+        let open = start + open_rel;
+        let name = css[start + "@keyframes".len()..open].trim().to_string();
+        let mut depth = 0;
+        let mut end = None;
+        for (i, c) in css[open..].char_indices() {
+            match c {
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 { end = Some(open + i + 1); break; }
+                }
+                _ => {}
+            }
+        }
+        let Some(end) = end else { break };
+        spans.push((name, start, end));
+        from = end;
+    }
+    spans
+}
+
+/// Every `@keyframes` rule in the stylesheet, in order.
+pub fn parse_keyframes(css: &str) -> Vec<Keyframes> {
+    keyframe_spans(css).into_iter().map(|(name, start, end)| {
+        let block = &css[start..end];
+        let body = &block[block.find('{').map(|i| i + 1).unwrap_or(0)..block.len() - 1];
+        let mut stops = Vec::new();
+        let mut rest = body;
+        while let (Some(open), Some(close)) = (rest.find('{'), rest.find('}')) {
+            if close < open { break; }
+            stops.push(Stop { offset: rest[..open].trim().to_string(), decls: parse_decls(&rest[open + 1..close]) });
+            rest = &rest[close + 1..];
+        }
+        Keyframes { name, stops }
+    }).collect()
+}
+
+pub fn keyframes_css(kf: &Keyframes) -> String {
+    let mut out = format!("@keyframes {} {{\n", kf.name);
+    for stop in &kf.stops {
+        out.push_str(&format!("    {} {{ {} }}\n", stop.offset, decls_text(&stop.decls)));
+    }
+    out.push('}');
+    out
+}
+
+/// Replaces the `@keyframes` rule named `old_name` with `kf` (keeping its place), or
+/// appends `kf` when there is none.
+pub fn set_keyframes(css: &str, old_name: &str, kf: &Keyframes) -> String {
+    match keyframe_spans(css).into_iter().find(|(n, _, _)| n == old_name) {
+        Some((_, start, end)) => format!("{}{}{}", &css[..start], keyframes_css(kf), &css[end..]),
+        None => format!("{}\n\n{}\n", css.trim_end(), keyframes_css(kf)),
+    }
+}
+
+pub fn remove_keyframes(css: &str, name: &str) -> String {
+    match keyframe_spans(css).into_iter().find(|(n, _, _)| n == name) {
+        Some((_, start, end)) => {
+            let before = css[..start].trim_end();
+            let after = css[end..].trim_start_matches('\n');
+            if after.trim().is_empty() { format!("{}\n", before) } else { format!("{}\n\n{}", before, after) }
+        }
+        None => css.to_string(),
+    }
+}
+
+/// Appends the built-in keyframes that `css` does not define yet.
+pub fn ensure_builtin(css: &str) -> String {
+    let present: Vec<String> = keyframe_spans(css).into_iter().map(|(n, _, _)| n).collect();
+    let missing: Vec<Keyframes> = parse_keyframes(BUILTIN_KEYFRAMES).into_iter().filter(|k| !present.contains(&k.name)).collect();
+    if missing.is_empty() { return css.to_string(); }
+    let mut out = css.trim_end().to_string();
+    if !css.contains("/* Animation Keyframes */") { out.push_str("\n\n/* Animation Keyframes */"); }
+    for kf in missing {
+        out.push('\n');
+        out.push_str(&keyframes_css(&kf));
+    }
+    out.push('\n');
+    out
+}
+
+/// Points every `animation` that uses `old` at `new`.
+pub fn rename_in_animations(css: &str, old: &str, new: &str) -> String {
+    let re = regex::Regex::new(&format!(r"(animation\s*:\s*){}(\s|;)", regex::escape(old))).unwrap();
+    re.replace_all(css, |c: &regex::Captures| format!("{}{}{}", &c[1], new, &c[2])).to_string()
+}
+
+pub fn is_builtin(name: &str) -> bool {
+    keyframe_spans(BUILTIN_KEYFRAMES).iter().any(|(n, _, _)| n == name)
+}
+
+/// An `animation` shorthand split into the parts the editor exposes. Anything else
+/// (delay, fill mode...) is kept in `extra` and written back unchanged.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationSpec {
+    pub name: String,
+    pub duration: f64,
+    pub easing: String,
+    /// None for `infinite`.
+    pub iterations: Option<f64>,
+    pub direction: String,
+    pub extra: Vec<String>,
+}
+
+fn parse_time(token: &str) -> Option<f64> {
+    if let Some(ms) = token.strip_suffix("ms") { return ms.parse::<f64>().ok().map(|v| v / 1000.0); }
+    token.strip_suffix('s')?.parse().ok()
+}
+
+impl AnimationSpec {
+    /// Timing each built-in animation was tuned for; custom ones start at one second.
+    pub fn default_for(name: &str) -> Self {
+        let (duration, easing, direction) = match name {
+            "blink" => (1.0, "ease", "alternate"),
+            "glow_pulse" => (2.0, "ease", "normal"),
+            "rainbow" => (4.0, "linear", "normal"),
+            "shiver" => (0.2, "ease", "normal"),
+            "shake" => (0.3, "ease", "normal"),
+            "bounce" => (0.5, "ease", "normal"),
+            "wobble" => (0.4, "ease", "normal"),
+            _ => (1.0, "ease", "normal"),
+        };
+        AnimationSpec { name: name.to_string(), duration, easing: easing.to_string(), iterations: None, direction: direction.to_string(), extra: Vec::new() }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut spec = AnimationSpec { name: String::new(), duration: 0.0, easing: "ease".into(), iterations: Some(1.0), direction: "normal".into(), extra: Vec::new() };
+        let mut seen_duration = false;
+        for t in crate::css::split_value(value) {
+            if let Some(secs) = parse_time(&t) {
+                if seen_duration { spec.extra.push(t); } else { spec.duration = secs; seen_duration = true; }
+            } else if t == "infinite" {
+                spec.iterations = None;
+            } else if let Ok(n) = t.parse::<f64>() {
+                spec.iterations = Some(n);
+            } else if EASINGS.contains(&t.as_str()) || t.starts_with("cubic-bezier(") || t.starts_with("steps(") {
+                spec.easing = t;
+            } else if DIRECTIONS.contains(&t.as_str()) {
+                spec.direction = t;
+            } else if spec.name.is_empty() && !["none", "forwards", "backwards", "both", "paused", "running"].contains(&t.as_str()) {
+                spec.name = t;
+            } else {
+                spec.extra.push(t);
+            }
+        }
+        if spec.name.is_empty() { None } else { Some(spec) }
+    }
+
+    pub fn to_css(&self) -> String {
+        let mut parts = vec![self.name.clone(), format!("{}s", (self.duration * 1000.0).round() / 1000.0)];
+        if self.easing != "ease" { parts.push(self.easing.clone()); }
+        parts.push(match self.iterations {
+            None => "infinite".to_string(),
+            Some(n) => n.to_string(),
+        });
+        if self.direction != "normal" { parts.push(self.direction.clone()); }
+        parts.extend(self.extra.iter().cloned());
+        parts.join(" ")
+    }
+}
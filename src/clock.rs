@@ -0,0 +1,201 @@
+use regex::Regex;
+use std::path::Path;
+
+/// strftime tokens offered by the clock editor's token picker.
+pub const STRFTIME_TOKENS: &[(&str, &str)] = &[
+    ("%H", "Hour (00-23)"),
+    ("%I", "Hour (01-12)"),
+    ("%M", "Minute"),
+    ("%S", "Second"),
+    ("%p", "AM / PM"),
+    ("%a", "Weekday, short"),
+    ("%A", "Weekday"),
+    ("%d", "Day of month (01-31)"),
+    ("%e", "Day of month (1-31)"),
+    ("%b", "Month, short"),
+    ("%B", "Month"),
+    ("%m", "Month (01-12)"),
+    ("%y", "Year, two digits"),
+    ("%Y", "Year"),
+    ("%j", "Day of year"),
+    ("%V", "ISO week number"),
+    ("%F", "Date (YYYY-MM-DD)"),
+    ("%R", "Time (HH:MM)"),
+    ("%T", "Time (HH:MM:SS)"),
+    ("%Z", "Time zone name"),
+    ("%z", "UTC offset"),
+];
+
+/// Waybar placeholders that are not strftime tokens.
+pub const CLOCK_PLACEHOLDERS: &[(&str, &str)] = &[
+    ("{calendar}", "Calendar grid"),
+    ("{tz_list}", "Time in every listed timezone"),
+];
+
+const MONTHS: [&str; 12] = ["January", "February", "March", "April", "May", "June", "July", "August", "September", "October", "November", "December"];
+const WEEKDAYS: [&str; 7] = ["Mo", "Tu", "We", "Th", "Fr", "Sa", "Su"];
+
+/// Renders a clock format: `{:%H:%M}` through `strftime`, `{}` as `%H:%M`, and the
+/// `{calendar}` / `{tz_list}` placeholders with the given text.
+pub fn render_format(format: &str, strftime: &dyn Fn(&str) -> String, calendar: &str, tz_list: &str) -> String {
+    let re = Regex::new(r"\{:L?([^}]*)\}|\{\}|\{calendar\}|\{tz_list\}").unwrap();
+    re.replace_all(format, |c: &regex::Captures| match &c[0] {
+        "{}" => strftime("%H:%M"),
+        "{calendar}" => calendar.to_string(),
+        "{tz_list}" => tz_list.to_string(),
+        _ => strftime(&c[1]),
+    }).to_string()
+}
+
+/// Inserts `token` at char index `cursor`. Outside a `{:...}` field the token gets its own
+/// field. Returns the new text and the cursor position after the insertion.
+pub fn insert_token(text: &str, cursor: usize, token: &str) -> (String, usize) {
+    let chars: Vec<char> = text.chars().collect();
+    let cursor = cursor.min(chars.len());
+    let before: String = chars[..cursor].iter().collect();
+    let inside_field = before.rfind("{:").is_some_and(|open| !before[open..].contains('}'));
+    let insert = if inside_field || token.starts_with('{') { token.to_string() } else { format!("{{:{}}}", token) };
+    let after: String = chars[cursor..].iter().collect();
+    (format!("{}{}{}", before, insert, after), cursor + insert.chars().count())
+}
+
+/// A timezone name Waybar can load, checked against the system zoneinfo database.
+pub fn is_valid_timezone(name: &str) -> bool {
+    !name.is_empty()
+        && !name.contains("..")
+        && ["/usr/share/zoneinfo", "/usr/lib/zoneinfo", "/usr/share/lib/zoneinfo"]
+            .iter()
+            .any(|dir| Path::new(dir).join(name).is_file())
+}
+
+pub fn is_leap(y: i32) -> bool {
+    (y % 4 == 0 && y % 100 != 0) || y % 400 == 0
+}
+
+pub fn days_in_month(y: i32, m: u32) -> u32 {
+    match m {
+        2 if is_leap(y) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Day of the week, 0 = Monday.
+pub fn weekday(y: i32, m: u32, d: u32) -> u32 {
+    // Sakamoto's method (0 = Sunday), shifted to start on Monday.
+    let t = [0, 3, 2, 5, 0, 3, 5, 1, 4, 6, 2, 4];
+    let y = if m < 3 { y - 1 } else { y };
+    let sunday_based = (y + y / 4 - y / 100 + y / 400 + t[(m - 1) as usize] + d as i32).rem_euclid(7) as u32;
+    (sunday_based + 6) % 7
+}
+
+fn day_of_year(y: i32, m: u32, d: u32) -> u32 {
+    (1..m).map(|mm| days_in_month(y, mm)).sum::<u32>() + d
+}
+
+/// ISO 8601 week number.
+pub fn iso_week(y: i32, m: u32, d: u32) -> u32 {
+    let wd = weekday(y, m, d) as i32 + 1; // 1 = Monday
+    let week = (day_of_year(y, m, d) as i32 - wd + 10) / 7;
+    let weeks_in = |year: i32| {
+        let jan1 = weekday(year, 1, 1);
+        if jan1 == 3 || (jan1 == 2 && is_leap(year)) { 53 } else { 52 }
+    };
+    if week < 1 {
+        weeks_in(y - 1) as u32
+    } else if week > weeks_in(y) {
+        1
+    } else {
+        week as u32
+    }
+}
+
+/// The `calendar` options that change how the grid looks.
+#[derive(Debug, Clone, Default)]
+pub struct CalendarStyle {
+    pub year_mode: bool,
+    pub months_per_row: u32,
+    /// `Some(true)` for week numbers on the left, `Some(false)` on the right.
+    pub weeks_left: Option<bool>,
+    pub fmt_months: Option<String>,
+    pub fmt_days: Option<String>,
+    pub fmt_weeks: Option<String>,
+    pub fmt_weekdays: Option<String>,
+    pub fmt_today: Option<String>,
+}
+
+fn wrap(fmt: &Option<String>, text: &str) -> String {
+    match fmt {
+        Some(f) => f.replace("{}", text),
+        None => text.to_string(),
+    }
+}
+
+/// One month as lines of Pango markup, all of the same visible width.
+fn month_lines(year: i32, month: u32, today: (i32, u32, u32), style: &CalendarStyle) -> Vec<String> {
+    let week_col = style.weeks_left.is_some();
+    let width: usize = 20 + if week_col { 4 } else { 0 };
+    let mut lines = Vec::new();
+
+    let title = format!("{} {}", MONTHS[(month - 1) as usize], year);
+    let pad = width.saturating_sub(title.len());
+    lines.push(format!("{}{}{}", " ".repeat(pad / 2), wrap(&style.fmt_months, &title), " ".repeat(pad - pad / 2)));
+
+    let header = WEEKDAYS.iter().map(|d| wrap(&style.fmt_weekdays, d)).collect::<Vec<_>>().join(" ");
+    lines.push(match style.weeks_left {
+        Some(true) => format!("    {}", header),
+        Some(false) => format!("{}    ", header),
+        None => header,
+    });
+
+    let first = weekday(year, month, 1);
+    let days = days_in_month(year, month);
+    let mut day = 1;
+    while day <= days {
+        let mut cells = Vec::new();
+        let week_day = if day == 1 { 1 } else { day };
+        for col in 0..7 {
+            if (day == 1 && col < first) || day > days {
+                cells.push("  ".to_string());
+            } else {
+                let text = format!("{:>2}", day);
+                let fmt = if (year, month, day) == today { &style.fmt_today } else { &style.fmt_days };
+                cells.push(wrap(fmt, &text));
+                day += 1;
+            }
+        }
+        let row = cells.join(" ");
+        let week = wrap(&style.fmt_weeks, &format!("{:>2}", iso_week(year, month, week_day)));
+        lines.push(match style.weeks_left {
+            Some(true) => format!("{}  {}", week, row),
+            Some(false) => format!("{}  {}", row, week),
+            None => row,
+        });
+    }
+    lines
+}
+
+/// The grid Waybar shows for `{calendar}`: the current month, or the whole year laid out
+/// `months_per_row` months wide.
+pub fn render_calendar(today: (i32, u32, u32), style: &CalendarStyle) -> String {
+    if !style.year_mode {
+        return month_lines(today.0, today.1, today, style).join("\n");
+    }
+    let per_row = style.months_per_row.clamp(1, 12) as usize;
+    let months: Vec<Vec<String>> = (1..=12).map(|m| month_lines(today.0, m, today, style)).collect();
+    let width = 20 + if style.weeks_left.is_some() { 4 } else { 0 };
+    let mut out = Vec::new();
+    for chunk in months.chunks(per_row) {
+        let height = chunk.iter().map(|m| m.len()).max().unwrap_or(0);
+        for i in 0..height {
+            let line = chunk.iter()
+                .map(|m| m.get(i).cloned().unwrap_or_else(|| " ".repeat(width)))
+                .collect::<Vec<_>>()
+                .join("   ");
+            out.push(line);
+        }
+        out.push(String::new());
+    }
+    out.join("\n").trim_end().to_string()
+}
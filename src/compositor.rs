@@ -0,0 +1,93 @@
+use std::fs;
+use std::path::PathBuf;
+
+/// Compositors with their own Waybar modules (`hyprland/workspaces`, `sway/mode`...).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compositor {
+    Hyprland,
+    Sway,
+    Niri,
+    River,
+    Dwl,
+}
+
+impl Compositor {
+    pub const ALL: [Compositor; 5] = [Compositor::Hyprland, Compositor::Sway, Compositor::Niri, Compositor::River, Compositor::Dwl];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Compositor::Hyprland => "Hyprland",
+            Compositor::Sway => "Sway",
+            Compositor::Niri => "niri",
+            Compositor::River => "River",
+            Compositor::Dwl => "dwl",
+        }
+    }
+
+    /// Module name prefix, without the slash.
+    pub fn prefix(self) -> &'static str {
+        match self {
+            Compositor::Hyprland => "hyprland",
+            Compositor::Sway => "sway",
+            Compositor::Niri => "niri",
+            Compositor::River => "river",
+            Compositor::Dwl => "dwl",
+        }
+    }
+}
+
+fn runtime_dir() -> Option<PathBuf> {
+    std::env::var("XDG_RUNTIME_DIR").ok().map(PathBuf::from)
+}
+
+/// True when the runtime dir holds a file named `<prefix>…<suffix>` (e.g. `sway-ipc.1000.42.sock`).
+fn runtime_socket(prefix: &str, suffix: &str) -> bool {
+    let Some(dir) = runtime_dir() else { return false };
+    fs::read_dir(dir)
+        .map(|entries| entries.flatten().any(|e| {
+            let name = e.file_name().to_string_lossy().to_string();
+            name.starts_with(prefix) && name.ends_with(suffix)
+        }))
+        .unwrap_or(false)
+}
+
+/// The running compositor: first from the variables each one exports, then from
+/// `XDG_CURRENT_DESKTOP`, then from IPC sockets left in `$XDG_RUNTIME_DIR`.
+pub fn detect() -> Option<Compositor> {
+    let env = |k: &str| std::env::var(k).ok().filter(|v| !v.is_empty());
+    if env("HYPRLAND_INSTANCE_SIGNATURE").is_some() { return Some(Compositor::Hyprland); }
+    if env("SWAYSOCK").is_some() { return Some(Compositor::Sway); }
+    if env("NIRI_SOCKET").is_some() { return Some(Compositor::Niri); }
+
+    let desktop = [env("XDG_CURRENT_DESKTOP"), env("XDG_SESSION_DESKTOP")]
+        .into_iter().flatten().collect::<Vec<_>>().join(":").to_lowercase();
+    if let Some(c) = Compositor::ALL.iter().find(|c| desktop.split(':').any(|d| d == c.prefix())) {
+        return Some(*c);
+    }
+
+    if runtime_dir().is_some_and(|d| d.join("hypr").is_dir()) { return Some(Compositor::Hyprland); }
+    if runtime_socket("sway-ipc.", ".sock") { return Some(Compositor::Sway); }
+    if runtime_socket("niri.", ".sock") { return Some(Compositor::Niri); }
+    None
+}
+
+/// The compositor a module only works on, e.g. `sway/workspaces#2` -> Sway.
+pub fn module_target(module: &str) -> Option<Compositor> {
+    let base = module.split('#').next().unwrap_or(module);
+    let prefix = base.split_once('/')?.0;
+    Compositor::ALL.into_iter().find(|c| c.prefix() == prefix)
+}
+
+/// Orders the module catalog for `current`: its own modules first, then the generic ones,
+/// then modules for other compositors. Without a detected compositor the order is kept.
+pub fn rank_modules<'a>(options: &[&'a str], current: Option<Compositor>) -> Vec<&'a str> {
+    let Some(current) = current else { return options.to_vec() };
+    let rank = |m: &str| match module_target(m) {
+        Some(c) if c == current => 0,
+        None => 1,
+        Some(_) => 2,
+    };
+    let mut ranked = options.to_vec();
+    ranked.sort_by_key(|m| rank(m));
+    ranked
+}
@@ -4,6 +4,7 @@ use indexmap::IndexMap;
 use std::fs;
 use std::path::Path;
 use json_comments::StripComments;
+use crate::targets::TargetPaths;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct WaybarConfig {
@@ -14,13 +15,126 @@ pub struct WaybarConfig {
     #[serde(rename = "modules-right", default)]
     pub modules_right: Vec<String>,
 
-    #[serde(default)]
-    pub position: Option<String>,
-    
+    /// Bar-level options. Flattened before `module_definitions` so these keys are
+    /// taken out of the root object and never show up as modules.
+    #[serde(flatten)]
+    pub bar: BarSettings,
+
     #[serde(flatten)]
     pub module_definitions: IndexMap<String, Value>,
 }
 
+/// `output` accepts a single name or a list of names.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl OneOrMany {
+    pub fn to_vec(&self) -> Vec<String> {
+        match self {
+            OneOrMany::One(s) => vec![s.clone()],
+            OneOrMany::Many(v) => v.clone(),
+        }
+    }
+
+    /// `None` for an empty list and a plain string for a single monitor name. Waybar
+    /// compares a string literally, so `*` and exclusions stay in a list; a list matches
+    /// only through an entry that includes the monitor, so one holding nothing but
+    /// exclusions gets a trailing `*`.
+    pub fn from_vec(mut items: Vec<String>) -> Option<Self> {
+        items.retain(|s| !s.is_empty());
+        if !items.is_empty() && items.iter().all(|s| s.starts_with('!')) {
+            items.push("*".to_string());
+        }
+        match items.len() {
+            0 => None,
+            1 if !items[0].starts_with('*') => Some(OneOrMany::One(items.remove(0))),
+            _ => Some(OneOrMany::Many(items)),
+        }
+    }
+}
+
+/// `margin` is either one number for all sides or a CSS-like string such as `"5 10"`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum NumberOrString {
+    Number(i64),
+    Text(String),
+}
+
+impl NumberOrString {
+    /// A plain integer is written back as a number, anything else as a string.
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        if text.is_empty() { return None; }
+        Some(text.parse().map(NumberOrString::Number).unwrap_or_else(|_| NumberOrString::Text(text.to_string())))
+    }
+}
+
+impl std::fmt::Display for NumberOrString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NumberOrString::Number(n) => write!(f, "{}", n),
+            NumberOrString::Text(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+/// Top-level bar options from the Waybar man page. Unset fields are left out of the
+/// written config so Waybar's own defaults apply.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct BarSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layer: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<OneOrMany>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spacing: Option<u32>,
+    /// A number or CSS-like shorthand, e.g. `"5 10"`. The per-side keys below take precedence.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub margin: Option<NumberOrString>,
+    #[serde(rename = "margin-top", default, skip_serializing_if = "Option::is_none")]
+    pub margin_top: Option<i32>,
+    #[serde(rename = "margin-right", default, skip_serializing_if = "Option::is_none")]
+    pub margin_right: Option<i32>,
+    #[serde(rename = "margin-bottom", default, skip_serializing_if = "Option::is_none")]
+    pub margin_bottom: Option<i32>,
+    #[serde(rename = "margin-left", default, skip_serializing_if = "Option::is_none")]
+    pub margin_left: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exclusive: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub passthrough: Option<bool>,
+    #[serde(rename = "fixed-center", default, skip_serializing_if = "Option::is_none")]
+    pub fixed_center: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_hidden: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reload_style_on_change: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ipc: Option<bool>,
+}
+
+impl BarSettings {
+    /// Left and right bars stack their modules top to bottom.
+    pub fn is_vertical(&self) -> bool {
+        matches!(self.position.as_deref(), Some("left") | Some("right"))
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct WaybarProfile {
     pub config: WaybarConfig,
@@ -28,26 +142,134 @@ pub struct WaybarProfile {
     pub style_vars: IndexMap<String, String>,
     #[serde(default)]
     pub layout_css: String,
+    /// Further bars of a multi-monitor config; `config` is the first one.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra_bars: Vec<WaybarConfig>,
+    /// Files Apply writes for this profile.
+    #[serde(default, skip_serializing_if = "TargetPaths::is_default")]
+    pub targets: TargetPaths,
+}
+
+/// Every bar in a Waybar config file (a single object or an array of bars). The bar being
+/// edited is held outside, in the UI state; its slot here is refreshed by `switch_to`/`all`.
+#[derive(Debug, Clone, Default)]
+pub struct BarSet {
+    pub bars: Vec<WaybarConfig>,
+    pub active: usize,
+}
+
+impl BarSet {
+    pub fn new(bars: Vec<WaybarConfig>) -> Self {
+        BarSet { bars, active: 0 }
+    }
+
+    /// All bars with `current` in the active slot.
+    pub fn all(&self, current: &WaybarConfig) -> Vec<WaybarConfig> {
+        let mut bars = self.bars.clone();
+        match bars.get_mut(self.active) {
+            Some(slot) => *slot = current.clone(),
+            None => bars.push(current.clone()),
+        }
+        bars
+    }
+
+    /// Stores `current` and replaces it with bar `idx`.
+    pub fn switch_to(&mut self, idx: usize, current: &mut WaybarConfig) {
+        self.bars = self.all(current);
+        if let Some(bar) = self.bars.get(idx) {
+            *current = bar.clone();
+            self.active = idx;
+        }
+    }
+
+    pub fn add(&mut self, current: &WaybarConfig, bar: WaybarConfig) {
+        self.bars = self.all(current);
+        self.bars.push(bar);
+    }
+
+    /// Removes bar `idx`; the last bar cannot be removed. Removing the edited bar switches
+    /// to the first one.
+    pub fn remove(&mut self, idx: usize, current: &mut WaybarConfig) {
+        self.bars = self.all(current);
+        if self.bars.len() < 2 || idx >= self.bars.len() { return; }
+        self.bars.remove(idx);
+        if idx == self.active {
+            self.active = 0;
+            *current = self.bars[0].clone();
+        } else if idx < self.active {
+            self.active -= 1;
+        }
+    }
+
+    /// Display name: the bar's `name`, else its position in the file.
+    pub fn label(bar: &WaybarConfig, idx: usize) -> String {
+        match &bar.bar.name {
+            Some(n) => format!("Bar {} ({})", idx + 1, n),
+            None => format!("Bar {}", idx + 1),
+        }
+    }
 }
 
 impl WaybarConfig {
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
         let content = fs::read_to_string(path)?;
         let stripped = StripComments::new(content.as_bytes());
-        let config: WaybarConfig = serde_json::from_reader(stripped)?;
+        let value: Value = serde_json::from_reader(stripped)?;
+        Ok(Self::from_value(value)?)
+    }
+
+    /// Parses one bar. A bar option whose value doesn't have the type `BarSettings` expects
+    /// (`"height": "30"`) is kept as it is among the module definitions, so it is written
+    /// back unchanged instead of failing the whole file.
+    pub fn from_value(value: Value) -> Result<Self, serde_json::Error> {
+        let Value::Object(mut map) = value else { return serde_json::from_value(value) };
+        let mut stray = serde_json::Map::new();
+        let keys: Vec<String> = map.keys().cloned().collect();
+        for key in keys {
+            let single = Value::Object(serde_json::Map::from_iter([(key.clone(), map[&key].clone())]));
+            if serde_json::from_value::<BarSettings>(single).is_err() {
+                if let Some(v) = map.remove(&key) { stray.insert(key, v); }
+            }
+        }
+        let mut config: WaybarConfig = serde_json::from_value(Value::Object(map))?;
+        config.module_definitions.extend(stray);
         Ok(config)
     }
 
-    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn std::error::Error>> {
+    /// Reads every bar of a config file, whether it holds one bar object or an array.
+    pub fn load_all<P: AsRef<Path>>(path: P) -> Result<Vec<Self>, Box<dyn std::error::Error>> {
+        let content = fs::read_to_string(path)?;
+        let stripped = StripComments::new(content.as_bytes());
+        let value: Value = serde_json::from_reader(stripped)?;
+        match value {
+            Value::Array(items) => Ok(items.into_iter().map(Self::from_value).collect::<Result<_, _>>()?),
+            other => Ok(vec![Self::from_value(other)?]),
+        }
+    }
+
+    fn cleaned(&self) -> Self {
         let mut cleaned = self.clone();
         cleaned.modules_left.retain(|m| !m.is_empty());
         cleaned.modules_center.retain(|m| !m.is_empty());
         cleaned.modules_right.retain(|m| !m.is_empty());
-        
-        let json = serde_json::to_string_pretty(&cleaned)?;
+        cleaned
+    }
+
+    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn std::error::Error>> {
+        let json = serde_json::to_string_pretty(&self.cleaned())?;
         fs::write(path, json)?;
         Ok(())
     }
+
+    /// Writes a single bar as an object and several bars as an array.
+    pub fn save_all<P: AsRef<Path>>(bars: &[Self], path: P) -> Result<(), Box<dyn std::error::Error>> {
+        if let [only] = bars {
+            return only.save_to_file(path);
+        }
+        let cleaned: Vec<Self> = bars.iter().map(|b| b.cleaned()).collect();
+        fs::write(path, serde_json::to_string_pretty(&cleaned)?)?;
+        Ok(())
+    }
 }
 
 impl WaybarProfile {
@@ -65,6 +287,8 @@ impl WaybarProfile {
             config,
             style_vars: IndexMap::new(),
             layout_css: String::new(),
+            extra_bars: Vec::new(),
+            targets: TargetPaths::default(),
         })
     }
 
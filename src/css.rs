@@ -0,0 +1,468 @@
+use indexmap::IndexMap;
+use regex::Regex;
+
+/// Selector list covering every standard module box. Shared by the default layout and
+/// the generated metrics block so both target the same widgets.
+macro_rules! module_selectors {
+    () => {
+        "#clock, #cpu, #battery, #backlight, #pulseaudio, #network, #memory, #tray, #idle_inhibitor, #bluetooth, #cava, #disk, #temperature, #upower, #wireplumber, #mpris, #mpd, #backlight-slider, #pulseaudio-slider, #power-profiles-daemon, #privacy, #load, #jack, #sndio, #systemd-failed-units, #user,
+.clock, .battery, .cpu, .memory, .disk, .temperature, .backlight, .network, .pulseaudio, .tray, .workspaces, .mode, .window, .user, .load, .wireplumber, .mpris, .mpd, .bluetooth, .idle_inhibitor,
+#custom-launcher, #custom-power, #custom-weather-simple, #custom-chrome, #custom-terminal, #custom-code, #custom-music, #custom-thunar, #custom-clip, #custom-snip, #custom-theme-switcher, #custom-todo, #custom-countdown,
+.custom"
+    };
+}
+
+pub const MODULE_SELECTORS: &str = module_selectors!();
+const SECTION_SELECTORS: &str = ".modules-left, .modules-center, .modules-right";
+
+/// A layout metric stored in `StyleConfig.vars` and rendered as real CSS properties.
+pub struct Metric {
+    pub key: &'static str,
+    pub title: &'static str,
+    pub selector: &'static str,
+    pub props: &'static [&'static str],
+    pub min: f64,
+    pub max: f64,
+    pub default: f64,
+}
+
+pub const METRICS: &[Metric] = &[
+    Metric { key: "module_padding_x", title: "Module Padding (along the bar)", selector: MODULE_SELECTORS, props: &["padding-left", "padding-right"], min: 0.0, max: 40.0, default: 8.0 },
+    Metric { key: "module_padding_y", title: "Module Padding (across the bar)", selector: MODULE_SELECTORS, props: &["padding-top", "padding-bottom"], min: 0.0, max: 20.0, default: 4.0 },
+    Metric { key: "module_margin", title: "Module Spacing", selector: MODULE_SELECTORS, props: &["margin-left", "margin-right"], min: 0.0, max: 30.0, default: 4.0 },
+    Metric { key: "module_radius", title: "Module Radius", selector: MODULE_SELECTORS, props: &["border-radius"], min: 0.0, max: 30.0, default: 8.0 },
+    Metric { key: "section_margin", title: "Section Spacing", selector: SECTION_SELECTORS, props: &["margin-left", "margin-right"], min: 0.0, max: 40.0, default: 0.0 },
+    Metric { key: "font_size", title: "Font Size", selector: "*", props: &["font-size"], min: 6.0, max: 32.0, default: 13.0 },
+];
+
+const BLOCK_BEGIN: &str = "/* @waybarconf metrics begin */";
+const BLOCK_END: &str = "/* @waybarconf metrics end */";
+
+pub fn metric_value(vars: &IndexMap<String, String>, key: &str) -> Option<f64> {
+    vars.get(key).and_then(|v| v.trim_end_matches("px").trim().parse::<f64>().ok())
+}
+
+/// Renders the metrics that are set in `vars` as plain CSS rules. The props are written
+/// for a horizontal bar and rotated with `swap_side` when `vertical` is set.
+pub fn metrics_css(vars: &IndexMap<String, String>, vertical: bool) -> String {
+    let mut rules: IndexMap<&str, Vec<String>> = IndexMap::new();
+    for m in METRICS {
+        if let Some(v) = metric_value(vars, m.key) {
+            let decls = rules.entry(m.selector).or_default();
+            for p in m.props {
+                let prop = if vertical { swap_side(p).unwrap_or_else(|| p.to_string()) } else { p.to_string() };
+                decls.push(format!("    {}: {}px;", prop, v));
+            }
+        }
+    }
+    rules.iter()
+        .map(|(sel, decls)| format!("{} {{\n{}\n}}", sel, decls.join("\n")))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Writes the metrics block into `css`. An existing block is updated in place so rules
+/// written after it (per-module overrides) keep winning. A new block goes after the last
+/// `@import`, or before the first single-module override, or at the end.
+pub fn apply_metrics_block(css: &str, vars: &IndexMap<String, String>, vertical: bool) -> String {
+    let body = metrics_css(vars, vertical);
+    let block = if body.is_empty() { String::new() } else { format!("{}\n{}\n{}", BLOCK_BEGIN, body, BLOCK_END) };
+
+    if let (Some(start), Some(end)) = (css.find(BLOCK_BEGIN), css.find(BLOCK_END)) {
+        if start < end {
+            let mut out = css.to_string();
+            let mut range = start..end + BLOCK_END.len();
+            if block.is_empty() {
+                // Also drop the blank lines the block was inserted with.
+                if css[..range.start].ends_with("\n\n") { range.start -= 1; }
+                if css[range.end..].starts_with("\n\n") { range.end += 1; }
+            }
+            out.replace_range(range, &block);
+            return out;
+        }
+    }
+    if block.is_empty() {
+        return css.to_string();
+    }
+
+    let lines: Vec<&str> = css.lines().collect();
+    let insert_at = if let Some(i) = lines.iter().rposition(|l| l.trim_start().starts_with("@import")) {
+        i + 1
+    } else if let Some(i) = lines.iter().position(|l| is_single_module_rule(l)) {
+        i
+    } else {
+        lines.len()
+    };
+    let mut out: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
+    out.insert(insert_at, format!("\n{}\n", block));
+    out.join("\n")
+}
+
+/// The property on the other axis: `margin-left` <-> `margin-top`, `padding-right` <->
+/// `padding-bottom`, `border-left-width` <-> `border-top-width`, `min-height` <-> `min-width`.
+pub fn swap_side(prop: &str) -> Option<String> {
+    if prop == "min-height" { return Some("min-width".to_string()); }
+    if prop == "min-width" { return Some("min-height".to_string()); }
+    // Transposing the box maps the corners top-right <-> bottom-left; the other two stay.
+    if let Some(corner) = prop.strip_prefix("border-").and_then(|p| p.strip_suffix("-radius")) {
+        return match corner {
+            "top-right" => Some("border-bottom-left-radius".to_string()),
+            "bottom-left" => Some("border-top-right-radius".to_string()),
+            _ => None,
+        };
+    }
+    let (base, rest) = prop.split_once('-')?;
+    if !matches!(base, "margin" | "padding" | "border") { return None; }
+    let (side, suffix) = match rest.split_once('-') {
+        Some((side, suffix)) => (side, format!("-{}", suffix)),
+        None => (rest, String::new()),
+    };
+    let swapped = match side {
+        "left" => "top",
+        "top" => "left",
+        "right" => "bottom",
+        "bottom" => "right",
+        _ => return None,
+    };
+    Some(format!("{}-{}{}", base, swapped, suffix))
+}
+
+/// Rotates a `margin`/`padding` shorthand value to the other axis (`0 4px` -> `4px 0`).
+fn swap_shorthand(value: &str) -> String {
+    let v: Vec<&str> = value.split_whitespace().collect();
+    match v.len() {
+        2 => format!("{} {}", v[1], v[0]),
+        3 => format!("{} {} {} {}", v[1], v[2], v[1], v[0]),
+        4 => format!("{} {} {} {}", v[3], v[2], v[1], v[0]),
+        _ => value.to_string(),
+    }
+}
+
+/// Transposes a four-value `border-radius` (top-left, top-right, bottom-right, bottom-left)
+/// by swapping the top-right and bottom-left corners. Fewer values are symmetric under
+/// the swap; elliptical `a / b` radii are left alone.
+fn swap_radius_shorthand(value: &str) -> String {
+    let v: Vec<&str> = value.split_whitespace().collect();
+    if v.len() == 4 && !value.contains('/') {
+        format!("{} {} {} {}", v[0], v[3], v[2], v[1])
+    } else {
+        value.to_string()
+    }
+}
+
+/// Converts layout CSS written for a horizontal bar into its vertical equivalent (and back):
+/// per-side margin/padding/border props, corner radii and `min-height`/`min-width` trade
+/// places, and two- to four-value `margin`/`padding` and four-value `border-radius`
+/// shorthands are rotated.
+pub fn swap_axes(css: &str) -> String {
+    let re = Regex::new(r"(?m)(^|[\s{;])([a-z-]+)(\s*:\s*)((?:[^;{}\n]|\{\{|\}\})+);").unwrap();
+    re.replace_all(css, |c: &regex::Captures| {
+        let prop = &c[2];
+        let value = c[4].trim_end();
+        let (prop, value) = match prop {
+            "margin" | "padding" => (prop.to_string(), swap_shorthand(value)),
+            "border-radius" => (prop.to_string(), swap_radius_shorthand(value)),
+            _ => (swap_side(prop).unwrap_or_else(|| prop.to_string()), value.to_string()),
+        };
+        format!("{}{}{}{};", &c[1], prop, &c[3], value)
+    }).to_string()
+}
+
+/// `#clock {` / `#custom-foo:hover {` — the blocks written by the per-module editors.
+fn is_single_module_rule(line: &str) -> bool {
+    let t = line.trim();
+    t.starts_with('#') && t.ends_with('{') && !t.contains(',')
+}
+
+/// A CSS identifier, usable as a class or `@keyframes` name.
+pub fn is_identifier(name: &str) -> bool {
+    Regex::new(r"^-?[A-Za-z_][A-Za-z0-9_-]*$").unwrap().is_match(name)
+}
+
+/// The selector Waybar gives a module's widget: `custom/x` and `group/x` keep their prefix
+/// (`#custom-x`), compositor modules drop it (`hyprland/workspaces` is `#workspaces`), and a
+/// `#name` suffix becomes a class (`battery#bat2` is `#battery.bat2`).
+pub fn module_selector(module: &str) -> String {
+    let (base, id) = match module.split_once('#') {
+        Some((b, id)) => (b, Some(id)),
+        None => (module, None),
+    };
+    let name = match base.split_once('/') {
+        Some((prefix @ ("custom" | "group"), rest)) => format!("{}-{}", prefix, rest),
+        Some((_, rest)) => rest.to_string(),
+        None => base.to_string(),
+    };
+    match id {
+        Some(id) => format!("#{}.{}", name, id),
+        None => format!("#{}", name),
+    }
+}
+
+/// True when `line` opens the rule for exactly `selector` (`#clock {`, or `#clock` with the
+/// brace on the next line), so `#clock` does not match `#clock:hover` or a selector list.
+pub fn opens_rule(line: &str, selector: &str) -> bool {
+    match line.trim().strip_prefix(selector) {
+        Some(rest) => rest.trim().is_empty() || rest.trim_start().starts_with('{'),
+        None => false,
+    }
+}
+
+fn is_prop_line(line: &str, prop: &str) -> bool {
+    line.trim().strip_prefix(prop).is_some_and(|rest| rest.trim_start().starts_with(':'))
+}
+
+/// Line indices of the rule opened by `selector`: (selector line, closing brace line).
+fn find_rule(lines: &[String], selector: &str) -> Option<(usize, usize)> {
+    let start = lines.iter().enumerate().position(|(i, line)| {
+        opens_rule(line, selector) && (line.contains('{') || lines.get(i + 1).is_some_and(|n| n.trim_start().starts_with('{')))
+    })?;
+    let end = lines[start..].iter().position(|l| l.contains('}'))? + start;
+    Some((start, end))
+}
+
+/// Splits a one-line rule (`#clock { color: red; }`) into one declaration per line.
+fn expand_single_line_rule(lines: &mut Vec<String>, idx: usize) {
+    let line = lines[idx].clone();
+    if let (Some(open), Some(close)) = (line.find('{'), line.rfind('}')) {
+        if open < close {
+            let mut expanded = vec![format!("{}{{", &line[..open])];
+            for decl in line[open + 1..close].split(';').map(|d| d.trim()).filter(|d| !d.is_empty()) {
+                expanded.push(format!("    {};", decl));
+            }
+            expanded.push("}".to_string());
+            lines.splice(idx..=idx, expanded);
+        }
+    }
+}
+
+/// Value of `prop` inside the rule for exactly `selector`.
+pub fn get_rule_prop(css: &str, selector: &str, prop: &str) -> Option<String> {
+    let mut lines: Vec<String> = css.lines().map(|s| s.to_string()).collect();
+    let (start, end) = find_rule(&lines, selector)?;
+    if start == end {
+        expand_single_line_rule(&mut lines, start);
+        return get_rule_prop(&lines.join("\n"), selector, prop);
+    }
+    lines[start + 1..end].iter()
+        .find(|l| is_prop_line(l, prop))
+        .and_then(|l| l.split_once(':'))
+        .map(|(_, v)| v.trim().trim_end_matches(';').trim().to_string())
+}
+
+/// Line of `prop` within the rule for `selector`, to tell which of two declarations
+/// comes later.
+fn prop_index(css: &str, selector: &str, prop: &str) -> Option<usize> {
+    let mut lines: Vec<String> = css.lines().map(|s| s.to_string()).collect();
+    let (start, end) = find_rule(&lines, selector)?;
+    if start == end {
+        expand_single_line_rule(&mut lines, start);
+        return prop_index(&lines.join("\n"), selector, prop);
+    }
+    lines[start + 1..end].iter().position(|l| is_prop_line(l, prop))
+}
+
+/// Sets (or with an empty `value`, removes) `prop` in the rule for `selector`,
+/// creating the rule at the end of the stylesheet when needed.
+pub fn set_rule_prop(css: &str, selector: &str, prop: &str, value: &str) -> String {
+    let mut lines: Vec<String> = css.lines().map(|s| s.to_string()).collect();
+    if let Some((start, end)) = find_rule(&lines, selector) {
+        if start == end {
+            expand_single_line_rule(&mut lines, start);
+            return set_rule_prop(&lines.join("\n"), selector, prop, value);
+        }
+        let prop_idx = (start + 1..end).find(|&i| is_prop_line(&lines[i], prop));
+        if value.is_empty() {
+            if let Some(idx) = prop_idx { lines.remove(idx); }
+        } else {
+            let new_line = format!("    {}: {};", prop, value);
+            match prop_idx {
+                Some(idx) => lines[idx] = new_line,
+                None => lines.insert(end, new_line),
+            }
+        }
+    } else if !value.is_empty() {
+        lines.push(format!("\n{} {{\n    {}: {};\n}}", selector, prop, value));
+    }
+    lines.join("\n")
+}
+
+/// Removes the whole rule for exactly `selector`, with the blank line before it.
+pub fn remove_rule(css: &str, selector: &str) -> String {
+    let mut lines: Vec<String> = css.lines().map(|s| s.to_string()).collect();
+    if let Some((start, end)) = find_rule(&lines, selector) {
+        let start = if start > 0 && lines[start - 1].trim().is_empty() { start - 1 } else { start };
+        lines.drain(start..=end);
+    }
+    lines.join("\n")
+}
+
+/// Removes the rule for `selector` when it has no declarations left.
+pub fn remove_rule_if_empty(css: &str, selector: &str) -> String {
+    let lines: Vec<String> = css.lines().map(|s| s.to_string()).collect();
+    match find_rule(&lines, selector) {
+        Some((start, end)) if start < end && lines[start + 1..end].iter().all(|l| l.trim().is_empty() || l.trim() == "{") => remove_rule(css, selector),
+        _ => css.to_string(),
+    }
+}
+
+/// Renames class `old` to `new` in every selector under `scope` (`#group-x .old:hover`
+/// becomes `#group-x .new:hover`), leaving rules outside the scope alone.
+pub fn rename_class(css: &str, scope: &str, old: &str, new: &str) -> String {
+    let re = Regex::new(&format!(r"(?m)^(\s*{}\s[^{{\n]*\.){}([^\w-]|$)", regex::escape(scope), regex::escape(old))).unwrap();
+    re.replace_all(css, |c: &regex::Captures| format!("{}{}{}", &c[1], new, &c[2])).to_string()
+}
+
+/// Splits a property value on whitespace outside parentheses, so `rgba(0, 0, 0, 0.5)` and
+/// `cubic-bezier(...)` stay whole.
+pub fn split_value(value: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut cur = String::new();
+    let mut depth = 0;
+    for c in value.chars() {
+        match c {
+            '(' => { depth += 1; cur.push(c); }
+            ')' => { depth -= 1; cur.push(c); }
+            c if c.is_whitespace() && depth == 0 => {
+                if !cur.is_empty() { out.push(std::mem::take(&mut cur)); }
+            }
+            _ => cur.push(c),
+        }
+    }
+    if !cur.is_empty() { out.push(cur); }
+    out
+}
+
+/// The number at the start of a length (`-2.5px` -> -2.5, `0` -> 0).
+pub fn parse_length(value: &str) -> Option<f64> {
+    let re = Regex::new(r"^\s*(-?\d*\.?\d+)").unwrap();
+    re.captures(value).and_then(|c| c[1].parse().ok())
+}
+
+/// A single length as number and unit (`0.9em` -> 0.9, "em"; `0` -> 0, ""). None for
+/// anything else (`calc(...)`, `medium`, several values).
+pub fn split_length(value: &str) -> Option<(f64, String)> {
+    let re = Regex::new(r"^\s*(-?\d*\.?\d+)([a-zA-Z%]*)\s*$").unwrap();
+    let c = re.captures(value)?;
+    Some((c[1].parse().ok()?, c[2].to_lowercase()))
+}
+
+pub const SIDES: [&str; 4] = ["top", "right", "bottom", "left"];
+
+/// A `margin`/`padding` shorthand as top, right, bottom, left.
+pub fn expand_sides(value: &str) -> Option<[String; 4]> {
+    let v = split_value(value);
+    let [t, r, b, l] = match v.as_slice() {
+        [a] => [a, a, a, a],
+        [a, b] => [a, b, a, b],
+        [a, b, c] => [a, b, c, b],
+        [a, b, c, d] => [a, b, c, d],
+        _ => return None,
+    };
+    Some([t.clone(), r.clone(), b.clone(), l.clone()])
+}
+
+/// The shortest shorthand for four sides.
+pub fn collapse_sides(sides: &[String; 4]) -> String {
+    let [t, r, b, l] = sides;
+    if l != r {
+        format!("{} {} {} {}", t, r, b, l)
+    } else if t != b {
+        format!("{} {} {}", t, r, b)
+    } else if t != r {
+        format!("{} {}", t, r)
+    } else {
+        t.clone()
+    }
+}
+
+/// `border-radius` corners in shorthand order; the shorthand expands like the sides do.
+pub const CORNERS: [&str; 4] = ["top-left", "top-right", "bottom-right", "bottom-left"];
+
+/// The longhand for one side (`margin-top`) or, for `border-radius`, one corner
+/// (`border-top-left-radius`).
+fn side_longhand(prop: &str, side: usize) -> String {
+    if prop == "border-radius" {
+        format!("border-{}-radius", CORNERS[side])
+    } else {
+        format!("{}-{}", prop, SIDES[side])
+    }
+}
+
+/// One side of `margin`/`padding` (or corner of `border-radius`) in the rule for
+/// `selector`: from the longhand or the shorthand, whichever comes later in the rule.
+pub fn get_side(css: &str, selector: &str, prop: &str, side: usize) -> Option<String> {
+    let longhand = side_longhand(prop, side);
+    let from_shorthand = || get_rule_prop(css, selector, prop).and_then(|v| expand_sides(&v)).map(|s| s[side].clone());
+    match (prop_index(css, selector, &longhand), prop_index(css, selector, prop)) {
+        (Some(l), Some(s)) if s > l => from_shorthand().or_else(|| get_rule_prop(css, selector, &longhand)),
+        (Some(_), _) => get_rule_prop(css, selector, &longhand),
+        (None, _) => from_shorthand(),
+    }
+}
+
+/// Sets one side. A shorthand already in the rule is edited in place (and a longhand for
+/// the same side dropped); otherwise only the longhand is written.
+pub fn set_side(css: &str, selector: &str, prop: &str, side: usize, value: &str) -> String {
+    let longhand = side_longhand(prop, side);
+    match get_rule_prop(css, selector, prop).and_then(|v| expand_sides(&v)) {
+        Some(mut sides) => {
+            sides[side] = if value.is_empty() { "0".to_string() } else { value.to_string() };
+            let css = set_rule_prop(css, selector, prop, &collapse_sides(&sides));
+            set_rule_prop(&css, selector, &longhand, "")
+        }
+        None => set_rule_prop(css, selector, &longhand, value),
+    }
+}
+
+pub const BORDER_STYLES: &[&str] = &["none", "solid", "dashed", "dotted", "double", "groove", "ridge", "inset", "outset"];
+
+/// Which part of a `border` shorthand a token is: "width", "style" or "color".
+fn border_part_of(token: &str) -> &'static str {
+    if BORDER_STYLES.contains(&token) || token == "hidden" {
+        "style"
+    } else if parse_length(token).is_some() || ["thin", "medium", "thick"].contains(&token) {
+        "width"
+    } else {
+        "color"
+    }
+}
+
+/// `part` ("width", "style" or "color") of the border in the rule for `selector`, from
+/// `border-<part>` or the `border` shorthand.
+pub fn get_border_part(css: &str, selector: &str, part: &str) -> Option<String> {
+    get_rule_prop(css, selector, &format!("border-{}", part)).or_else(|| {
+        split_value(&get_rule_prop(css, selector, "border")?).into_iter().find(|t| border_part_of(t) == part)
+    })
+}
+
+/// Sets one part of the border, keeping a `border` shorthand as a shorthand. Giving a
+/// width or color to a border without a style makes it solid, so the change shows.
+pub fn set_border_part(css: &str, selector: &str, part: &str, value: &str) -> String {
+    let longhand = format!("border-{}", part);
+    match get_rule_prop(css, selector, "border") {
+        Some(shorthand) => {
+            let tokens = split_value(&shorthand);
+            let find = |p: &str| tokens.iter().find(|t| border_part_of(t) == p).cloned();
+            let (mut width, mut style, mut color) = (find("width"), find("style"), find("color"));
+            let new = if value.is_empty() { None } else { Some(value.to_string()) };
+            match part {
+                "width" => width = new,
+                "style" => style = new,
+                _ => color = new,
+            }
+            if part != "style" && value != "0" && !value.is_empty() && style.as_deref().is_none_or(|s| s == "none") {
+                style = Some("solid".to_string());
+            }
+            let joined = [width, style, color].into_iter().flatten().collect::<Vec<_>>().join(" ");
+            let css = set_rule_prop(css, selector, "border", if joined.is_empty() { "none" } else { &joined });
+            set_rule_prop(&css, selector, &longhand, "")
+        }
+        None => {
+            let mut css = set_rule_prop(css, selector, &longhand, value);
+            if part != "style" && !value.is_empty() && get_rule_prop(&css, selector, "border-style").is_none() {
+                css = set_rule_prop(&css, selector, "border-style", "solid");
+            }
+            css
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use serde::{Deserialize, Serialize};
+use indexmap::IndexMap;
+use regex::Regex;
+use std::fs;
+use std::path::PathBuf;
+use crate::palette::{hex_to_rgb, resolve_vars};
+
+/// A single palette export: a template rendered with the current style vars into `output`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExportTarget {
+    pub name: String,
+    /// Either a bundled template name (e.g. `rofi-colors.rasi`) or a path to a user template.
+    pub template: String,
+    pub output: String,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ExportSettings {
+    #[serde(default)]
+    pub export_on_apply: bool,
+    #[serde(default)]
+    pub targets: Vec<ExportTarget>,
+}
+
+fn default_true() -> bool { true }
+
+/// Bundled templates and the output path they are usually written to.
+pub const BUNDLED_TEMPLATES: &[(&str, &str, &str)] = &[
+    ("Rofi", "rofi-colors.rasi", "~/.config/rofi/shared/colors.rasi"),
+    ("Mako", "mako.conf", "~/.config/mako/colors"),
+    ("Kitty", "kitty-colors.conf", "~/.config/kitty/colors.conf"),
+    ("GTK", "gtk-colors.css", "~/.config/gtk-4.0/colors.css"),
+];
+
+pub fn app_config_dir() -> PathBuf {
+    std::env::var("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(std::env::var("HOME").unwrap_or_default()).join(".config"))
+        .join("waybarconf")
+}
+
+pub fn expand_home(path: &str) -> PathBuf {
+    if let Some(rest) = path.strip_prefix("~/") {
+        PathBuf::from(std::env::var("HOME").unwrap_or_default()).join(rest)
+    } else {
+        PathBuf::from(path)
+    }
+}
+
+impl ExportSettings {
+    fn path() -> PathBuf {
+        app_config_dir().join("exports.json")
+    }
+
+    pub fn load() -> Self {
+        fs::read_to_string(Self::path())
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let path = Self::path();
+        if let Some(dir) = path.parent() { fs::create_dir_all(dir)?; }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Renders every enabled target. Returns the written paths and any errors.
+    pub fn export_all(&self, vars: &IndexMap<String, String>) -> (Vec<PathBuf>, Vec<String>) {
+        let mut written = Vec::new();
+        let mut errors = Vec::new();
+        for target in self.targets.iter().filter(|t| t.enabled) {
+            match export_target(target, vars) {
+                Ok(p) => written.push(p),
+                Err(e) => errors.push(format!("{}: {}", target.name, e)),
+            }
+        }
+        (written, errors)
+    }
+}
+
+pub fn export_target(target: &ExportTarget, vars: &IndexMap<String, String>) -> Result<PathBuf, String> {
+    let template = if target.template.contains('/') {
+        let template_path = expand_home(&target.template);
+        fs::read_to_string(&template_path)
+            .map_err(|e| format!("Failed to read {}: {}", template_path.display(), e))?
+    } else {
+        crate::resources::find("templates", &target.template)
+            .and_then(|f| f.read())
+            .ok_or_else(|| format!("Template {} not found", target.template))?
+    };
+    let output = expand_home(&target.output);
+    if let Some(dir) = output.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let rendered = render_template(&template, vars)?;
+    fs::write(&output, rendered).map_err(|e| e.to_string())?;
+    Ok(output)
+}
+
+/// A palette value as `(r, g, b, alpha)`: hex, `rgb()`/`rgba()` or GTK's `alpha(color, f)`,
+/// with `@name` references followed. None for anything else (`shade()`, `mix()`, names).
+fn plain_color(value: &str, resolved: &IndexMap<String, String>, depth: usize) -> Option<(u8, u8, u8, f64)> {
+    let value = value.trim();
+    if depth > 8 { return None; }
+    if let Some(name) = value.strip_prefix('@') {
+        return plain_color(resolved.get(name)?, resolved, depth + 1);
+    }
+    if let Some(h) = value.strip_prefix('#') {
+        let (r, g, b) = hex_to_rgb(value)?;
+        let alpha = match h.len() {
+            8 => u8::from_str_radix(&h[6..], 16).ok()? as f64 / 255.0,
+            4 => u8::from_str_radix(&h[3..].repeat(2), 16).ok()? as f64 / 255.0,
+            _ => 1.0,
+        };
+        return Some((r, g, b, alpha));
+    }
+    let (func, args) = value.strip_suffix(')')?.split_once('(')?;
+    match func.trim() {
+        "rgb" | "rgba" => {
+            let parts: Vec<&str> = args.split(',').map(|p| p.trim()).collect();
+            if parts.len() != 3 && parts.len() != 4 { return None; }
+            let ch = |p: &str| p.parse::<f64>().ok().filter(|v| (0.0..=255.0).contains(v)).map(|v| v.round() as u8);
+            let alpha = match parts.get(3) {
+                Some(a) => a.parse::<f64>().ok()?.clamp(0.0, 1.0),
+                None => 1.0,
+            };
+            Some((ch(parts[0])?, ch(parts[1])?, ch(parts[2])?, alpha))
+        }
+        "alpha" => {
+            let (color, factor) = args.rsplit_once(',')?;
+            let (r, g, b, a) = plain_color(color, resolved, depth + 1)?;
+            Some((r, g, b, (a * factor.trim().parse::<f64>().ok()?).clamp(0.0, 1.0)))
+        }
+        _ => None,
+    }
+}
+
+/// Replaces `{{name}}` (`#rrggbb`, or `#rrggbbaa` when translucent), `{{name.strip}}` (the
+/// same without `#`) and `{{name.rgb}}` (`r, g, b`). `{{name|fallback}}` uses `fallback`
+/// (another var or a literal) when `name` is not defined. Placeholders that cannot be
+/// resolved are left untouched; a value that is not a plain color is an error, since the
+/// apps these files are written for only read hex.
+pub fn render_template(template: &str, vars: &IndexMap<String, String>) -> Result<String, String> {
+    let resolved = resolve_vars(vars);
+    let re = Regex::new(r"\{\{\s*([A-Za-z0-9_\-|#]+?)(?:\.(strip|rgb))?\s*\}\}").unwrap();
+    let mut error = None;
+    let out = re.replace_all(template, |caps: &regex::Captures| {
+        let mut alternatives = caps[1].split('|');
+        let name = alternatives.next().unwrap_or("");
+        let value = resolved.get(name).cloned().or_else(|| {
+            alternatives.next().map(|fb| resolved.get(fb).cloned().unwrap_or_else(|| fb.to_string()))
+        });
+        let Some(value) = value else { return caps[0].to_string() };
+        let Some((r, g, b, a)) = plain_color(&value, &resolved, 0) else {
+            error.get_or_insert_with(|| format!("{} is {}, which cannot be written as a hex color", name, value));
+            return caps[0].to_string();
+        };
+        let hex = if a < 1.0 {
+            format!("{:02x}{:02x}{:02x}{:02x}", r, g, b, (a * 255.0).round() as u8)
+        } else {
+            format!("{:02x}{:02x}{:02x}", r, g, b)
+        };
+        match caps.get(2).map(|m| m.as_str()) {
+            Some("strip") => hex,
+            Some("rgb") => format!("{}, {}, {}", r, g, b),
+            _ => format!("#{}", hex),
+        }
+    }).to_string();
+    match error {
+        Some(e) => Err(e),
+        None => Ok(out),
+    }
+}
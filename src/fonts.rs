@@ -0,0 +1,185 @@
+use std::process::Command;
+use std::sync::OnceLock;
+use crate::config::WaybarConfig;
+
+/// An installed font family as fontconfig reports it.
+#[derive(Debug, Clone)]
+pub struct FontFamily {
+    pub name: String,
+    pub mono: bool,
+    pub nerd: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FamilyFilter {
+    All,
+    Monospace,
+    NerdFonts,
+}
+
+impl FamilyFilter {
+    pub const ALL: [FamilyFilter; 3] = [FamilyFilter::All, FamilyFilter::Monospace, FamilyFilter::NerdFonts];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            FamilyFilter::All => "All Fonts",
+            FamilyFilter::Monospace => "Monospace",
+            FamilyFilter::NerdFonts => "Nerd Fonts",
+        }
+    }
+
+    pub fn accepts(self, f: &FontFamily) -> bool {
+        match self {
+            FamilyFilter::All => true,
+            FamilyFilter::Monospace => f.mono,
+            FamilyFilter::NerdFonts => f.nerd,
+        }
+    }
+}
+
+pub fn is_nerd_font(name: &str) -> bool {
+    name.contains("Nerd Font") || name.ends_with(" NF") || name.contains(" NF ") || name.ends_with(" NFM") || name.ends_with(" NFP")
+}
+
+/// Installed families from `fc-list`, sorted and without duplicates. Fontconfig is only
+/// asked once per run; an empty list means it is not available.
+pub fn families() -> &'static [FontFamily] {
+    static FAMILIES: OnceLock<Vec<FontFamily>> = OnceLock::new();
+    FAMILIES.get_or_init(|| {
+        let Ok(out) = Command::new("fc-list").args(["--format", "%{family[0]}\t%{spacing}\n"]).output() else { return Vec::new() };
+        let mut list: Vec<FontFamily> = Vec::new();
+        for line in String::from_utf8_lossy(&out.stdout).lines() {
+            let (name, spacing) = line.split_once('\t').unwrap_or((line, ""));
+            let name = name.trim();
+            if name.is_empty() { continue; }
+            // spacing 100 = mono, 90 = dual width (most Nerd Font "Mono" variants)
+            let mono = spacing == "100" || spacing == "90";
+            match list.iter_mut().find(|f| f.name == name) {
+                Some(f) => f.mono |= mono,
+                None => list.push(FontFamily { name: name.to_string(), mono, nerd: is_nerd_font(name) }),
+            }
+        }
+        list.sort_by_key(|f| f.name.to_lowercase());
+        list
+    })
+}
+
+/// Family names of a `font-family` value, in order, without quotes.
+pub fn font_stack(value: &str) -> Vec<String> {
+    value.split(',')
+        .map(|f| f.trim().trim_matches(|c| c == '"' || c == '\'').to_string())
+        .filter(|f| !f.is_empty())
+        .collect()
+}
+
+/// `font-family` value with `family` in front of the existing stack.
+pub fn with_primary(value: Option<&str>, family: &str) -> String {
+    let quote = |f: &str| if f.contains(' ') { format!("\"{}\"", f) } else { f.to_string() };
+    let mut out = vec![quote(family)];
+    for f in value.map(font_stack).unwrap_or_default() {
+        if f != family { out.push(quote(&f)); }
+    }
+    out.join(", ")
+}
+
+/// Non-ASCII characters used in `format*` options and `format-icons`, each with the
+/// modules that use it, in order of first use.
+pub fn glyphs_in_config(cfg: &WaybarConfig) -> Vec<(char, Vec<String>)> {
+    fn collect(v: &serde_json::Value, module: &str, out: &mut Vec<(char, Vec<String>)>) {
+        match v {
+            serde_json::Value::String(s) => {
+                for c in s.chars().filter(|c| !c.is_ascii() && !c.is_whitespace()) {
+                    match out.iter_mut().find(|(g, _)| *g == c) {
+                        Some((_, mods)) => if !mods.iter().any(|m| m == module) { mods.push(module.to_string()) },
+                        None => out.push((c, vec![module.to_string()])),
+                    }
+                }
+            }
+            serde_json::Value::Array(items) => items.iter().for_each(|i| collect(i, module, out)),
+            serde_json::Value::Object(map) => map.values().for_each(|i| collect(i, module, out)),
+            _ => {}
+        }
+    }
+    let mut out = Vec::new();
+    for (module, def) in &cfg.module_definitions {
+        let Some(obj) = def.as_object() else { continue };
+        for (key, value) in obj {
+            if key.starts_with("format") || key.starts_with("tooltip-format") {
+                collect(value, module, &mut out);
+            }
+        }
+    }
+    out
+}
+
+/// How a glyph will be drawn with the chosen font stack.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Coverage {
+    InStack(String),
+    /// Not in the stack, but this installed family (the first by name) has it, so
+    /// fontconfig will fall back to one of the families that do.
+    Fallback(String),
+    /// No installed font has it: it shows as a box (tofu).
+    Missing,
+}
+
+/// Families with the code point ranges (inclusive) each one covers.
+type Charsets = Vec<(String, Vec<(u32, u32)>)>;
+
+/// Every installed family with the code point ranges it covers, from a single `fc-list`
+/// run that is cached for the rest of the session. None when fontconfig cannot be asked.
+fn charsets() -> Option<&'static Charsets> {
+    static CHARSETS: OnceLock<Option<Charsets>> = OnceLock::new();
+    CHARSETS.get_or_init(|| {
+        let out = Command::new("fc-list").args(["--format", "%{family[0]}\t%{charset}\n"]).output().ok()?;
+        if !out.status.success() { return None; }
+        let mut list: Charsets = Vec::new();
+        for line in String::from_utf8_lossy(&out.stdout).lines() {
+            let Some((name, charset)) = line.split_once('\t') else { continue };
+            let name = name.trim();
+            if name.is_empty() { continue; }
+            // Ranges of hex code points: "20-7e a0-17f 2022".
+            let ranges = charset.split_whitespace().filter_map(|r| {
+                let (lo, hi) = r.split_once('-').unwrap_or((r, r));
+                Some((u32::from_str_radix(lo, 16).ok()?, u32::from_str_radix(hi, 16).ok()?))
+            });
+            match list.iter_mut().find(|(n, _)| n == name) {
+                Some((_, r)) => r.extend(ranges),
+                None => list.push((name.to_string(), ranges.collect())),
+            }
+        }
+        Some(list)
+    }).as_ref()
+}
+
+/// Families that contain `c`, or None when fontconfig cannot be asked.
+fn families_with(c: char) -> Option<Vec<&'static str>> {
+    let c = c as u32;
+    Some(charsets()?.iter()
+        .filter(|(_, ranges)| ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi))
+        .map(|(name, _)| name.as_str())
+        .collect())
+}
+
+/// The stack with generic names (`monospace`, `sans-serif`...) replaced by the family
+/// fontconfig picks for them.
+pub fn resolve_stack(stack: &[String]) -> Vec<String> {
+    stack.iter().map(|f| {
+        if !["monospace", "sans-serif", "sans", "serif", "system-ui"].contains(&f.as_str()) { return f.clone(); }
+        Command::new("fc-match").args(["--format", "%{family[0]}", f]).output().ok()
+            .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(|| f.clone())
+    }).collect()
+}
+
+pub fn coverage(c: char, stack: &[String]) -> Option<Coverage> {
+    let with = families_with(c)?;
+    if let Some(f) = stack.iter().find(|f| with.iter().any(|w| w.eq_ignore_ascii_case(f))) {
+        return Some(Coverage::InStack(f.clone()));
+    }
+    Some(match with.into_iter().min_by_key(|f| f.to_lowercase()) {
+        Some(f) => Coverage::Fallback(f.to_string()),
+        None => Coverage::Missing,
+    })
+}
@@ -0,0 +1,119 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use crate::export::app_config_dir;
+
+/// Bundled name table: `category<TAB>name<TAB>codepoint` per line, names as on the
+/// Nerd Fonts cheat sheet.
+const GLYPH_TABLE: &str = include_str!("../presets/glyphs.tsv");
+
+const RECENT_LIMIT: usize = 24;
+
+#[derive(Debug, Clone)]
+pub struct Glyph {
+    pub category: &'static str,
+    pub name: &'static str,
+    pub glyph: char,
+}
+
+pub fn all() -> &'static [Glyph] {
+    static GLYPHS: OnceLock<Vec<Glyph>> = OnceLock::new();
+    GLYPHS.get_or_init(|| {
+        GLYPH_TABLE.lines()
+            .filter(|l| !l.trim().is_empty() && !l.starts_with('#'))
+            .filter_map(|l| {
+                let mut parts = l.split('\t');
+                let (category, name, code) = (parts.next()?, parts.next()?, parts.next()?);
+                let glyph = char::from_u32(u32::from_str_radix(code.trim(), 16).ok()?)?;
+                Some(Glyph { category, name, glyph })
+            })
+            .collect()
+    })
+}
+
+/// Categories in table order.
+pub fn categories() -> Vec<&'static str> {
+    let mut out: Vec<&'static str> = Vec::new();
+    for g in all() {
+        if !out.contains(&g.category) { out.push(g.category); }
+    }
+    out
+}
+
+/// Glyphs whose name (or category) contains every word of `query`, with names where each
+/// word starts a part of the name ("arch" → `archlinux` before `search`) listed first.
+/// `_` and `-` are interchangeable, so "battery-charging" and "battery_charging" both match.
+pub fn search(query: &str, category: Option<&str>) -> Vec<&'static Glyph> {
+    let norm = |s: &str| s.to_lowercase().replace('_', "-");
+    let terms: Vec<String> = query.split_whitespace().map(norm).collect();
+    let mut found: Vec<(&'static Glyph, bool)> = all().iter()
+        .filter(|g| category.is_none_or(|c| g.category == c))
+        .filter_map(|g| {
+            let hay = format!("{} {}", norm(g.name), g.category.to_lowercase());
+            if !terms.iter().all(|t| hay.contains(t.as_str())) { return None; }
+            let at_start = terms.iter().all(|t| hay.split(['-', ' ']).any(|w| w.starts_with(t.as_str())));
+            Some((g, at_start))
+        })
+        .collect();
+    found.sort_by_key(|(_, at_start)| !at_start);
+    found.into_iter().map(|(g, _)| g).collect()
+}
+
+/// Inserts `glyph` at char index `cursor`. Returns the new text and the cursor position
+/// after the insertion.
+pub fn insert_glyph(text: &str, cursor: usize, glyph: &str) -> (String, usize) {
+    let chars: Vec<char> = text.chars().collect();
+    let cursor = cursor.min(chars.len());
+    let before: String = chars[..cursor].iter().collect();
+    let after: String = chars[cursor..].iter().collect();
+    (format!("{}{}{}", before, glyph, after), cursor + glyph.chars().count())
+}
+
+pub fn by_name(name: &str) -> Option<char> {
+    all().iter().find(|g| g.name == name).map(|g| g.glyph)
+}
+
+pub fn name_of(c: char) -> Option<&'static str> {
+    all().iter().find(|g| g.glyph == c).map(|g| g.name)
+}
+
+/// Ramps for `format-icons` arrays, lowest value first as Waybar expects.
+pub const ICON_SETS: &[(&str, &[&str])] = &[
+    ("Battery · 5 steps", &["nf-fa-battery_empty", "nf-fa-battery_quarter", "nf-fa-battery_half", "nf-fa-battery_three_quarters", "nf-fa-battery_full"]),
+    ("Battery · 11 steps", &["nf-md-battery_outline", "nf-md-battery_10", "nf-md-battery_20", "nf-md-battery_30", "nf-md-battery_40", "nf-md-battery_50", "nf-md-battery_60", "nf-md-battery_70", "nf-md-battery_80", "nf-md-battery_90", "nf-md-battery"]),
+    ("Battery charging · 10 steps", &["nf-md-battery_charging_10", "nf-md-battery_charging_20", "nf-md-battery_charging_30", "nf-md-battery_charging_40", "nf-md-battery_charging_50", "nf-md-battery_charging_60", "nf-md-battery_charging_70", "nf-md-battery_charging_80", "nf-md-battery_charging_90", "nf-md-battery_charging_100"]),
+    ("Wi-Fi strength · 5 steps", &["nf-md-wifi_strength_outline", "nf-md-wifi_strength_1", "nf-md-wifi_strength_2", "nf-md-wifi_strength_3", "nf-md-wifi_strength_4"]),
+    ("Volume · 3 steps", &["nf-md-volume_low", "nf-md-volume_medium", "nf-md-volume_high"]),
+    ("Volume · 3 steps (Font Awesome)", &["nf-fa-volume_off", "nf-fa-volume_down", "nf-fa-volume_up"]),
+    ("Brightness · 3 steps", &["nf-md-brightness_5", "nf-md-brightness_6", "nf-md-brightness_7"]),
+    ("Temperature · 5 steps", &["nf-fa-thermometer_empty", "nf-fa-thermometer_quarter", "nf-fa-thermometer_half", "nf-fa-thermometer_three_quarters", "nf-fa-thermometer_full"]),
+    ("Hourglass · 3 steps", &["nf-fa-hourglass_start", "nf-fa-hourglass_half", "nf-fa-hourglass_end"]),
+];
+
+pub fn icon_set(names: &[&str]) -> Vec<String> {
+    names.iter().filter_map(|n| by_name(n)).map(|c| c.to_string()).collect()
+}
+
+fn recent_path() -> PathBuf {
+    app_config_dir().join("recent_glyphs.json")
+}
+
+/// Recently inserted glyphs, newest first.
+pub fn load_recent() -> Vec<String> {
+    fs::read_to_string(recent_path())
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+/// Moves `glyph` to the front of the recent list and saves it.
+pub fn push_recent(glyph: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut recent = load_recent();
+    recent.retain(|g| g != glyph);
+    recent.insert(0, glyph.to_string());
+    recent.truncate(RECENT_LIMIT);
+    let path = recent_path();
+    if let Some(dir) = path.parent() { fs::create_dir_all(dir)?; }
+    fs::write(path, serde_json::to_string_pretty(&recent)?)?;
+    Ok(())
+}
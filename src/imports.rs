@@ -0,0 +1,112 @@
+use indexmap::IndexMap;
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImportStatus {
+    Ok,
+    Missing,
+    /// Already imported earlier in the graph; not followed again.
+    Repeated,
+}
+
+/// A stylesheet in the `@import` graph of `style.css`.
+#[derive(Debug, Clone)]
+pub struct ImportNode {
+    pub path: PathBuf,
+    pub status: ImportStatus,
+    /// `@define-color` names and values in this file, in order.
+    pub defines: Vec<(String, String)>,
+    pub children: Vec<ImportNode>,
+}
+
+fn strip_comments(css: &str) -> String {
+    Regex::new(r"(?s)/\*.*?\*/").unwrap().replace_all(css, "").to_string()
+}
+
+/// Targets of the `@import` rules, as written (`"colors.css"`, `url(...)`).
+pub fn import_specs(css: &str) -> Vec<String> {
+    let re = Regex::new(r#"@import\s+(?:url\(\s*)?["']?([^"')\s;]+)["']?\s*\)?[^;]*;"#).unwrap();
+    re.captures_iter(&strip_comments(css)).map(|c| c[1].to_string()).collect()
+}
+
+/// The file an import points at: `~/` and `file://` are expanded, relative paths are taken
+/// from the importing file's directory like GTK does.
+pub fn resolve(base_dir: &Path, spec: &str) -> PathBuf {
+    let spec = spec.strip_prefix("file://").unwrap_or(spec);
+    if let Some(rest) = spec.strip_prefix("~/") {
+        return PathBuf::from(std::env::var("HOME").unwrap_or_default()).join(rest);
+    }
+    let p = Path::new(spec);
+    if p.is_absolute() { p.to_path_buf() } else { base_dir.join(p) }
+}
+
+pub fn define_colors(css: &str) -> Vec<(String, String)> {
+    let re = Regex::new(r"@define-color\s+([\w-]+)\s+([^;]+);").unwrap();
+    re.captures_iter(&strip_comments(css)).map(|c| (c[1].to_string(), c[2].trim().to_string())).collect()
+}
+
+fn node(path: PathBuf, content: &str, base_dir: &Path, seen: &mut Vec<PathBuf>) -> ImportNode {
+    let children = import_specs(content).into_iter().map(|spec| {
+        let target = resolve(base_dir, &spec);
+        let key = target.canonicalize().unwrap_or_else(|_| target.clone());
+        if seen.contains(&key) {
+            return ImportNode { path: target, status: ImportStatus::Repeated, defines: Vec::new(), children: Vec::new() };
+        }
+        seen.push(key);
+        match fs::read_to_string(&target) {
+            Ok(text) => {
+                let dir = target.parent().map(|d| d.to_path_buf()).unwrap_or_default();
+                node(target, &text, &dir, seen)
+            }
+            Err(_) => ImportNode { path: target, status: ImportStatus::Missing, defines: Vec::new(), children: Vec::new() },
+        }
+    }).collect();
+    ImportNode { path, status: ImportStatus::Ok, defines: define_colors(content), children }
+}
+
+/// The import graph of a stylesheet whose text is `content`. `path` is what the root is
+/// shown as; imports resolve against `base_dir`, so a session copy can stand in for the
+/// real file.
+pub fn tree_from(path: &Path, content: &str, base_dir: &Path) -> ImportNode {
+    let mut seen = vec![path.canonicalize().unwrap_or_else(|_| path.to_path_buf())];
+    node(path.to_path_buf(), content, base_dir, &mut seen)
+}
+
+pub fn build_tree(path: &Path) -> ImportNode {
+    match fs::read_to_string(path) {
+        Ok(content) => tree_from(path, &content, path.parent().unwrap_or(Path::new("."))),
+        Err(_) => ImportNode { path: path.to_path_buf(), status: ImportStatus::Missing, defines: Vec::new(), children: Vec::new() },
+    }
+}
+
+/// Every color variable in the graph with its value and the file that defines it. Imports
+/// come before a file's own rules, so a later definition wins as in GTK.
+pub fn definitions(tree: &ImportNode) -> IndexMap<String, (String, PathBuf)> {
+    fn walk(n: &ImportNode, out: &mut IndexMap<String, (String, PathBuf)>) {
+        for c in &n.children { walk(c, out); }
+        for (name, value) in &n.defines {
+            out.shift_remove(name);
+            out.insert(name.clone(), (value.clone(), n.path.clone()));
+        }
+    }
+    let mut out = IndexMap::new();
+    walk(tree, &mut out);
+    out
+}
+
+/// `content` with the `@define-color` of `name` set to `value`.
+pub fn set_define(content: &str, name: &str, value: &str) -> String {
+    let re = Regex::new(&format!(r"(@define-color\s+{}\s+)[^;]+;", regex::escape(name))).unwrap();
+    re.replace_all(content, |c: &regex::Captures| format!("{}{};", &c[1], value)).to_string()
+}
+
+/// `path` with the home directory shown as `~`.
+pub fn display_path(path: &Path) -> String {
+    let home = std::env::var("HOME").unwrap_or_default();
+    match path.strip_prefix(&home) {
+        Ok(rest) if !home.is_empty() => format!("~/{}", rest.display()),
+        _ => path.display().to_string(),
+    }
+}
@@ -0,0 +1,120 @@
+use indexmap::IndexMap;
+use regex::Regex;
+use crate::resources::{self, PresetFile};
+
+const HEADER_START: &str = "/* @waybarconf-layout";
+
+/// A tunable value declared in a layout header:
+/// `param: key | Label | min | max | default`, used in the body as `{{key}}`.
+#[derive(Debug, Clone)]
+pub struct LayoutParam {
+    pub key: String,
+    pub label: String,
+    pub min: f64,
+    pub max: f64,
+    pub default: f64,
+}
+
+/// A layout from the `layouts` presets. Files without a metadata header are plain CSS
+/// templates with no parameters.
+#[derive(Debug, Clone)]
+pub struct LayoutTemplate {
+    pub file_name: String,
+    pub name: String,
+    pub description: String,
+    pub params: Vec<LayoutParam>,
+    body: String,
+}
+
+impl LayoutTemplate {
+    pub fn load(file: &PresetFile) -> Option<Self> {
+        let content = file.read()?;
+        let mut tpl = LayoutTemplate {
+            file_name: file.name.clone(),
+            name: file.stem().to_string(),
+            description: String::new(),
+            params: Vec::new(),
+            body: content.clone(),
+        };
+
+        let trimmed = content.trim_start();
+        if let Some(rest) = trimmed.strip_prefix(HEADER_START) {
+            if let Some(end) = rest.find("*/") {
+                for line in rest[..end].lines() {
+                    let Some((key, value)) = line.split_once(':') else { continue };
+                    let value = value.trim();
+                    match key.trim() {
+                        "name" => tpl.name = value.to_string(),
+                        "description" => tpl.description = value.to_string(),
+                        "param" => {
+                            let f: Vec<&str> = value.split('|').map(|s| s.trim()).collect();
+                            if f.len() == 5 {
+                                let num = |s: &str| s.parse::<f64>().unwrap_or(0.0);
+                                tpl.params.push(LayoutParam { key: f[0].to_string(), label: f[1].to_string(), min: num(f[2]), max: num(f[3]), default: num(f[4]) });
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                tpl.body = rest[end + 2..].trim_start_matches('\n').to_string();
+            }
+        }
+        Some(tpl)
+    }
+
+    /// Style var that stores a parameter value, e.g. `layout_pill_radius`.
+    pub fn param_var(&self, key: &str) -> String {
+        let stem = self.file_name.trim_end_matches(".css").replace('-', "_");
+        format!("layout_{}_{}", stem, key)
+    }
+
+    pub fn param_value(&self, param: &LayoutParam, vars: &IndexMap<String, String>) -> f64 {
+        vars.get(&self.param_var(&param.key))
+            .and_then(|v| v.trim_end_matches("px").parse::<f64>().ok())
+            .unwrap_or(param.default)
+    }
+
+    /// Palette roles (`@bar_bg`, `@hover_fg`...) referenced by the layout, in order of use.
+    pub fn roles(&self) -> Vec<String> {
+        let re = Regex::new(r"@([A-Za-z_][A-Za-z0-9_]*)").unwrap();
+        let mut roles: Vec<String> = Vec::new();
+        for cap in re.captures_iter(&self.body) {
+            let name = cap[1].to_string();
+            if name != "import" && name != "keyframes" && !roles.contains(&name) {
+                roles.push(name);
+            }
+        }
+        roles
+    }
+
+    /// Value the template itself gives `prop` in the rule for `selector`, if any.
+    pub fn rule_prop(&self, selector: &str, prop: &str, vars: &IndexMap<String, String>) -> Option<String> {
+        crate::css::get_rule_prop(&self.render(vars), selector, prop)
+    }
+
+    /// The CSS Waybar should load: header stripped and `{{param}}` replaced by its value.
+    pub fn render(&self, vars: &IndexMap<String, String>) -> String {
+        let mut css = format!("/* Layout: {} */\n\n{}", self.name, self.body);
+        for p in &self.params {
+            let v = self.param_value(p, vars);
+            let text = if v.fract() == 0.0 { format!("{}", v as i64) } else { format!("{}", v) };
+            css = css.replace(&format!("{{{{{}}}}}", p.key), &text);
+        }
+        css
+    }
+}
+
+/// Layouts from every preset layer (see `resources::list`).
+pub fn list_layouts() -> Vec<LayoutTemplate> {
+    resources::list("layouts").iter()
+        .filter(|f| f.extension() == Some("css"))
+        .filter_map(LayoutTemplate::load)
+        .collect()
+}
+
+/// The template imported by the session stylesheet (`@import "layouts/<file>";`).
+pub fn active_layout(session_css: &str) -> Option<LayoutTemplate> {
+    let re = Regex::new(r#"@import\s+"layouts/([^"]+)";"#).unwrap();
+    let file = re.captures(session_css)?.get(1)?.as_str().to_string();
+    LayoutTemplate::load(&resources::find("layouts", &file)?)
+}
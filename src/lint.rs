@@ -0,0 +1,49 @@
+use crate::compositor::{self, Compositor};
+use crate::config::WaybarConfig;
+
+/// A problem with a module placed on the bar.
+#[derive(Debug, Clone)]
+pub struct LintWarning {
+    pub module: String,
+    pub message: String,
+}
+
+/// Every module on the bar, including the children of groups.
+pub fn placed_modules(cfg: &WaybarConfig) -> Vec<String> {
+    fn walk(cfg: &WaybarConfig, modules: &[String], out: &mut Vec<String>) {
+        for m in modules {
+            if out.contains(m) { continue; }
+            out.push(m.clone());
+            if m.starts_with("group/") {
+                let children: Vec<String> = cfg.module_definitions.get(m)
+                    .and_then(|d| d.get("modules"))
+                    .and_then(|v| v.as_array())
+                    .map(|a| a.iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect())
+                    .unwrap_or_default();
+                walk(cfg, &children, out);
+            }
+        }
+    }
+    let mut out = Vec::new();
+    for column in [&cfg.modules_left, &cfg.modules_center, &cfg.modules_right] {
+        walk(cfg, column, &mut out);
+    }
+    out
+}
+
+pub fn lint_config(cfg: &WaybarConfig, current: Option<Compositor>) -> Vec<LintWarning> {
+    let mut warnings = Vec::new();
+    if let Some(current) = current {
+        for m in placed_modules(cfg) {
+            if let Some(target) = compositor::module_target(&m) {
+                if target != current {
+                    warnings.push(LintWarning {
+                        message: format!("{} only works on {}, but {} is running", m, target.label(), current.label()),
+                        module: m,
+                    });
+                }
+            }
+        }
+    }
+    warnings
+}
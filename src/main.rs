@@ -1,4 +1,24 @@
+#[macro_use]
+mod animation;
+mod clock;
+mod compositor;
 mod config;
+#[macro_use]
+mod css;
+mod export;
+mod fonts;
+mod glyphs;
+mod imports;
+mod layout;
+mod lint;
+mod outputs;
+mod palette;
+mod recolor;
+mod resources;
+mod states;
+mod targets;
+mod tooltip;
+mod workspaces;
 
 use libadwaita as adw;
 use gtk4 as gtk;
@@ -14,7 +34,10 @@ use std::process::Command;
 use adw::prelude::*;
 use adw::{ActionRow, Application, ApplicationWindow, HeaderBar, ViewStack, ViewSwitcher, PreferencesGroup, ToastOverlay, Toast, MessageDialog, ComboRow};
 use gtk::{Box as GtkBox, ListBox, Orientation, Label, ScrolledWindow, TextView, Entry, Switch, Button, ColorButton, FileDialog, FileFilter, StringList, SearchEntry, Scale};
-use crate::config::{WaybarConfig, WaybarProfile};
+use crate::config::{BarSet, WaybarConfig, WaybarProfile};
+use crate::export::{ExportSettings, ExportTarget};
+use crate::palette::{parse_style_vars, PresetSource};
+use crate::targets::TargetPaths;
 use serde::{Deserialize, Serialize};
 
 const DEFAULT_CONFIG_JSON: &str = r#"{
@@ -40,11 +63,7 @@ const DEFAULT_STYLE_VARS: &str = r#"/* WaybarConf Style Variables */
 
 "#;
 
-const DEFAULT_LAYOUT_CSS: &str = r#"/* WaybarConf Layout CSS */
-#clock, #cpu, #battery, #backlight, #pulseaudio, #network, #memory, #tray, #idle_inhibitor, #bluetooth, #cava, #disk, #temperature, #upower, #wireplumber, #mpris, #mpd, #backlight-slider, #pulseaudio-slider, #power-profiles-daemon, #privacy, #load, #jack, #sndio, #systemd-failed-units, #user,
-.clock, .battery, .cpu, .memory, .disk, .temperature, .backlight, .network, .pulseaudio, .tray, .workspaces, .mode, .window, .user, .load, .wireplumber, .mpris, .mpd, .bluetooth, .idle_inhibitor,
-#custom-launcher, #custom-power, #custom-weather-simple, #custom-chrome, #custom-terminal, #custom-code, #custom-music, #custom-thunar, #custom-clip, #custom-snip, #custom-theme-switcher, #custom-todo, #custom-countdown,
-.custom {
+const DEFAULT_LAYOUT_CSS: &str = concat!("/* WaybarConf Layout CSS */\n", module_selectors!(), r#" {
     padding: 4px 8px;
     margin: 0 4px;
     background: @module_bg;
@@ -53,68 +72,10 @@ const DEFAULT_LAYOUT_CSS: &str = r#"/* WaybarConf Layout CSS */
     transition: all 0.3s ease;
 }
 
-@keyframes blink {
-    to {
-        background-color: rgba(255, 255, 255, 0.1);
-        color: @module_fg;
-    }
-}
-
-@keyframes glow_pulse {
-    0% { background-color: @module_bg; }
-    50% { background-color: @hover_bg; }
-    100% { background-color: @module_bg; }
-}
-
-@keyframes lift {
-    to { margin-top: -2px; }
-}
-
-@keyframes bounce {
-    0% { margin-top: 0; }
-    50% { margin-top: -5px; }
-    100% { margin-top: 0; }
-}
-
-@keyframes shiver {
-    0% { margin-left: 0; }
-    25% { margin-left: -2px; }
-    75% { margin-left: 2px; }
-    100% { margin-left: 0; }
-}
-
-@keyframes rainbow {
-    0% { color: #ff0000; }
-    16% { color: #ff7f00; }
-    33% { color: #ffff00; }
-    50% { color: #00ff00; }
-    66% { color: #0000ff; }
-    83% { color: #4b0082; }
-    100% { color: #9400d3; }
-}
-
-@keyframes spin {
-    from { margin-left: 0; }
-    to { margin-left: 0.1px; } 
-}
-
-@keyframes shake {
-    0% { margin-left: 0; }
-    10% { margin-left: -4px; }
-    30% { margin-left: 4px; }
-    50% { margin-left: -4px; }
-    70% { margin-left: 4px; }
-    90% { margin-left: -4px; }
-    100% { margin-left: 0; }
-}
-"#;
-
-const ICON_LIST: &[&str] = &[
-    "󰣇", "󰀻", "󰀕", "󰍛", "󰘚", "", "", "", "", "", "", "", "󰋊", "󰝚", "󰂄", "", "", "󰊠", "󰀘", "󰀯",
-    "", "󰠮", "󰂚", "󰵗", "󰦈", "󰃠", "󰃡", "󰖩", "󰖪", "󰤨", "󰤭", "󰥔", "󰥒", "󰥓", "󰥖", "󰥑", "󰥕", "󰥐"
-];
+"#, builtin_keyframes!());
 
 fn main() {
+    resources::register().expect("Failed to register bundled presets");
     let application = Application::builder()
         .application_id("com.github.waybarconf")
         .build();
@@ -128,6 +89,9 @@ fn main() {
 struct StyleConfig {
     vars: indexmap::IndexMap<String, String>,
     path: PathBuf,
+    /// Variables defined in another file of the `style.css` import graph. Changes to them
+    /// are written back there instead of into `path`.
+    sources: indexmap::IndexMap<String, PathBuf>,
 }
 
 impl StyleConfig {
@@ -137,7 +101,23 @@ impl StyleConfig {
         } else {
             indexmap::IndexMap::new()
         };
-        Self { vars, path: path.to_path_buf() }
+        Self { vars, path: path.to_path_buf(), sources: indexmap::IndexMap::new() }
+    }
+
+    /// Takes every `@define-color` reachable through the imports of `layout` (a stylesheet)
+    /// along with the file defining it. Definitions in `layout` itself are attributed to
+    /// `session`, the copy that gets edited and applied.
+    fn adopt_imports(&mut self, layout: &Path, session: &Path) {
+        let own = self.path.canonicalize().unwrap_or_else(|_| self.path.clone());
+        self.sources.clear();
+        for (name, (value, src)) in imports::definitions(&imports::build_tree(layout)) {
+            self.vars.insert(name.clone(), value);
+            if src == layout {
+                self.sources.insert(name, session.to_path_buf());
+            } else if src.canonicalize().unwrap_or_else(|_| src.clone()) != own {
+                self.sources.insert(name, src);
+            }
+        }
     }
 
     fn save(&self) -> std::io::Result<()> {
@@ -147,19 +127,29 @@ impl StyleConfig {
     fn save_to(&self, path: &Path) -> std::io::Result<()> {
         let mut content = String::from("/* WaybarConf Style Variables */\n\n");
         let mut metrics = Vec::new();
+        let mut external: indexmap::IndexMap<&PathBuf, Vec<(&String, &String)>> = indexmap::IndexMap::new();
         for (k, v) in &self.vars {
-            if v.ends_with("px") || v.parse::<f64>().is_ok() {
+            if let Some(src) = self.sources.get(k) {
+                external.entry(src).or_default().push((k, v));
+            } else if palette::is_metric(v) {
                 metrics.push((k, v));
             } else {
                 content.push_str(&format!("@define-color {} {};\n", k, v));
             }
         }
+        // GTK3 CSS has no custom properties; metrics are kept as comments so the file stays
+        // valid and are rendered as real properties by `css::apply_metrics_block`.
         if !metrics.is_empty() {
-            content.push_str("\n* {\n");
+            content.push('\n');
             for (k, v) in metrics {
-                content.push_str(&format!("    --{}: {};\n", k, v));
+                content.push_str(&format!("/* --{}: {}; */\n", k, v));
             }
-            content.push_str("}\n");
+        }
+        // Only touch a defining file when one of its values actually changed.
+        for (src, defs) in external {
+            let Ok(old) = fs::read_to_string(src) else { continue };
+            let new = defs.iter().fold(old.clone(), |acc, (k, v)| imports::set_define(&acc, k, v));
+            if new != old { fs::write(src, new)?; }
         }
         fs::write(path, content)
     }
@@ -192,43 +182,6 @@ fn detect_wallpaper() -> Option<String> {
     None
 }
 
-fn get_waybar_config_path() -> Option<PathBuf> {
-    let home = std::env::var("HOME").ok()?;
-    let xdg_config = std::env::var("XDG_CONFIG_HOME")
-        .map(PathBuf::from)
-        .unwrap_or_else(|_| PathBuf::from(home).join(".config"));
-    
-    let paths = vec![
-        xdg_config.join("waybar/config.jsonc"),
-        xdg_config.join("waybar/config"),
-    ];
-    
-    paths.into_iter().find(|p| p.exists())
-}
-
-fn parse_style_vars(content: &str) -> indexmap::IndexMap<String, String> {
-    let mut vars = indexmap::IndexMap::new();
-    for line in content.lines() {
-        let line = line.trim();
-        if line.starts_with("@define-color") {
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.len() >= 3 {
-                let name = parts[1].to_string();
-                let value = parts[2].trim_matches(';').to_string();
-                vars.insert(name, value);
-            }
-        } else if line.starts_with("--") {
-            let parts: Vec<&str> = line.split(':').collect();
-            if parts.len() >= 2 {
-                let name = parts[0].trim_matches('-').trim().to_string();
-                let value = parts[1].trim_matches(';').trim().to_string();
-                vars.insert(name, value);
-            }
-        }
-    }
-    vars
-}
-
 fn apply_matugen(path: &str, scheme_type: &str, style_rc: Rc<RefCell<StyleConfig>>) -> Result<(), String> {
     let output = Command::new("matugen")
         .args(["image", path, "-j", "hex", "--type", scheme_type])
@@ -261,98 +214,40 @@ fn apply_matugen(path: &str, scheme_type: &str, style_rc: Rc<RefCell<StyleConfig
     Ok(())
 }
 
-fn ensure_keyframes(lines: &mut Vec<String>) {
-    let css = lines.join("\n");
-    if !css.contains("@keyframes rainbow") {
-        lines.push("\n/* Animation Keyframes */".to_string());
-        lines.push("@keyframes blink { to { background-color: rgba(255, 255, 255, 0.1); color: @module_fg; } }".to_string());
-        lines.push("@keyframes glow_pulse { 0% { background-color: @module_bg; } 50% { background-color: @hover_bg; } 100% { background-color: @module_bg; } }".to_string());
-        lines.push("@keyframes lift { to { margin-top: -2px; } }".to_string());
-        lines.push("@keyframes bounce { 0% { margin-top: 0; } 50% { margin-top: -5px; } 100% { margin-top: 0; } }".to_string());
-        lines.push("@keyframes wobble { 0% { margin-left: 0; } 25% { margin-left: -3px; } 75% { margin-left: 3px; } 100% { margin-left: 0; } }".to_string());
-        lines.push("@keyframes shake { 0% { margin-left: 0; } 10% { margin-left: -4px; } 30% { margin-left: 4px; } 50% { margin-left: -4px; } 70% { margin-left: 4px; } 90% { margin-left: -4px; } 100% { margin-left: 0; } }".to_string());
-        lines.push("@keyframes shiver { 0% { margin-left: 0; } 25% { margin-left: -2px; } 75% { margin-left: 2px; } 100% { margin-left: 0; } }".to_string());
-        lines.push("@keyframes rainbow { 0% { color: #ff0000; } 16% { color: #ff7f00; } 33% { color: #ffff00; } 50% { color: #00ff00; } 66% { color: #0000ff; } 83% { color: #4b0082; } 100% { color: #9400d3; } }".to_string());
-    }
-}
-
 fn update_module_css(path: &Path, mod_name: &str, suffix: &str, prop: &str, value: &str) {
-    let id = format!("#{}{}", mod_name.replace("/", "-"), suffix);
+    let id = format!("{}{}", css::module_selector(mod_name), suffix);
     let full_css = fs::read_to_string(path).unwrap_or_else(|_| DEFAULT_LAYOUT_CSS.to_string());
-    let mut lines: Vec<String> = full_css.lines().map(|s| s.to_string()).collect();
-    
-    ensure_keyframes(&mut lines);
-    
-    let mut block_start = None;
-    let mut block_end = None;
-    for (i, line) in lines.iter().enumerate() {
-        if line.trim().starts_with(&id) && (line.contains('{') || i + 1 < lines.len() && lines[i+1].contains('{')) {
-            block_start = Some(i);
-        }
-        if block_start.is_some() && line.contains('}') {
-            block_end = Some(i);
-            break;
-        }
-    }
-
-    if let (Some(start), Some(end)) = (block_start, block_end) {
-        let mut prop_idx = None;
-        for i in start + 1..end {
-            if lines[i].trim().starts_with(prop) {
-                prop_idx = Some(i);
-                break;
-            }
-        }
-
-        if value.is_empty() {
-             if let Some(idx) = prop_idx { lines.remove(idx); }
-        } else {
-            let new_line = format!("    {}: {};", prop, value);
-            if let Some(idx) = prop_idx {
-                lines[idx] = new_line;
-            } else {
-                lines.insert(end, new_line);
-            }
-        }
-    } else if !value.is_empty() {
-        lines.push(format!("\n{} {{\n    {}: {};\n}}", id, prop, value));
-    }
-
-    let _ = fs::write(path, lines.join("\n"));
+    let full_css = animation::ensure_builtin(&full_css);
+    let _ = fs::write(path, css::set_rule_prop(&full_css, &id, prop, value));
 }
 
 fn get_module_css_prop(path: &Path, mod_name: &str, suffix: &str, prop: &str) -> Option<String> {
-    let id = format!("#{}{}", mod_name.replace("/", "-"), suffix);
+    let id = format!("{}{}", css::module_selector(mod_name), suffix);
     let full_css = fs::read_to_string(path).ok()?;
-    let lines: Vec<&str> = full_css.lines().collect();
-    
-    let mut block_start = None;
-    for (i, line) in lines.iter().enumerate() {
-        if line.trim().starts_with(&id) && (line.contains('{') || i + 1 < lines.len() && lines[i+1].contains('{')) {
-            block_start = Some(i);
-            break;
-        }
-    }
+    css::get_rule_prop(&full_css, &id, prop)
+}
 
-    if let Some(start) = block_start {
-        for line in &lines[start + 1..] {
-            if line.contains('}') { break; }
-            if line.trim().starts_with(prop) {
-                let val = line.split(':').collect::<Vec<&str>>()[1].trim().trim_matches(';').to_string();
-                return Some(val);
-            }
-        }
-    }
-    None
+/// Same as `update_module_css` for an arbitrary selector (`window#waybar`, `.modules-left`...).
+fn update_selector_css(path: &Path, selector: &str, prop: &str, value: &str) {
+    let full_css = fs::read_to_string(path).unwrap_or_default();
+    let _ = fs::write(path, css::set_rule_prop(&full_css, selector, prop, value));
+}
+
+fn get_selector_css_prop(path: &Path, selector: &str, prop: &str) -> Option<String> {
+    let full_css = fs::read_to_string(path).ok()?;
+    css::get_rule_prop(&full_css, selector, prop)
 }
 
 fn build_ui(app: &Application) {
     let waybar_config: WaybarConfig = serde_json::from_str(DEFAULT_CONFIG_JSON).unwrap();
     let config_rc = Rc::new(RefCell::new(waybar_config));
+    // All bars of the config; the one being edited lives in `config_rc`.
+    let bars_rc = Rc::new(RefCell::new(BarSet::new(vec![config_rc.borrow().clone()])));
     
     let style_vars = parse_style_vars(DEFAULT_STYLE_VARS);
-    let default_style_path = PathBuf::from(std::env::var("HOME").unwrap_or_default()).join(".config/waybar/colors/wallpaper.css");
-    let style_rc = Rc::new(RefCell::new(StyleConfig { vars: style_vars, path: default_style_path }));
+    let style_rc = Rc::new(RefCell::new(StyleConfig { vars: style_vars, path: TargetPaths::default_palette(), sources: indexmap::IndexMap::new() }));
+    // Where Apply writes; part of each profile.
+    let targets_rc = Rc::new(RefCell::new(TargetPaths::default()));
     
     // Use a temporary path for session CSS
     let layout_css_path = std::env::temp_dir().join("waybarconf_style.css");
@@ -376,8 +271,13 @@ fn build_ui(app: &Application) {
     let apply_btn = Button::with_label("Apply");
     apply_btn.add_css_class("accent");
     header.pack_start(&apply_btn);
+    header.pack_start(&target_paths_button(&targets_rc, &style_rc));
 
     main_box.append(&header);
+
+    // Config linter results for the edited bar, refreshed with the columns.
+    let lint_banner = adw::Banner::new("");
+    main_box.append(&lint_banner);
     
     let paned = gtk::Paned::new(Orientation::Horizontal);
     paned.set_wide_handle(true);
@@ -421,6 +321,14 @@ fn build_ui(app: &Application) {
     view_stack.add_titled(&styles_scroll, Some("styles"), "Styles");
     view_stack.add_titled(&code_page, Some("code"), "Code");
 
+    let outputs_page = GtkBox::new(Orientation::Vertical, 12);
+    outputs_page.set_margin_top(12);
+    outputs_page.set_margin_bottom(12);
+    outputs_page.set_margin_start(12);
+    outputs_page.set_margin_end(12);
+    let outputs_scroll = ScrolledWindow::builder().child(&outputs_page).vexpand(true).build();
+    view_stack.add_titled(&outputs_scroll, Some("outputs"), "Outputs");
+
     // --- Gallery Page ---
     let gallery_page = GtkBox::new(Orientation::Vertical, 12);
     gallery_page.set_margin_top(12);
@@ -440,6 +348,8 @@ fn build_ui(app: &Application) {
 
     settings_panel.append(&view_stack);
 
+    // Column headers follow the bar orientation: Left/Center/Right or Top/Center/Bottom.
+    let column_labels = [Label::new(Some("Left")), Label::new(Some("Center")), Label::new(Some("Right"))];
     let left_list = ListBox::new();
     left_list.add_css_class("boxed-list");
     let center_list = ListBox::new();
@@ -459,6 +369,8 @@ fn build_ui(app: &Application) {
         "user", "cffi", "custom/new-module"
     ];
 
+    let running_compositor = compositor::detect();
+
     let update_properties_fn = Rc::new(RefCell::new(None::<Box<dyn Fn(String)>>));
     let refresh_ui_fn: Rc<RefCell<Option<Box<dyn Fn()>>>> = Rc::new(RefCell::new(None));
 
@@ -467,6 +379,8 @@ fn build_ui(app: &Application) {
         let left_list = left_list.clone();
         let center_list = center_list.clone();
         let right_list = right_list.clone();
+        let column_labels = column_labels.clone();
+        let lint_banner = lint_banner.clone();
         let update_props_ref = Rc::clone(&update_properties_fn);
         let sel_state = Rc::clone(&selected_module_state);
         let refresh_ui_fn_c = Rc::clone(&refresh_ui_fn);
@@ -478,9 +392,16 @@ fn build_ui(app: &Application) {
                                  update_cb: &Rc<RefCell<Option<Box<dyn Fn(String)>>>>, 
                                  sel_s: &Rc<RefCell<Option<(String, String)>>>,
                                  config_rc: &Rc<RefCell<WaybarConfig>>,
-                                 refresh_ui_fn: &Rc<RefCell<Option<Box<dyn Fn()>>>>) {
+                                 refresh_ui_fn: &Rc<RefCell<Option<Box<dyn Fn()>>>>,
+                                 warnings: &[lint::LintWarning]) {
                 for m in modules {
                     let row = create_module_row(m, depth);
+                    for w in warnings.iter().filter(|w| &w.module == m) {
+                        let icon = gtk::Image::from_icon_name("dialog-warning-symbolic");
+                        icon.add_css_class("warning");
+                        icon.set_tooltip_text(Some(&w.message));
+                        row.add_suffix(&icon);
+                    }
                     let name = m.clone();
                     let cid = col_id.to_string();
                     let update_cb_c = Rc::clone(update_cb);
@@ -522,21 +443,34 @@ fn build_ui(app: &Application) {
                         if let Some(def) = cfg.module_definitions.get(m) {
                             if let Some(children) = def.get("modules").and_then(|v| v.as_array()) {
                                 let child_names: Vec<String> = children.iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect();
-                                populate_recursive(list, &child_names, col_id, depth + 1, cfg, update_cb, sel_s, config_rc, refresh_ui_fn);
+                                populate_recursive(list, &child_names, col_id, depth + 1, cfg, update_cb, sel_s, config_rc, refresh_ui_fn, warnings);
                             }
                         }
                     }
                 }
             }
 
+            let names = if config.bar.is_vertical() { ["Top", "Center", "Bottom"] } else { ["Left", "Center", "Right"] };
+            for (label, name) in column_labels.iter().zip(names) { label.set_text(name); }
+
             while let Some(child) = left_list.first_child() { left_list.remove(&child); }
             while let Some(child) = center_list.first_child() { center_list.remove(&child); }
             while let Some(child) = right_list.first_child() { right_list.remove(&child); }
 
+            let warnings = lint::lint_config(&config, running_compositor);
+            match warnings.as_slice() {
+                [] => lint_banner.set_revealed(false),
+                [only] => { lint_banner.set_title(&glib::markup_escape_text(&only.message)); lint_banner.set_revealed(true); }
+                [first, rest @ ..] => {
+                    lint_banner.set_title(&glib::markup_escape_text(&format!("{} (and {} more)", first.message, rest.len())));
+                    lint_banner.set_revealed(true);
+                }
+            }
+
             let r_fn = Rc::clone(&refresh_ui_fn_c);
-            populate_recursive(&left_list, &config.modules_left, "left", 0, &config, &update_props_ref, &sel_state, &config_rc, &r_fn);
-            populate_recursive(&center_list, &config.modules_center, "center", 0, &config, &update_props_ref, &sel_state, &config_rc, &r_fn);
-            populate_recursive(&right_list, &config.modules_right, "right", 0, &config, &update_props_ref, &sel_state, &config_rc, &r_fn);
+            populate_recursive(&left_list, &config.modules_left, "left", 0, &config, &update_props_ref, &sel_state, &config_rc, &r_fn, &warnings);
+            populate_recursive(&center_list, &config.modules_center, "center", 0, &config, &update_props_ref, &sel_state, &config_rc, &r_fn, &warnings);
+            populate_recursive(&right_list, &config.modules_right, "right", 0, &config, &update_props_ref, &sel_state, &config_rc, &r_fn, &warnings);
         }
     };
 
@@ -559,93 +493,91 @@ fn build_ui(app: &Application) {
         move |filter: &str| {
             while let Some(child) = list.first_child() { list.remove(&child); }
             
-            let bricks_dir = PathBuf::from("presets/bricks");
-            if let Ok(entries) = fs::read_dir(bricks_dir) {
-                let mut bricks: Vec<ModuleBrick> = Vec::new();
-                for entry in entries.flatten() {
-                    if let Ok(content) = fs::read_to_string(entry.path()) {
-                        if let Ok(brick) = serde_json::from_str::<ModuleBrick>(&content) {
-                            if filter.is_empty() || brick.name.to_lowercase().contains(&filter.to_lowercase()) {
-                                bricks.push(brick);
-                            }
+            let mut bricks: Vec<ModuleBrick> = Vec::new();
+            for file in resources::list("bricks").iter().filter(|f| f.extension() == Some("json")) {
+                if let Some(content) = file.read() {
+                    if let Ok(brick) = serde_json::from_str::<ModuleBrick>(&content) {
+                        if filter.is_empty() || brick.name.to_lowercase().contains(&filter.to_lowercase()) {
+                            bricks.push(brick);
                         }
                     }
                 }
-                bricks.sort_by(|a, b| a.name.cmp(&b.name));
+            }
+            bricks.sort_by(|a, b| a.name.cmp(&b.name));
+            
+            for brick in bricks {
+                let row = ActionRow::new();
+                row.set_title(&brick.name);
+                row.set_subtitle(&brick.description);
                 
-                for brick in bricks {
-                    let row = ActionRow::new();
-                    row.set_title(&brick.name);
-                    row.set_subtitle(&brick.description);
+                // Icon logic (simple check for now)
+                let icon_name = if gtk::IconTheme::for_display(&gdk::Display::default().unwrap()).has_icon(&brick.icon) {
+                    brick.icon.clone()
+                } else {
+                    "application-x-addon-symbolic".to_string()
+                };
+                row.add_prefix(&gtk::Image::from_icon_name(&icon_name));
+                
+                let add_btn = Button::builder().icon_name("list-add-symbolic").has_frame(false).valign(gtk::Align::Center).build();
+                let b_cfg = Rc::clone(&config_rc);
+                let b_ref = Rc::clone(&refresh_rc);
+                let b_sel = Rc::clone(&sel_state);
+                let b_brick = brick.clone();
+                let b_toast = toast_g.clone();
+                
+                add_btn.connect_clicked(move |_| {
+                    let mut cfg = b_cfg.borrow_mut();
                     
-                    // Icon logic (simple check for now)
-                    let icon_name = if gtk::IconTheme::for_display(&gdk::Display::default().unwrap()).has_icon(&brick.icon) {
-                        brick.icon.clone()
+                    // 1. Determine ID
+                    let safe_name = b_brick.name.to_lowercase()
+                        .replace(" ", "-")
+                        .chars()
+                        .filter(|c| c.is_alphanumeric() || *c == '-' || *c == '_')
+                        .collect::<String>();
+                        
+                    let base_id = if b_brick.module_type == "custom" {
+                        format!("custom/{}", safe_name)
                     } else {
-                        "application-x-addon-symbolic".to_string()
+                        format!("{}#{}", b_brick.module_type, safe_name)
                     };
-                    row.add_prefix(&gtk::Image::from_icon_name(&icon_name));
                     
-                    let add_btn = Button::builder().icon_name("list-add-symbolic").has_frame(false).valign(gtk::Align::Center).build();
-                    let b_cfg = Rc::clone(&config_rc);
-                    let b_ref = Rc::clone(&refresh_rc);
-                    let b_sel = Rc::clone(&sel_state);
-                    let b_brick = brick.clone();
-                    let b_toast = toast_g.clone();
+                    let mut final_id = base_id.clone();
+                    let mut counter = 1;
+                    while cfg.module_definitions.contains_key(&final_id) {
+                        final_id = format!("{}-{}", base_id, counter);
+                        counter += 1;
+                    }
                     
-                    add_btn.connect_clicked(move |_| {
-                        let mut cfg = b_cfg.borrow_mut();
-                        
-                        // 1. Determine ID
-                        let safe_name = b_brick.name.to_lowercase()
-                            .replace(" ", "-")
-                            .chars()
-                            .filter(|c| c.is_alphanumeric() || *c == '-' || *c == '_')
-                            .collect::<String>();
-                            
-                        let base_id = if b_brick.module_type == "custom" {
-                            format!("custom/{}", safe_name)
-                        } else {
-                            format!("{}#{}", b_brick.module_type, safe_name)
-                        };
-                        
-                        let mut final_id = base_id.clone();
-                        let mut counter = 1;
-                        while cfg.module_definitions.contains_key(&final_id) {
-                            final_id = format!("{}-{}", base_id, counter);
-                            counter += 1;
-                        }
-                        
-                        // 2. Insert Definition
-                        let mut def = b_brick.config.clone();
-                        if b_brick.module_type == "custom" {
-                            if let serde_json::Value::Object(ref mut map) = def {
-                                if !map.contains_key("class") {
-                                    map.insert("class".to_string(), serde_json::Value::String("custom".to_string()));
-                                }
+                    // 2. Insert Definition
+                    let mut def = b_brick.config.clone();
+                    resources::resolve_script_refs(&mut def);
+                    if b_brick.module_type == "custom" {
+                        if let serde_json::Value::Object(ref mut map) = def {
+                            if !map.contains_key("class") {
+                                map.insert("class".to_string(), serde_json::Value::String("custom".to_string()));
                             }
                         }
-                        cfg.module_definitions.insert(final_id.clone(), def);
-                        
-                        // 3. Add to Column
-                        // Default to right column if nothing selected
-                        let target_col = if let Some((col, _)) = &*b_sel.borrow() { col.clone() } else { "right".to_string() };
-                        
-                        match target_col.as_str() {
-                            "left" => cfg.modules_left.push(final_id.clone()),
-                            "center" => cfg.modules_center.push(final_id.clone()),
-                            "right" => cfg.modules_right.push(final_id.clone()),
-                            _ => cfg.modules_right.push(final_id.clone()),
-                        }
-                        
-                        drop(cfg);
-                        b_ref();
-                        b_toast.add_toast(Toast::new(&format!("Added {}", b_brick.name)));
-                    });
+                    }
+                    cfg.module_definitions.insert(final_id.clone(), def);
                     
-                    row.add_suffix(&add_btn);
-                    list.append(&row);
-                }
+                    // 3. Add to Column
+                    // Default to right column if nothing selected
+                    let target_col = if let Some((col, _)) = &*b_sel.borrow() { col.clone() } else { "right".to_string() };
+                    
+                    match target_col.as_str() {
+                        "left" => cfg.modules_left.push(final_id.clone()),
+                        "center" => cfg.modules_center.push(final_id.clone()),
+                        "right" => cfg.modules_right.push(final_id.clone()),
+                        _ => cfg.modules_right.push(final_id.clone()),
+                    }
+                    
+                    drop(cfg);
+                    b_ref();
+                    b_toast.add_toast(Toast::new(&format!("Added {}", b_brick.name)));
+                });
+                
+                row.add_suffix(&add_btn);
+                list.append(&row);
             }
         }
     };
@@ -671,6 +603,7 @@ fn build_ui(app: &Application) {
         let refresh_rc = Rc::clone(&refresh_rc);
         let update_props_self = Rc::clone(&update_properties_fn);
         let layout_css_path = layout_css_path.clone();
+        let targets_rc = Rc::clone(&targets_rc);
         let sel_state_props = Rc::clone(&selected_module_state);
         let toast_p = toast_ref.clone();
         
@@ -781,9 +714,10 @@ fn build_ui(app: &Application) {
                                       .chars()
                                       .filter(|c| c.is_alphanumeric() || *c == '-' || *c == '_')
                                       .collect::<String>();
-                                  let filename = format!("presets/bricks/{}.json", safe_filename);
+                                  let dir = resources::user_dir("bricks");
+                                  let filename = dir.join(format!("{}.json", safe_filename));
                                   if let Ok(json) = serde_json::to_string_pretty(&brick) {
-                                      if fs::write(&filename, json).is_ok() {
+                                      if fs::create_dir_all(&dir).is_ok() && fs::write(&filename, json).is_ok() {
                                           d_toast.add_toast(Toast::new(&format!("Saved brick: {}", name)));
                                           d_pop(""); // Refresh gallery
                                       }
@@ -803,6 +737,14 @@ fn build_ui(app: &Application) {
             if let Some(def) = config_borrow_orig.module_definitions.get(&mod_name) {
                 if let Some(obj) = def.as_object() {
                     for (k, v) in obj {
+                        // Lists of strings (`format-icons` ramps, group `modules`) get one row per item.
+                        if let Some(items) = v.as_array().filter(|a| a.iter().all(|i| i.is_string())) {
+                            let items: Vec<String> = items.iter().filter_map(|i| i.as_str().map(|s| s.to_string())).collect();
+                            let upd_l = Rc::clone(&update_props_self); let mn_l = mod_name.clone();
+                            let rebuild: Rc<dyn Fn()> = Rc::new(move || { if let Some(f) = &*upd_l.borrow() { f(mn_l.clone()); } });
+                            group.add(&string_list_row(&config_rc, &mod_name, k, &items, Rc::clone(&refresh_rc), rebuild));
+                            continue;
+                        }
                         let row = ActionRow::new();
                         row.set_title(k);
                         let del_btn = Button::builder().icon_name("user-trash-symbolic").has_frame(false).build();
@@ -838,30 +780,9 @@ fn build_ui(app: &Application) {
                                         o.insert(k_inner.clone(), val);
                                     }
                                 });
-                                
-                                let icon_btn = Button::builder().icon_name("face-smile-symbolic").has_frame(false).build();
-                                let pop = gtk::Popover::new();
-                                let grid = gtk::FlowBox::builder().max_children_per_line(8).min_children_per_line(8).selection_mode(gtk::SelectionMode::None).build();
-                                grid.set_margin_top(8); grid.set_margin_bottom(8); grid.set_margin_start(8); grid.set_margin_end(8);
-                                
-                                for icon in ICON_LIST {
-                                    let btn = Button::with_label(icon);
-                                    btn.add_css_class("flat");
-                                    let e_c = en.clone(); let i_c = icon.to_string();
-                                    let p_weak = pop.downgrade();
-                                    btn.connect_clicked(move |_| {
-                                        let text = e_c.text().to_string();
-                                        e_c.set_text(&(text + &i_c));
-                                        if let Some(p) = p_weak.upgrade() { p.popdown(); }
-                                    });
-                                    grid.insert(&btn, -1);
-                                }
-                                pop.set_child(Some(&grid));
-                                pop.set_parent(&icon_btn);
-                                icon_btn.connect_clicked(move |_| pop.popup());
-                                
+
                                 row.add_suffix(&en);
-                                row.add_suffix(&icon_btn);
+                                row.add_suffix(&glyph_picker(&en));
                             }
                         }
                         group.add(&row);
@@ -890,34 +811,16 @@ fn build_ui(app: &Application) {
             vis_group.set_title("Visual Overrides");
             vis_group.set_description(Some("These settings override default styles and are saved to your layout CSS."));
             
-            let create_css_row = |title: &str, prop: &str, min: f64, max: f64, layout_path: PathBuf, mod_name: String, update_fn: Rc<RefCell<Option<Box<dyn Fn(String)>>>>| {
-                let row = ActionRow::new();
-                row.set_title(title);
-                let current = get_module_css_prop(&layout_path, &mod_name, "", prop)
-                    .and_then(|v| v.chars().filter(|c| c.is_digit(10)).collect::<String>().parse::<f64>().ok())
-                    .unwrap_or(if prop == "font-size" { 14.0 } else { 0.0 });
-                
-                let scale = gtk::Scale::with_range(Orientation::Horizontal, min, max, 1.0);
-                scale.set_value(current);
-                scale.set_width_request(150);
-                scale.set_valign(gtk::Align::Center);
-                
-                let lp = layout_path.clone(); let mn = mod_name.clone(); let pr = prop.to_string();
-                let upd = Rc::clone(&update_fn);
-                scale.connect_value_changed(move |s| {
-                    let val = format!("{}px", s.value() as i32);
-                    update_module_css(&lp, &mn, "", &pr, &val);
-                    if let Some(f) = &*upd.borrow() { f(mn.clone()); }
-                });
-                row.add_suffix(&scale);
-                row
-            };
-            
-            vis_group.add(&create_css_row("Font Size", "font-size", 6.0, 48.0, layout_css_path.clone(), mod_name.clone(), Rc::clone(&update_props_self)));
-            vis_group.add(&create_css_row("Margin", "margin", 0.0, 50.0, layout_css_path.clone(), mod_name.clone(), Rc::clone(&update_props_self)));
-            vis_group.add(&create_css_row("Padding", "padding", 0.0, 50.0, layout_css_path.clone(), mod_name.clone(), Rc::clone(&update_props_self)));
-            vis_group.add(&create_css_row("Border Radius", "border-radius", 0.0, 50.0, layout_css_path.clone(), mod_name.clone(), Rc::clone(&update_props_self)));
-            
+            vis_group.add(&css_length_row("Font Size", &layout_css_path, &mod_name, "font-size", 6.0, 48.0, 1.0, 14.0));
+            vis_group.add(&font_family_row(&layout_css_path, &mod_name));
+            vis_group.add(&font_weight_row(&layout_css_path, &mod_name));
+            vis_group.add(&css_length_row("Letter Spacing", &layout_css_path, &mod_name, "letter-spacing", -2.0, 10.0, 0.5, 0.0));
+            vis_group.add(&box_sides_row("Margin", &layout_css_path, &mod_name, "margin", -50.0));
+            vis_group.add(&box_sides_row("Padding", &layout_css_path, &mod_name, "padding", 0.0));
+            vis_group.add(&box_sides_row("Border Radius", &layout_css_path, &mod_name, "border-radius", 0.0));
+            vis_group.add(&border_row(&layout_css_path, &mod_name, &style_rc));
+            vis_group.add(&css_length_row("Minimum Width", &layout_css_path, &mod_name, "min-width", 0.0, 300.0, 1.0, 0.0));
+
             props_page.append(&vis_group);
             
             // --- Color Overrides (FG/BG) ---
@@ -984,7 +887,7 @@ fn build_ui(app: &Application) {
                     if def.get("drawer").is_some() { drawer_sw.set_active(true); }
                 }
 
-                let cfg_d = Rc::clone(&config_rc); let mn_d = mod_name.clone(); let ref_d = Rc::clone(&refresh_rc);
+                let cfg_d = Rc::clone(&config_rc); let mn_d = mod_name.clone(); let ref_d = Rc::clone(&refresh_rc); let upd_d = Rc::clone(&update_props_self);
                 drawer_sw.connect_state_set(move |_, state| {
                     let mut c = cfg_d.borrow_mut();
                     if let Some(def) = c.module_definitions.get_mut(&mn_d) {
@@ -997,6 +900,8 @@ fn build_ui(app: &Application) {
                         }
                     }
                     drop(c); ref_d();
+                    let (upd, mn) = (Rc::clone(&upd_d), mn_d.clone());
+                    glib::idle_add_local_once(move || { if let Some(f) = &*upd.borrow() { f(mn); } });
                     glib::Propagation::Proceed
                 });
                 drawer_row.add_suffix(&drawer_sw);
@@ -1078,13 +983,13 @@ fn build_ui(app: &Application) {
                 // Orientation
                 let orient_row = ComboRow::new();
                 orient_row.set_title("Orientation");
-                let orients = vec!["inherit", "horizontal", "vertical"];
+                let orients = vec!["inherit", "horizontal", "vertical", "orthogonal"];
                 let model = StringList::new(&orients);
                 orient_row.set_model(Some(&model));
                 
                 if let Some(def) = config_borrow_orig.module_definitions.get(&mod_name) {
                     if let Some(v) = def.get("orientation").and_then(|v| v.as_str()) {
-                        let idx = match v { "horizontal" => 1, "vertical" => 2, _ => 0 };
+                        let idx = match v { "horizontal" => 1, "vertical" => 2, "orthogonal" => 3, _ => 0 };
                         orient_row.set_selected(idx);
                     }
                 }
@@ -1096,6 +1001,7 @@ fn build_ui(app: &Application) {
                         let val = match row.selected() {
                             1 => Some("horizontal"),
                             2 => Some("vertical"),
+                            3 => Some("orthogonal"),
                             _ => None,
                         };
                         if let Some(v) = val { def.as_object_mut().unwrap().insert("orientation".to_string(), serde_json::json!(v)); }
@@ -1105,6 +1011,59 @@ fn build_ui(app: &Application) {
                 });
                 group_cfg.add(&orient_row);
                 props_page.append(&group_cfg);
+
+                let upd_gs = Rc::clone(&update_props_self); let mn_gs = mod_name.clone();
+                let rebuild: Rc<dyn Fn()> = Rc::new(move || { if let Some(f) = &*upd_gs.borrow() { f(mn_gs.clone()); } });
+                props_page.append(&group_style_editor(&mod_name, &config_rc, &layout_css_path, &style_rc, rebuild));
+            }
+
+            let base_module = mod_name.split('#').next().unwrap_or(&mod_name);
+            if base_module == "hyprland/workspaces" || base_module == "sway/workspaces" {
+                let upd_ws = Rc::clone(&update_props_self); let mn_ws = mod_name.clone();
+                let rebuild: Rc<dyn Fn()> = Rc::new(move || { if let Some(f) = &*upd_ws.borrow() { f(mn_ws.clone()); } });
+                props_page.append(&workspaces_editor(&mod_name, &config_rc, rebuild));
+            } else if base_module == "clock" {
+                let upd_cl = Rc::clone(&update_props_self); let mn_cl = mod_name.clone();
+                let rebuild: Rc<dyn Fn()> = Rc::new(move || { if let Some(f) = &*upd_cl.borrow() { f(mn_cl.clone()); } });
+                props_page.append(&clock_editor(&mod_name, &config_rc, rebuild));
+            }
+
+            // --- Rotation (label modules) / Orientation (sliders) ---
+            let base_name = mod_name.split('#').next().unwrap_or(&mod_name);
+            let is_slider = base_name.ends_with("/slider");
+            let no_rotate = ["tray", "privacy", "wlr/taskbar", "cava"];
+            if !mod_name.starts_with("group/") && (is_slider || !(no_rotate.contains(&base_name) || base_name.ends_with("/workspaces") || base_name.ends_with("/tags"))) {
+                let rot_group = PreferencesGroup::new();
+                rot_group.set_title(if is_slider { "Orientation" } else { "Rotation" });
+                if config_borrow_orig.bar.is_vertical() {
+                    rot_group.set_description(Some("The bar is vertical: rotate labels or turn sliders so they fit the bar width"));
+                }
+                let (key, options): (&'static str, &'static [&'static str]) = if is_slider {
+                    ("orientation", &["default", "horizontal", "vertical"])
+                } else {
+                    ("rotate", &["0", "90", "180", "270"])
+                };
+                let row = ComboRow::new();
+                row.set_title(if is_slider { "Slider Orientation" } else { "Rotate Label (degrees)" });
+                row.set_model(Some(&StringList::new(options)));
+                let current = config_borrow_orig.module_definitions.get(&mod_name)
+                    .and_then(|d| d.get(key))
+                    .map(|v| v.to_string().replace('"', ""));
+                row.set_selected(current.and_then(|c| options.iter().position(|o| *o == c)).unwrap_or(0) as u32);
+                let cfg_r = Rc::clone(&config_rc); let mn_r = mod_name.clone(); let ref_r = Rc::clone(&refresh_rc);
+                row.connect_selected_notify(move |r| {
+                    let idx = r.selected() as usize;
+                    let mut c = cfg_r.borrow_mut();
+                    let def = c.module_definitions.entry(mn_r.clone()).or_insert_with(|| serde_json::json!({}));
+                    if let Some(o) = def.as_object_mut() {
+                        if idx == 0 { o.remove(key); }
+                        else if is_slider { o.insert(key.to_string(), serde_json::json!(options[idx])); }
+                        else { o.insert(key.to_string(), serde_json::json!(options[idx].parse::<i64>().unwrap_or(0))); }
+                    }
+                    drop(c); ref_r();
+                });
+                rot_group.add(&row);
+                props_page.append(&rot_group);
             }
 
             let anim_group = PreferencesGroup::new();
@@ -1126,29 +1085,31 @@ fn build_ui(app: &Application) {
             anim_group.add(&transition_row);
 
             // --- Hover Effects ---
+            let anim_names = keyframe_names(&layout_css_path);
             let hover_row = ComboRow::new();
             hover_row.set_title("Hover Effect");
-            let hover_effects = vec!["none", "glow", "lift", "bounce", "wobble", "shake", "blink"];
-            let hover_model = StringList::new(&hover_effects);
-            hover_row.set_model(Some(&hover_model));
-            
-            let current_hover = if get_module_css_prop(&layout_css_path, &mod_name, ":hover", "background-color").is_some() { 1 }
-                               else if get_module_css_prop(&layout_css_path, &mod_name, ":hover", "margin-top").is_some() && get_module_css_prop(&layout_css_path, &mod_name, ":hover", "animation").is_none() { 2 }
-                               else if let Some(a) = get_module_css_prop(&layout_css_path, &mod_name, ":hover", "animation") {
-                                   if a.contains("bounce") { 3 } else if a.contains("wobble") { 4 } else if a.contains("shake") { 5 } else if a.contains("blink") { 6 } else { 0 }
-                               }
+            // Static presets first; keyframe animations are labelled apart, since the
+            // built-in `lift` animation shares its name with the static preset.
+            let mut hover_effects = vec!["none".to_string(), "glow".to_string(), "lift (static)".to_string()];
+            hover_effects.extend(anim_names.iter().map(|n| format!("{} (animation)", n)));
+            hover_row.set_model(Some(&StringList::new(&hover_effects.iter().map(|s| s.as_str()).collect::<Vec<_>>())));
+
+            let hover_anim = get_module_css_prop(&layout_css_path, &mod_name, ":hover", "animation").and_then(|a| animation::AnimationSpec::parse(&a));
+            let current_hover = if let Some(spec) = &hover_anim { anim_names.iter().position(|n| *n == spec.name).map(|i| i + 3).unwrap_or(0) }
+                               else if get_module_css_prop(&layout_css_path, &mod_name, ":hover", "background-color").is_some() { 1 }
+                               else if get_module_css_prop(&layout_css_path, &mod_name, ":hover", "margin-top").is_some() { 2 }
                                else { 0 };
-            hover_row.set_selected(current_hover);
-            
-            let lp_h = layout_css_path.clone(); let mn_h = mod_name.clone(); let upd_h = Rc::clone(&update_props_self);
+            hover_row.set_selected(current_hover as u32);
+
+            let lp_h = layout_css_path.clone(); let mn_h = mod_name.clone(); let upd_h = Rc::clone(&update_props_self); let names_h = anim_names.clone();
             hover_row.connect_selected_notify(move |row| {
-                let sel = row.selected();
+                let sel = row.selected() as usize;
                 // Clear old hover effects
                 update_module_css(&lp_h, &mn_h, ":hover", "background-color", "");
                 update_module_css(&lp_h, &mn_h, ":hover", "margin-top", "");
                 update_module_css(&lp_h, &mn_h, ":hover", "color", "");
                 update_module_css(&lp_h, &mn_h, ":hover", "animation", "");
-                
+
                 match sel {
                     1 => { // Glow
                         update_module_css(&lp_h, &mn_h, ":hover", "background-color", "@hover_bg");
@@ -1157,149 +1118,69 @@ fn build_ui(app: &Application) {
                     2 => { // Lift (static)
                         update_module_css(&lp_h, &mn_h, ":hover", "margin-top", "-2px");
                     }
-                    3 => { // Bounce
-                        update_module_css(&lp_h, &mn_h, ":hover", "animation", "bounce 0.5s infinite");
-                    }
-                    4 => { // Wobble
-                        update_module_css(&lp_h, &mn_h, ":hover", "animation", "wobble 0.4s infinite");
-                    }
-                    5 => { // Shake
-                        update_module_css(&lp_h, &mn_h, ":hover", "animation", "shake 0.3s infinite");
-                    }
-                    6 => { // Blink
-                        update_module_css(&lp_h, &mn_h, ":hover", "animation", "blink 1s infinite alternate");
+                    _ => {
+                        if let Some(name) = sel.checked_sub(3).and_then(|i| names_h.get(i)) {
+                            update_module_css(&lp_h, &mn_h, ":hover", "animation", &animation::AnimationSpec::default_for(name).to_css());
+                        }
                     }
-                    _ => {}
                 }
                 if let Some(f) = &*upd_h.borrow() { f(mn_h.clone()); }
             });
             anim_group.add(&hover_row);
+            if let Some(timing) = animation_timing_row(&layout_css_path, &mod_name, ":hover") { anim_group.add(&timing); }
 
             // --- Constant Animation ---
             let const_row = ComboRow::new();
             const_row.set_title("Constant Animation");
-            let const_effects = vec!["none", "blink", "pulse", "rainbow", "shiver"];
-            let const_model = StringList::new(&const_effects);
-            const_row.set_model(Some(&const_model));
+            let mut const_effects = vec!["none"];
+            const_effects.extend(anim_names.iter().map(|n| n.as_str()));
+            const_row.set_model(Some(&StringList::new(&const_effects)));
 
-            let current_const = if let Some(a) = get_module_css_prop(&layout_css_path, &mod_name, "", "animation") {
-                if a.contains("pulse") { 2 } else if a.contains("rainbow") { 3 } else if a.contains("shiver") { 4 } else if a.contains("blink") { 1 } else { 0 }
-            } else { 0 };
-            const_row.set_selected(current_const);
+            let current_const = get_module_css_prop(&layout_css_path, &mod_name, "", "animation")
+                .and_then(|a| animation::AnimationSpec::parse(&a))
+                .and_then(|spec| anim_names.iter().position(|n| *n == spec.name).map(|i| i + 1))
+                .unwrap_or(0);
+            const_row.set_selected(current_const as u32);
 
-            let lp_c = layout_css_path.clone(); let mn_c = mod_name.clone(); let upd_c = Rc::clone(&update_props_self);
+            let lp_c = layout_css_path.clone(); let mn_c = mod_name.clone(); let upd_c = Rc::clone(&update_props_self); let names_c = anim_names.clone();
             const_row.connect_selected_notify(move |row| {
-                let sel = row.selected();
+                let sel = row.selected() as usize;
                 update_module_css(&lp_c, &mn_c, "", "animation", "");
-                match sel {
-                    1 => { 
-                        update_module_css(&lp_c, &mn_c, "", "animation", "blink 1s infinite alternate");
-                        update_module_css(&lp_c, &mn_c, "", "transition", "none");
-                    }
-                    2 => { 
-                        update_module_css(&lp_c, &mn_c, "", "animation", "glow_pulse 2s infinite");
-                        update_module_css(&lp_c, &mn_c, "", "transition", "none");
-                    }
-                    3 => {
-                        update_module_css(&lp_c, &mn_c, "", "animation", "rainbow 4s infinite linear");
+                match sel.checked_sub(1).and_then(|i| names_c.get(i)) {
+                    Some(name) => {
+                        update_module_css(&lp_c, &mn_c, "", "animation", &animation::AnimationSpec::default_for(name).to_css());
                         update_module_css(&lp_c, &mn_c, "", "transition", "none");
                     }
-                    4 => {
-                        update_module_css(&lp_c, &mn_c, "", "animation", "shiver 0.2s infinite");
-                        update_module_css(&lp_c, &mn_c, "", "transition", "none");
-                    }
-                    _ => {
+                    None => {
                         update_module_css(&lp_c, &mn_c, "", "transition", "all 0.3s ease");
                     }
                 }
                 if let Some(f) = &*upd_c.borrow() { f(mn_c.clone()); }
             });
             anim_group.add(&const_row);
+            if let Some(timing) = animation_timing_row(&layout_css_path, &mod_name, "") { anim_group.add(&timing); }
 
             props_page.append(&anim_group);
 
-            // --- Module States & Thresholds (Battery, CPU, Memory, Temperature, Disk, Load) ---
-            if ["battery", "cpu", "memory", "temperature", "disk", "load"].contains(&mod_name.as_str()) {
-                let state_group = PreferencesGroup::new();
-                state_group.set_title("Module States and Thresholds");
-                
-                let states_to_add = match mod_name.as_str() {
-                    "battery" => vec![("Warning", "warning", 30.0), ("Critical", "critical", 15.0), ("Full", "full", 100.0)],
-                    "temperature" => vec![("Critial", "critical", 80.0)],
-                    "disk" => vec![("Warning", "warning", 80.0), ("Critical", "critical", 90.0)],
-                    _ => vec![("Warning", "warning", 70.0), ("Critical", "critical", 90.0)],
-                };
-
-                for (label, key, default_val) in states_to_add {
-                    let row = ActionRow::new();
-                    row.set_title(label);
-                    
-                    // JSON threshold setup
-                    let adj = gtk::Adjustment::new(default_val, 0.0, 100.0, 1.0, 10.0, 0.0);
-                    let scale = Scale::new(gtk::Orientation::Horizontal, Some(&adj));
-                    scale.set_width_request(150);
-                    scale.set_draw_value(true);
-                    
-                    // Pre-load current JSON value
-                    if let Some(def) = config_borrow_orig.module_definitions.get(&mod_name) {
-                        if let Some(states) = def.get("states") {
-                            if let Some(v) = states.get(key) {
-                                if let Some(f) = v.as_f64() { scale.set_value(f); }
-                            }
-                        }
-                    }
+            // --- Conditions (classes Waybar sets itself) ---
+            {
+                let upd_cd = Rc::clone(&update_props_self); let mn_cd = mod_name.clone();
+                let rebuild: Rc<dyn Fn()> = Rc::new(move || { if let Some(f) = &*upd_cd.borrow() { f(mn_cd.clone()); } });
+                if let Some(group) = conditions_editor(&mod_name, &layout_css_path, &style_rc, rebuild) { props_page.append(&group); }
+            }
 
-                    let config_s = Rc::clone(&config_rc); let mod_s = mod_name.clone(); let key_s = key.to_string();
-                    let refresh_s = Rc::clone(&refresh_rc);
-                    scale.connect_value_changed(move |s: &Scale| {
-                        let val = s.value();
-                        {
-                            let mut cfg = config_s.borrow_mut();
-                            if let Some(def) = cfg.module_definitions.get_mut(&mod_s) {
-                                if def.get("states").is_none() {
-                                    def.as_object_mut().unwrap().insert("states".to_string(), serde_json::json!({}));
-                                }
-                                def.get_mut("states").unwrap().as_object_mut().unwrap().insert(key_s.clone(), serde_json::json!(val));
-                            }
-                        }
-                        refresh_s();
-                    });
-                    row.add_suffix(&scale);
+            // --- Tooltip ---
+            if !mod_name.starts_with("group/") && base_module != "clock" {
+                let upd_tt = Rc::clone(&update_props_self); let mn_tt = mod_name.clone();
+                let rebuild: Rc<dyn Fn()> = Rc::new(move || { if let Some(f) = &*upd_tt.borrow() { f(mn_tt.clone()); } });
+                props_page.append(&tooltip_editor(&mod_name, &config_rc, rebuild));
+            }
 
-                    // Animation picker for this state
-                    let anim_combo = ComboRow::new();
-                    let sub = format!("Triggered at {}%", label);
-                    anim_combo.set_subtitle(&sub);
-                    let effects = vec!["none", "blink", "pulse", "rainbow", "shiver", "shake"];
-                    let model = StringList::new(&effects);
-                    anim_combo.set_model(Some(&model));
-                    
-                    let suffix = format!(".{}", key);
-                    let current = if let Some(a) = get_module_css_prop(&layout_css_path, &mod_name, &suffix, "animation") {
-                        if a.contains("pulse") { 2 } else if a.contains("rainbow") { 3 } else if a.contains("shiver") { 4 } else if a.contains("shake") { 5 } else if a.contains("blink") { 1 } else { 0 }
-                    } else { 0 };
-                    anim_combo.set_selected(current);
-
-                    let lp_s = layout_css_path.clone(); let mn_s = mod_name.clone(); let suf_s = suffix.clone();
-                    let upd_s = Rc::clone(&update_props_self);
-                    anim_combo.connect_selected_notify(move |row| {
-                        let sel = row.selected();
-                        update_module_css(&lp_s, &mn_s, &suf_s, "animation", "");
-                        match sel {
-                            1 => { update_module_css(&lp_s, &mn_s, &suf_s, "animation", "blink 1s infinite alternate"); }
-                            2 => { update_module_css(&lp_s, &mn_s, &suf_s, "animation", "glow_pulse 2s infinite"); }
-                            3 => { update_module_css(&lp_s, &mn_s, &suf_s, "animation", "rainbow 4s infinite linear"); }
-                            4 => { update_module_css(&lp_s, &mn_s, &suf_s, "animation", "shiver 0.2s infinite"); }
-                            5 => { update_module_css(&lp_s, &mn_s, &suf_s, "animation", "shake 0.3s infinite"); }
-                            _ => {}
-                        }
-                        if let Some(f) = &*upd_s.borrow() { f(mn_s.clone()); }
-                    });
-                    
-                    state_group.add(&row);
-                    state_group.add(&anim_combo);
-                }
-                props_page.append(&state_group);
+            // --- Module States & Thresholds ---
+            if states::state_value(&mod_name).is_some() {
+                let upd_st = Rc::clone(&update_props_self); let mn_st = mod_name.clone();
+                let rebuild: Rc<dyn Fn()> = Rc::new(move || { if let Some(f) = &*upd_st.borrow() { f(mn_st.clone()); } });
+                props_page.append(&states_editor(&mod_name, &config_rc, &layout_css_path, &style_rc, rebuild));
             }
 
             while let Some(child) = code_page.first_child() { code_page.remove(&child); }
@@ -1329,7 +1210,7 @@ fn build_ui(app: &Application) {
             let css_view = TextView::builder().margin_top(8).margin_bottom(8).margin_start(8).margin_end(8).build();
             let mut css_content = String::new();
             let full_css = fs::read_to_string(&layout_css_path).unwrap_or_else(|_| DEFAULT_LAYOUT_CSS.to_string());
-            let id = format!("#{}", mod_name.replace("/", "-"));
+            let id = css::module_selector(&mod_name);
             if let Some(start) = full_css.find(&id) {
                 if let Some(end) = full_css[start..].find('}') { css_content = full_css[start..start + end + 1].to_string(); }
             }
@@ -1343,7 +1224,7 @@ fn build_ui(app: &Application) {
             css_apply.connect_clicked(move |_| {
                 let text = css_view.buffer().text(&css_view.buffer().start_iter(), &css_view.buffer().end_iter(), false).to_string();
                 let full_css = fs::read_to_string(&layout_css_path_inner).unwrap_or_else(|_| DEFAULT_LAYOUT_CSS.to_string());
-                let id = format!("#{}", mod_css.replace("/", "-"));
+                let id = css::module_selector(&mod_css);
                 let mut new_full = full_css.clone();
                 if let Some(start) = full_css.find(&id) {
                     if let Some(end_rel) = full_css[start..].find('}') { new_full.replace_range(start..start + end_rel + 1, &text); }
@@ -1352,6 +1233,14 @@ fn build_ui(app: &Application) {
                 toast_css.add_toast(Toast::new("CSS Applied to session"));
             });
             code_page.append(&css_apply);
+
+            let imports_label = Label::new(Some("Style Imports"));
+            imports_label.set_halign(gtk::Align::Start); imports_label.add_css_class("caption");
+            imports_label.set_margin_top(12);
+            code_page.append(&imports_label);
+            let targets = targets_rc.borrow().clone();
+            let tree = imports::tree_from(&targets.style(), &full_css, &targets.style_dir());
+            code_page.append(&import_tree_list(&tree));
             drop(config_borrow_orig);
         }
     }));
@@ -1371,6 +1260,12 @@ fn build_ui(app: &Application) {
             let toast_styles = toast_styles.clone();
             let refresh_self = Rc::clone(&refresh_styles_fn);
             let layout_css_path = layout_css_path.clone();
+            let refresh_rc = Rc::clone(&refresh_rc);
+            // Replace vs merge choice for color presets; survives page rebuilds.
+            let preset_mode = Rc::new(std::cell::Cell::new(0u32));
+            // Font family filter; None until the first build picks one.
+            let font_filter = Rc::new(std::cell::Cell::new(None::<fonts::FamilyFilter>));
+            let generator_opts = Rc::new(RefCell::new(palette::GeneratorOptions::default()));
             
             move || {
                 while let Some(child) = styles_page.first_child() { styles_page.remove(&child); }
@@ -1378,198 +1273,541 @@ fn build_ui(app: &Application) {
                 title.add_css_class("title-3");
                 styles_page.append(&title);
 
-                // --- General Settings (Position) ---
+                // --- General Settings (bar-level options) ---
                 let general_group = PreferencesGroup::new();
                 general_group.set_title("General Settings");
                 
                 let pos_row = ComboRow::new();
                 pos_row.set_title("Bar Position");
-                let positions = vec!["Top", "Bottom"];
-                let pos_model = StringList::new(&positions);
+                let positions = ["top", "bottom", "left", "right"];
+                let pos_model = StringList::new(&["Top", "Bottom", "Left", "Right"]);
                 pos_row.set_model(Some(&pos_model));
                 
-                let current_pos = config_rc.borrow().position.clone().unwrap_or_else(|| "top".to_string());
-                let pos_idx = if current_pos.to_lowercase() == "bottom" { 1 } else { 0 };
-                pos_row.set_selected(pos_idx);
+                let current_pos = config_rc.borrow().bar.position.clone().unwrap_or_else(|| "top".to_string());
+                let pos_idx = positions.iter().position(|p| *p == current_pos.to_lowercase()).unwrap_or(0);
+                pos_row.set_selected(pos_idx as u32);
 
                 let config_pos = Rc::clone(&config_rc);
                 let refresh_pos = Rc::clone(&refresh_self);
+                let refresh_cols = Rc::clone(&refresh_rc);
+                let style_pos = Rc::clone(&style_rc);
+                let lp_pos = layout_css_path.clone();
                 pos_row.connect_selected_notify(move |row| {
-                    let idx = row.selected();
-                    let val = if idx == 1 { "bottom" } else { "top" };
-                    config_pos.borrow_mut().position = Some(val.to_string());
+                    let val = positions[row.selected() as usize];
+                    let was_vertical = config_pos.borrow().bar.is_vertical();
+                    config_pos.borrow_mut().bar.position = Some(val.to_string());
+                    let vertical = config_pos.borrow().bar.is_vertical();
+                    if vertical != was_vertical {
+                        // Rotate the session layout so margins and padding follow the new axis.
+                        let css = fs::read_to_string(&lp_pos).unwrap_or_default();
+                        let css = css::apply_metrics_block(&css::swap_axes(&css), &style_pos.borrow().vars, vertical);
+                        let _ = fs::write(&lp_pos, css);
+                        refresh_cols();
+                    }
                     if let Some(f) = &*refresh_pos.borrow() { f(); }
                 });
                 
                 general_group.add(&pos_row);
+
+                // Bar-level keys of the config root; unset values fall back to Waybar's defaults.
+                general_group.set_description(Some("Options of the bar itself, stored at the top level of the config"));
+                type ChoiceRow = (&'static str, &'static [&'static str], fn(&config::BarSettings) -> Option<String>, fn(&mut config::BarSettings, Option<String>));
+                let choice_rows: [ChoiceRow; 2] = [
+                    ("Layer", &["Default", "top", "bottom", "overlay"], |b| b.layer.clone(), |b, v| b.layer = v),
+                    ("Mode", &["Default", "dock", "hide", "invisible", "overlay"], |b| b.mode.clone(), |b, v| b.mode = v),
+                ];
+                for (title, options, get, set) in choice_rows {
+                    let row = ComboRow::new();
+                    row.set_title(title);
+                    row.set_model(Some(&StringList::new(options)));
+                    let current = get(&config_rc.borrow().bar);
+                    row.set_selected(current.and_then(|c| options.iter().position(|o| *o == c)).unwrap_or(0) as u32);
+                    let config_c = Rc::clone(&config_rc);
+                    row.connect_selected_notify(move |r| {
+                        let idx = r.selected() as usize;
+                        set(&mut config_c.borrow_mut().bar, if idx == 0 { None } else { Some(options[idx].to_string()) });
+                    });
+                    general_group.add(&row);
+                }
+
+                let output_row = ActionRow::builder().title("Output").subtitle("Comma-separated monitor names; empty shows the bar on every output").build();
+                let current_output = config_rc.borrow().bar.output.as_ref().map(|o| o.to_vec().join(", ")).unwrap_or_default();
+                let output_en = Entry::builder().text(current_output).placeholder_text("DP-1, HDMI-A-1").valign(gtk::Align::Center).build();
+                let config_out = Rc::clone(&config_rc);
+                output_en.connect_changed(move |e| {
+                    let items = e.text().split(',').map(|s| s.trim().to_string()).collect();
+                    config_out.borrow_mut().bar.output = config::OneOrMany::from_vec(items);
+                });
+                output_row.add_suffix(&output_en);
+                general_group.add(&output_row);
+
+                let name_row = ActionRow::builder().title("Bar Name").subtitle("Adds a class to window#waybar for bar-specific CSS").build();
+                let name_en = Entry::builder().text(config_rc.borrow().bar.name.clone().unwrap_or_default()).valign(gtk::Align::Center).build();
+                let config_name = Rc::clone(&config_rc);
+                name_en.connect_changed(move |e| {
+                    let text = e.text().trim().to_string();
+                    config_name.borrow_mut().bar.name = if text.is_empty() { None } else { Some(text) };
+                });
+                name_row.add_suffix(&name_en);
+                general_group.add(&name_row);
+
+                // (title, min, max, zero unsets the key, getter, setter)
+                type NumberRow = (&'static str, f64, f64, bool, fn(&config::BarSettings) -> Option<i64>, fn(&mut config::BarSettings, Option<i64>));
+                let number_sections: [(&str, Vec<NumberRow>); 2] = [
+                    ("Size and Spacing", vec![
+                        ("Height (0 = auto)", 0.0, 200.0, true, |b| b.height.map(i64::from), |b, v| b.height = v.map(|n| n as u32)),
+                        ("Width (0 = auto)", 0.0, 4000.0, true, |b| b.width.map(i64::from), |b, v| b.width = v.map(|n| n as u32)),
+                        ("Module Spacing", 0.0, 50.0, false, |b| b.spacing.map(i64::from), |b, v| b.spacing = v.map(|n| n as u32)),
+                    ]),
+                    ("Margins", vec![
+                        ("Top", -100.0, 200.0, true, |b| b.margin_top.map(i64::from), |b, v| b.margin_top = v.map(|n| n as i32)),
+                        ("Right", -100.0, 200.0, true, |b| b.margin_right.map(i64::from), |b, v| b.margin_right = v.map(|n| n as i32)),
+                        ("Bottom", -100.0, 200.0, true, |b| b.margin_bottom.map(i64::from), |b, v| b.margin_bottom = v.map(|n| n as i32)),
+                        ("Left", -100.0, 200.0, true, |b| b.margin_left.map(i64::from), |b, v| b.margin_left = v.map(|n| n as i32)),
+                    ]),
+                ];
+                for (section, rows) in number_sections {
+                    let expander = adw::ExpanderRow::new();
+                    expander.set_title(section);
+                    for (title, min, max, zero_unsets, get, set) in rows {
+                        let row = ActionRow::builder().title(title).build();
+                        let current = get(&config_rc.borrow().bar);
+                        let spin = gtk::SpinButton::with_range(min, max, 1.0);
+                        spin.set_valign(gtk::Align::Center);
+                        spin.set_value(current.unwrap_or(0) as f64);
+                        if current.is_none() && !zero_unsets { row.set_subtitle("Waybar default"); }
+                        let config_c = Rc::clone(&config_rc);
+                        spin.connect_value_changed(move |sp| {
+                            let v = sp.value() as i64;
+                            set(&mut config_c.borrow_mut().bar, if zero_unsets && v == 0 { None } else { Some(v) });
+                        });
+                        row.add_suffix(&spin);
+                        expander.add_row(&row);
+                    }
+                    if section == "Margins" {
+                        let short_row = ActionRow::builder().title("Shorthand").subtitle("CSS-style \"5 10\"; the per-side values above take precedence").build();
+                        let short_en = Entry::builder().text(config_rc.borrow().bar.margin.as_ref().map(|m| m.to_string()).unwrap_or_default()).valign(gtk::Align::Center).build();
+                        let config_m = Rc::clone(&config_rc);
+                        short_en.connect_changed(move |e| {
+                            config_m.borrow_mut().bar.margin = config::NumberOrString::parse(&e.text());
+                        });
+                        short_row.add_suffix(&short_en);
+                        expander.add_row(&short_row);
+                    }
+                    general_group.add(&expander);
+                }
+
+                // (title, subtitle, Waybar default, getter, setter). Matching the default removes the key.
+                type FlagRow = (&'static str, &'static str, bool, fn(&config::BarSettings) -> Option<bool>, fn(&mut config::BarSettings, Option<bool>));
+                let flags: [FlagRow; 6] = [
+                    ("Exclusive Zone", "Reserve screen space so windows do not cover the bar", true, |b| b.exclusive, |b, v| b.exclusive = v),
+                    ("Passthrough", "Let pointer events pass through the bar", false, |b| b.passthrough, |b, v| b.passthrough = v),
+                    ("Fixed Center", "Keep the center modules centered on the bar", true, |b| b.fixed_center, |b, v| b.fixed_center = v),
+                    ("Start Hidden", "Start with the bar hidden (toggle with SIGUSR1)", false, |b| b.start_hidden, |b, v| b.start_hidden = v),
+                    ("Reload Style on Change", "Reload style.css when the file changes", false, |b| b.reload_style_on_change, |b, v| b.reload_style_on_change = v),
+                    ("Sway IPC", "Follow the bar mode and visibility set by Sway", false, |b| b.ipc, |b, v| b.ipc = v),
+                ];
+                let behavior = adw::ExpanderRow::new();
+                behavior.set_title("Behavior");
+                for (title, subtitle, default, get, set) in flags {
+                    let row = ActionRow::builder().title(title).subtitle(subtitle).build();
+                    let sw = Switch::builder().active(get(&config_rc.borrow().bar).unwrap_or(default)).valign(gtk::Align::Center).build();
+                    let config_c = Rc::clone(&config_rc);
+                    sw.connect_state_set(move |_, state| {
+                        set(&mut config_c.borrow_mut().bar, if state == default { None } else { Some(state) });
+                        glib::Propagation::Proceed
+                    });
+                    row.add_suffix(&sw);
+                    behavior.add_row(&row);
+                }
+                general_group.add(&behavior);
                 styles_page.append(&general_group);
 
                 // --- Base Layout Selector ---
                 let layout_group = PreferencesGroup::new();
                 layout_group.set_title("Base Layout");
                 
-                // Find presets/layouts directory
-                let exe_path = std::env::current_exe().unwrap_or_default();
-                let exe_dir = exe_path.parent().unwrap_or(Path::new("."));
-                let mut layouts_path = PathBuf::from("presets/layouts");
-                if !layouts_path.exists() {
-                    layouts_path = exe_dir.join("presets/layouts");
-                    if !layouts_path.exists() {
-                        layouts_path = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default()).join("presets/layouts");
-                    }
-                    if !layouts_path.exists() {
-                        let home = std::env::var("HOME").unwrap_or_default();
-                        layouts_path = PathBuf::from(home).join(".local/share/waybarconf/presets/layouts");
-                    }
-                }
+                let layouts = layout::list_layouts();
 
-                if layouts_path.exists() {
-                    let mut layouts = Vec::new();
-                    if let Ok(entries) = fs::read_dir(&layouts_path) {
-                        for entry in entries.flatten() {
-                            if let Ok(ft) = entry.file_type() {
-                                if ft.is_file() {
-                                    if let Some(name) = entry.file_name().to_str() {
-                                        if name.ends_with(".css") {
-                                            layouts.push(name.to_string());
-                                        }
-                                    }
+                if !layouts.is_empty() {
+                    let layout_row = ComboRow::new();
+                    layout_row.set_title("Layout Template");
+                    let model = StringList::new(layouts.iter().map(|l| l.name.as_str()).collect::<Vec<&str>>().as_slice());
+                    layout_row.set_model(Some(&model));
+
+                    // Determine current selection
+                    let current_css = fs::read_to_string(&layout_css_path).unwrap_or_default();
+                    let current_idx = layouts.iter()
+                        .position(|l| current_css.contains(&format!("layouts/{}", l.file_name)))
+                        .unwrap_or(0);
+                    layout_row.set_selected(current_idx as u32);
+                    let current = &layouts[current_idx];
+                    if !current.description.is_empty() { layout_row.set_subtitle(&current.description); }
+
+                    let lp = layout_css_path.clone();
+                    let toast_l = toast_styles.clone();
+                    let refresh_l = Rc::clone(&refresh_self);
+                    let layouts_c = layouts.clone();
+                    layout_row.connect_selected_notify(move |row| {
+                        let idx = row.selected() as usize;
+                        if idx < layouts_c.len() {
+                            let new_layout = &layouts_c[idx].file_name;
+                            let mut css = fs::read_to_string(&lp).unwrap_or_default();
+                            
+                            // Replace existing import or add new one
+                            let import_str = format!("@import \"layouts/{}\";", new_layout);
+                            let re = regex::Regex::new(r#"@import\s+"layouts/[^"]+";"#).unwrap();
+                            
+                            if re.is_match(&css) {
+                                css = re.replace(&css, import_str.as_str()).to_string();
+                            } else {
+                                // Insert at a reasonable place (after color import or at top)
+                                if let Some(pos) = css.find("@import \"colors/wallpaper.css\";") {
+                                    css.insert_str(pos + 31, &format!("\n\n/** Imports a layout (style) for bar **/\n{}\n", import_str));
+                                } else {
+                                    css.insert_str(0, &format!("{}\n", import_str));
                                 }
                             }
-                        }
-                    }
-                    layouts.sort();
-
-                    if !layouts.is_empty() {
-                        let layout_row = ComboRow::new();
-                        layout_row.set_title("Layout Template");
-                        let model = StringList::new(layouts.iter().map(|s| s.as_str()).collect::<Vec<&str>>().as_slice());
-                        layout_row.set_model(Some(&model));
-
-                        // Determine current selection
-                        let current_css = fs::read_to_string(&layout_css_path).unwrap_or_default();
-                        let mut current_idx = 0;
-                        for (i, name) in layouts.iter().enumerate() {
-                            if current_css.contains(&format!("layouts/{}", name)) {
-                                current_idx = i as u32;
-                                break;
+                            
+                            if fs::write(&lp, css).is_ok() {
+                                toast_l.add_toast(Toast::new(&format!("Switched to {}", layouts_c[idx].name)));
+                                if let Some(f) = &*refresh_l.borrow() { f(); }
                             }
                         }
-                        layout_row.set_selected(current_idx);
-
-                        let lp = layout_css_path.clone();
-                        let toast_l = toast_styles.clone();
-                        let layouts_c = layouts.clone();
-                        layout_row.connect_selected_notify(move |row| {
-                            let idx = row.selected() as usize;
-                            if idx < layouts_c.len() {
-                                let new_layout = &layouts_c[idx];
-                                let mut css = fs::read_to_string(&lp).unwrap_or_default();
-                                
-                                // Replace existing import or add new one
-                                let import_str = format!("@import \"layouts/{}\";", new_layout);
-                                let re = regex::Regex::new(r#"@import\s+"layouts/[^"]+";"#).unwrap();
-                                
-                                if re.is_match(&css) {
-                                    css = re.replace(&css, import_str.as_str()).to_string();
-                                } else {
-                                    // Insert at a reasonable place (after color import or at top)
-                                    if let Some(pos) = css.find("@import \"colors/wallpaper.css\";") {
-                                        css.insert_str(pos + 31, &format!("\n\n/** Imports a layout (style) for bar **/\n{}\n", import_str));
-                                    } else {
-                                        css.insert_str(0, &format!("{}\n", import_str));
-                                    }
-                                }
-                                
-                                if let Ok(_) = fs::write(&lp, css) {
-                                    toast_l.add_toast(Toast::new(&format!("Switched to {}", new_layout)));
-                                }
-                            }
+                    });
+                    layout_group.add(&layout_row);
+
+                    // Palette roles the template uses
+                    let roles = current.roles();
+                    if !roles.is_empty() {
+                        let roles_row = ActionRow::new();
+                        roles_row.set_title("Palette Roles");
+                        roles_row.set_subtitle(&roles.join(", "));
+                        let resolved = palette::resolve_vars(&style_rc.borrow().vars);
+                        let swatches: Vec<gdk::RGBA> = roles.iter()
+                            .filter_map(|r| resolved.get(r))
+                            .filter_map(|v| gdk::RGBA::parse(v.as_str()).ok())
+                            .collect();
+                        let strip = swatch_strip(swatches);
+                        strip.set_content_width(120);
+                        strip.set_content_height(20);
+                        strip.set_valign(gtk::Align::Center);
+                        roles_row.add_suffix(&strip);
+                        layout_group.add(&roles_row);
+                    }
+
+                    // Tunable parameters declared in the template header
+                    let vars_now = style_rc.borrow().vars.clone();
+                    for param in &current.params {
+                        let row = ActionRow::new();
+                        row.set_title(&param.label);
+                        let scale = gtk::Scale::with_range(Orientation::Horizontal, param.min, param.max, 1.0);
+                        scale.set_value(current.param_value(param, &vars_now));
+                        scale.set_width_request(150);
+                        scale.set_valign(gtk::Align::Center);
+                        scale.set_draw_value(true);
+                        let var_name = current.param_var(&param.key);
+                        let style_p = Rc::clone(&style_rc);
+                        scale.connect_value_changed(move |s| {
+                            style_p.borrow_mut().vars.insert(var_name.clone(), format!("{}px", s.value() as i32));
+                            let _ = style_p.borrow().save();
                         });
-                        layout_group.add(&layout_row);
+                        row.add_suffix(&scale);
+                        layout_group.add(&row);
                     }
                 }
                 styles_page.append(&layout_group);
 
+                // --- Bar (window#waybar and module containers) ---
+                let bar_group = PreferencesGroup::new();
+                bar_group.set_title("Bar");
+                bar_group.set_description(Some("Styles the bar window and the left/center/right containers."));
+                let session_css = fs::read_to_string(&layout_css_path).unwrap_or_default();
+                let active = layout::active_layout(&session_css);
+                let vars_bar = style_rc.borrow().vars.clone();
+                let layout_note = |selector: &str, prop: &str| -> Option<String> {
+                    active.as_ref()
+                        .and_then(|l| l.rule_prop(selector, prop, &vars_bar).map(|v| format!("{} layout sets {}", l.name, v)))
+                };
+                let first_number = |v: &str| -> Option<f64> {
+                    regex::Regex::new(r"-?[\d.]+").unwrap().find(v).and_then(|m| m.as_str().parse::<f64>().ok())
+                };
+
+                let opacity = match get_selector_css_prop(&layout_css_path, "window#waybar", "background") {
+                    Some(v) if v.starts_with("alpha(") => v.rsplit(',').next().and_then(|a| a.trim_end_matches(')').trim().parse::<f64>().ok()).unwrap_or(1.0) * 100.0,
+                    _ => 100.0,
+                };
+                bar_group.add(&css_scale_row(&layout_css_path, "window#waybar", "background", "Background Opacity (%)", 0.0, 100.0, opacity,
+                    |v| if v >= 100.0 { "@bar_bg".to_string() } else { format!("alpha(@bar_bg, {:.2})", v / 100.0) },
+                    layout_note("window#waybar", "background")));
+
+                let border = get_selector_css_prop(&layout_css_path, "window#waybar", "border").and_then(|v| first_number(&v)).unwrap_or(0.0);
+                bar_group.add(&css_scale_row(&layout_css_path, "window#waybar", "border", "Outer Border", 0.0, 6.0, border,
+                    |v| if v <= 0.0 { String::new() } else { format!("{}px solid @border_color", v as i32) },
+                    layout_note("window#waybar", "border")));
+
+                let radius = get_selector_css_prop(&layout_css_path, "window#waybar", "border-radius").and_then(|v| first_number(&v)).unwrap_or(0.0);
+                bar_group.add(&css_scale_row(&layout_css_path, "window#waybar", "border-radius", "Corner Radius", 0.0, 30.0, radius,
+                    |v| if v <= 0.0 { String::new() } else { format!("{}px", v as i32) },
+                    layout_note("window#waybar", "border-radius")));
+
+                let shadow = get_selector_css_prop(&layout_css_path, "window#waybar", "box-shadow")
+                    .and_then(|v| v.split_whitespace().nth(2).and_then(first_number))
+                    .unwrap_or(0.0);
+                bar_group.add(&css_scale_row(&layout_css_path, "window#waybar", "box-shadow", "Shadow Blur", 0.0, 20.0, shadow,
+                    |v| if v <= 0.0 { String::new() } else { format!("0 2px {}px alpha(black, 0.5)", v as i32) },
+                    layout_note("window#waybar", "box-shadow")));
+
+                let vertical = config_rc.borrow().bar.is_vertical();
+                let containers = if vertical {
+                    [(".modules-left", "Top Container"), (".modules-center", "Center Container"), (".modules-right", "Bottom Container")]
+                } else {
+                    [(".modules-left", "Left Container"), (".modules-center", "Center Container"), (".modules-right", "Right Container")]
+                };
+                for (selector, title) in containers {
+                    let expander = adw::ExpanderRow::new();
+                    expander.set_title(title);
+                    expander.set_subtitle(selector);
+
+                    let bg_row = ComboRow::new();
+                    bg_row.set_title("Background");
+                    let bg_options = ["none", "@module_bg", "@bar_bg", "@hover_bg"];
+                    bg_row.set_model(Some(&StringList::new(&bg_options)));
+                    let current_bg = get_selector_css_prop(&layout_css_path, selector, "background").unwrap_or_default();
+                    bg_row.set_selected(bg_options.iter().position(|o| *o == current_bg).unwrap_or(0) as u32);
+                    if let Some(note) = layout_note(selector, "background") { bg_row.set_subtitle(&note); }
+                    let lp_bg = layout_css_path.clone();
+                    bg_row.connect_selected_notify(move |row| {
+                        let value = match row.selected() { 0 => "", i => bg_options[i as usize] };
+                        update_selector_css(&lp_bg, selector, "background", value);
+                    });
+                    expander.add_row(&bg_row);
+
+                    let c_radius = get_selector_css_prop(&layout_css_path, selector, "border-radius").and_then(|v| first_number(&v)).unwrap_or(0.0);
+                    expander.add_row(&css_scale_row(&layout_css_path, selector, "border-radius", "Corner Radius", 0.0, 30.0, c_radius,
+                        |v| if v <= 0.0 { String::new() } else { format!("{}px", v as i32) },
+                        layout_note(selector, "border-radius")));
+
+                    // Padding runs along the bar: left/right when horizontal, top/bottom when vertical.
+                    let c_padding = get_selector_css_prop(&layout_css_path, selector, "padding")
+                        .and_then(|v| {
+                            let parts: Vec<&str> = v.split_whitespace().collect();
+                            let along = if vertical { parts.first() } else { parts.last() };
+                            along.and_then(|p| first_number(p))
+                        })
+                        .unwrap_or(0.0);
+                    let to_padding: fn(f64) -> String = if vertical {
+                        |v| if v <= 0.0 { String::new() } else { format!("{}px 0", v as i32) }
+                    } else {
+                        |v| if v <= 0.0 { String::new() } else { format!("0 {}px", v as i32) }
+                    };
+                    expander.add_row(&css_scale_row(&layout_css_path, selector, "padding", "Padding", 0.0, 30.0, c_padding,
+                        to_padding, layout_note(selector, "padding")));
+
+                    let c_margin = get_selector_css_prop(&layout_css_path, selector, "margin").and_then(|v| first_number(&v)).unwrap_or(0.0);
+                    expander.add_row(&css_scale_row(&layout_css_path, selector, "margin", "Margin", 0.0, 30.0, c_margin,
+                        |v| if v <= 0.0 { String::new() } else { format!("{}px", v as i32) },
+                        layout_note(selector, "margin")));
+                    bar_group.add(&expander);
+                }
+                styles_page.append(&bar_group);
+
+                // --- Hard-coded colors (hand-written style.css) ---
+                let literal_count = recolor::find_colors(&fs::read_to_string(&layout_css_path).unwrap_or_default()).len();
+                if literal_count > 0 {
+                    let recolor_group = PreferencesGroup::new();
+                    let recolor_row = ActionRow::new();
+                    recolor_row.set_title("Hard-coded Colors");
+                    recolor_row.set_subtitle(&format!("{} colors in the stylesheet ignore the palette", literal_count));
+                    recolor_row.add_prefix(&gtk::Image::from_icon_name("dialog-information-symbolic"));
+                    let recolor_btn = Button::builder().label("Convert…").valign(gtk::Align::Center).build();
+                    let lp_rc = layout_css_path.clone(); let style_rcl = Rc::clone(&style_rc); let toast_rc = toast_styles.clone(); let refresh_rcl = Rc::clone(&refresh_self);
+                    recolor_btn.connect_clicked(move |btn| {
+                        let refresh = Rc::clone(&refresh_rcl);
+                        let on_done: Rc<dyn Fn()> = Rc::new(move || { if let Some(f) = &*refresh.borrow() { f(); } });
+                        recolor_dialog(btn.root().and_downcast::<gtk::Window>(), &lp_rc, &style_rcl, &toast_rc, on_done);
+                    });
+                    recolor_row.add_suffix(&recolor_btn);
+                    recolor_group.add(&recolor_row);
+                    styles_page.append(&recolor_group);
+                }
+
                 // --- Color Preset Selector ---
                 let color_preset_group = PreferencesGroup::new();
                 color_preset_group.set_title("Color Presets");
                 
-                // Find presets/colors directory
-                let mut colors_path = PathBuf::from("presets/colors");
-                if !colors_path.exists() {
-                    colors_path = exe_dir.join("presets/colors");
-                    if !colors_path.exists() {
-                        colors_path = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default()).join("presets/colors");
-                    }
-                    if !colors_path.exists() {
-                        let home = std::env::var("HOME").unwrap_or_default();
-                        colors_path = PathBuf::from(home).join(".local/share/waybarconf/presets/colors");
+                let presets = palette::list_color_presets();
+                if !presets.is_empty() {
+                    let mode_row = ComboRow::new();
+                    mode_row.set_title("Applying a Preset");
+                    mode_row.set_model(Some(&StringList::new(&["Replace palette", "Merge over current"])));
+                    mode_row.set_selected(preset_mode.get());
+                    let mode_c = Rc::clone(&preset_mode);
+                    mode_row.connect_selected_notify(move |row| mode_c.set(row.selected()));
+                    color_preset_group.add(&mode_row);
+
+                    let cards = gtk::FlowBox::builder()
+                        .selection_mode(gtk::SelectionMode::Single)
+                        .min_children_per_line(2)
+                        .max_children_per_line(3)
+                        .column_spacing(6)
+                        .row_spacing(6)
+                        .homogeneous(true)
+                        .build();
+                    cards.set_margin_top(6);
+
+                    let current_vars = style_rc.borrow().vars.clone();
+                    let mut current_card = None;
+                    for preset in &presets {
+                        let vars = preset.load_vars();
+                        let resolved = palette::resolve_vars(&vars);
+                        let swatches: Vec<gdk::RGBA> = resolved.values().filter_map(|v| gdk::RGBA::parse(v.as_str()).ok()).collect();
+
+                        let card = GtkBox::new(Orientation::Vertical, 4);
+                        card.add_css_class("card");
+                        card.set_margin_top(4); card.set_margin_bottom(4); card.set_margin_start(4); card.set_margin_end(4);
+                        let strip = swatch_strip(swatches);
+                        strip.set_content_height(28);
+                        strip.set_hexpand(true);
+                        card.append(&strip);
+                        let name_label = Label::new(Some(&preset.name));
+                        name_label.set_ellipsize(gtk::pango::EllipsizeMode::End);
+                        name_label.set_margin_start(6); name_label.set_margin_end(6);
+                        card.append(&name_label);
+                        let source_label = Label::new(Some(if preset.source == PresetSource::User { "User" } else { "Bundled" }));
+                        source_label.add_css_class("caption");
+                        source_label.add_css_class("dim-label");
+                        source_label.set_margin_bottom(4);
+                        card.append(&source_label);
+
+                        let child = gtk::FlowBoxChild::new();
+                        child.set_child(Some(&card));
+                        child.set_tooltip_text(Some(&preset.label()));
+                        cards.insert(&child, -1);
+                        if current_card.is_none() && palette::preset_matches(&vars, &current_vars) {
+                            current_card = Some(child);
+                        }
                     }
-                }
+                    if let Some(child) = &current_card { cards.select_child(child); }
 
-                if colors_path.exists() {
-                    let mut preset_colors = Vec::new();
-                    if let Ok(entries) = fs::read_dir(&colors_path) {
-                        for entry in entries.flatten() {
-                            if let Ok(ft) = entry.file_type() {
-                                if ft.is_file() {
-                                    if let Some(name) = entry.file_name().to_str() {
-                                        if name.ends_with(".css") {
-                                            preset_colors.push(name.to_string());
-                                        }
-                                    }
-                                }
+                    let style_cp = Rc::clone(&style_rc);
+                    let refresh_cp = Rc::clone(&refresh_self);
+                    let toast_cp = toast_styles.clone();
+                    let presets_c = presets.clone();
+                    let mode_cp = Rc::clone(&preset_mode);
+                    let css_cp = layout_css_path.clone();
+                    cards.connect_child_activated(move |_, child| {
+                        if let Some(preset) = presets_c.get(child.index() as usize) {
+                            let new_vars = preset.load_vars();
+                            if !new_vars.is_empty() {
+                                let replace = mode_cp.get() == 0;
+                                let kept = palette::apply_palette(&mut style_cp.borrow_mut().vars, new_vars, replace, &vars_in_use(&css_cp));
+                                let _ = style_cp.borrow().save();
+                                if let Some(f) = &*refresh_cp.borrow() { f(); }
+                                toast_cp.add_toast(Toast::new(&palette_applied_message(&format!("Applied {} Theme", preset.name), &kept)));
                             }
                         }
-                    }
-                    preset_colors.sort();
+                    });
+                    color_preset_group.add(&cards);
+                }
 
-                    if !preset_colors.is_empty() {
-                        let cf_row = ComboRow::new();
-                        cf_row.set_title("Color Theme");
-                        let model = StringList::new(preset_colors.iter().map(|s| s.as_str()).collect::<Vec<&str>>().as_slice());
-                        cf_row.set_model(Some(&model));
-                        
-                        // We don't verify current selection against file content because vars are parsed values. 
-                        // Just set to -1 or 0. Since it's a dropdown, 0 is fine, or we could add a "Custom" entry.
-                        // For simplicity, we just won't try to sync the dropdown to current state if it doesn't match a filename.
-
-                        let cp_p = colors_path.clone();
-                        let style_cp = Rc::clone(&style_rc);
-                        let refresh_cp = Rc::clone(&refresh_self);
-                        let toast_cp = toast_styles.clone();
-                        let presets_c = preset_colors.clone();
-                        
-                        cf_row.connect_selected_notify(move |row| {
-                            let idx = row.selected() as usize;
-                            if idx < presets_c.len() {
-                                let filename = &presets_c[idx];
-                                let target_file = cp_p.join(filename);
-                                if let Ok(content) = fs::read_to_string(&target_file) {
-                                    let new_vars = parse_style_vars(&content);
-                                    if !new_vars.is_empty() {
-                                        {
-                                            let mut s = style_cp.borrow_mut();
-                                            // Merge or Replace? Replaced as per "Load Preset" logic usually.
-                                            // The user said "use EITHER mutagen OR one of the css files", implying full replacement of the scheme.
-                                            // But let's keep unknown vars? No, themes usually define the whole palette.
-                                            // However, `parse_style_vars` only returns what it finds.
-                                            // Let's iterate and update.
-                                            for (k, v) in new_vars {
-                                                s.vars.insert(k, v);
-                                            }
-                                        }
-                                        let _ = style_cp.borrow().save();
-                                        if let Some(f) = &*refresh_cp.borrow() { f(); }
-                                        toast_cp.add_toast(Toast::new(&format!("Applied {} Theme", filename)));
-                                    }
+                // User presets: rename / delete
+                for preset in presets.iter().filter(|p| p.source == PresetSource::User) {
+                    let row = ActionRow::new();
+                    row.set_title(&preset.name);
+                    row.set_subtitle("User preset");
+
+                    let rename_btn = Button::builder().icon_name("edit-symbolic").has_frame(false).valign(gtk::Align::Center).tooltip_text("Rename").build();
+                    let preset_r = preset.clone();
+                    let refresh_r = Rc::clone(&refresh_self);
+                    let toast_r = toast_styles.clone();
+                    rename_btn.connect_clicked(move |btn| {
+                        let dialog = MessageDialog::builder().heading("Rename Preset").body("Enter the new name for this color preset").build();
+                        if let Some(win) = btn.root().and_downcast::<gtk::Window>() { dialog.set_transient_for(Some(&win)); }
+                        let entry = Entry::builder().text(&preset_r.name).margin_top(12).build();
+                        dialog.set_extra_child(Some(&entry));
+                        dialog.add_response("cancel", "Cancel");
+                        dialog.add_response("rename", "Rename");
+                        dialog.set_response_appearance("rename", adw::ResponseAppearance::Suggested);
+                        let preset_d = preset_r.clone();
+                        let refresh_d = Rc::clone(&refresh_r);
+                        let toast_d = toast_r.clone();
+                        dialog.connect_response(None, move |d, response| {
+                            if response == "rename" {
+                                match palette::rename_user_preset(&preset_d, &entry.text()) {
+                                    Ok(_) => { if let Some(f) = &*refresh_d.borrow() { f(); } }
+                                    Err(e) => toast_d.add_toast(Toast::new(&glib::markup_escape_text(&e))),
                                 }
                             }
+                            d.close();
                         });
-                        color_preset_group.add(&cf_row);
-                    }
+                        dialog.present();
+                    });
+                    row.add_suffix(&rename_btn);
+
+                    let del_btn = Button::builder().icon_name("user-trash-symbolic").has_frame(false).valign(gtk::Align::Center).tooltip_text("Delete").build();
+                    let preset_del = preset.clone();
+                    let refresh_del = Rc::clone(&refresh_self);
+                    let toast_del = toast_styles.clone();
+                    del_btn.connect_clicked(move |btn| {
+                        let dialog = MessageDialog::builder().heading("Delete Preset?").body(format!("The color preset {} will be removed permanently.", preset_del.name)).build();
+                        if let Some(win) = btn.root().and_downcast::<gtk::Window>() { dialog.set_transient_for(Some(&win)); }
+                        dialog.add_response("cancel", "Cancel");
+                        dialog.add_response("delete", "Delete");
+                        dialog.set_response_appearance("delete", adw::ResponseAppearance::Destructive);
+                        let preset_d = preset_del.clone();
+                        let refresh_d = Rc::clone(&refresh_del);
+                        let toast_d = toast_del.clone();
+                        dialog.connect_response(None, move |d, response| {
+                            if response == "delete" {
+                                match palette::delete_user_preset(&preset_d) {
+                                    Ok(_) => {
+                                        toast_d.add_toast(Toast::new(&format!("Deleted {}", preset_d.name)));
+                                        if let Some(f) = &*refresh_d.borrow() { f(); }
+                                    }
+                                    Err(e) => toast_d.add_toast(Toast::new(&glib::markup_escape_text(&e))),
+                                }
+                            }
+                            d.close();
+                        });
+                        dialog.present();
+                    });
+                    row.add_suffix(&del_btn);
+                    color_preset_group.add(&row);
                 }
+
+                let save_preset_btn = Button::with_label("Save as Preset");
+                save_preset_btn.set_margin_top(6);
+                let style_sp = Rc::clone(&style_rc);
+                let refresh_sp = Rc::clone(&refresh_self);
+                let toast_sp = toast_styles.clone();
+                save_preset_btn.connect_clicked(move |btn| {
+                    let dialog = MessageDialog::builder().heading("Save Color Preset").body("Save the current palette as a reusable color preset.").build();
+                    if let Some(win) = btn.root().and_downcast::<gtk::Window>() { dialog.set_transient_for(Some(&win)); }
+                    let entry = Entry::builder().placeholder_text("Preset Name (e.g. My Theme)").margin_top(12).build();
+                    dialog.set_extra_child(Some(&entry));
+                    dialog.add_response("cancel", "Cancel");
+                    dialog.add_response("save", "Save");
+                    dialog.set_response_appearance("save", adw::ResponseAppearance::Suggested);
+                    let style_d = Rc::clone(&style_sp);
+                    let refresh_d = Rc::clone(&refresh_sp);
+                    let toast_d = toast_sp.clone();
+                    dialog.connect_response(None, move |d, response| {
+                        if response == "save" {
+                            let name = entry.text().to_string();
+                            match palette::save_user_preset(&name, &style_d.borrow().vars) {
+                                Ok(_) => {
+                                    toast_d.add_toast(Toast::new(&format!("Saved preset: {}", name)));
+                                    if let Some(f) = &*refresh_d.borrow() { f(); }
+                                }
+                                Err(e) => toast_d.add_toast(Toast::new(&glib::markup_escape_text(&e))),
+                            }
+                        }
+                        d.close();
+                    });
+                    dialog.present();
+                });
+                color_preset_group.add(&save_preset_btn);
                 styles_page.append(&color_preset_group);
 
                 let auto_group = PreferencesGroup::new();
@@ -1606,6 +1844,90 @@ fn build_ui(app: &Application) {
                 });
                 auto_group.add(&extract_btn);
                 styles_page.append(&auto_group);
+
+                // --- Palette Generator (seed color + harmony) ---
+                let gen_group = PreferencesGroup::new();
+                gen_group.set_title("Palette Generator");
+                gen_group.set_description(Some("Build a full theme around one accent color."));
+                let opts = generator_opts.borrow().clone();
+
+                let seed_row = ActionRow::new();
+                seed_row.set_title("Seed Color");
+                let seed_btn = ColorButton::new();
+                if let Ok(rgba) = gdk::RGBA::parse(&opts.seed) { seed_btn.set_rgba(&rgba); }
+                let gen_seed = Rc::clone(&generator_opts);
+                seed_btn.connect_color_set(move |btn| {
+                    let rgba = btn.rgba();
+                    let hex = format!("#{:02x}{:02x}{:02x}", (rgba.red() * 255.0) as u8, (rgba.green() * 255.0) as u8, (rgba.blue() * 255.0) as u8);
+                    let mut o = gen_seed.borrow_mut();
+                    o.seed = hex;
+                    o.shuffle = 0;
+                });
+                seed_row.add_suffix(&seed_btn);
+                gen_group.add(&seed_row);
+
+                let harmony_row = ComboRow::new();
+                harmony_row.set_title("Harmony");
+                let harmony_labels: Vec<&str> = palette::Harmony::ALL.iter().map(|h| h.label()).collect();
+                harmony_row.set_model(Some(&StringList::new(&harmony_labels)));
+                harmony_row.set_selected(palette::Harmony::ALL.iter().position(|h| *h == opts.harmony).unwrap_or(0) as u32);
+                let gen_harmony = Rc::clone(&generator_opts);
+                harmony_row.connect_selected_notify(move |row| {
+                    if let Some(h) = palette::Harmony::ALL.get(row.selected() as usize) {
+                        gen_harmony.borrow_mut().harmony = *h;
+                    }
+                });
+                gen_group.add(&harmony_row);
+
+                let dark_row = ActionRow::new();
+                dark_row.set_title("Dark Mode");
+                let dark_sw = Switch::builder().active(opts.dark).valign(gtk::Align::Center).build();
+                let gen_dark = Rc::clone(&generator_opts);
+                dark_sw.connect_state_set(move |_, state| {
+                    gen_dark.borrow_mut().dark = state;
+                    glib::Propagation::Proceed
+                });
+                dark_row.add_suffix(&dark_sw);
+                gen_group.add(&dark_row);
+
+                let apply_generated = {
+                    let style_gen = Rc::clone(&style_rc);
+                    let refresh_gen = Rc::clone(&refresh_self);
+                    let toast_gen = toast_styles.clone();
+                    let mode_gen = Rc::clone(&preset_mode);
+                    let opts_gen = Rc::clone(&generator_opts);
+                    let css_gen = layout_css_path.clone();
+                    Rc::new(move || {
+                        let opts = opts_gen.borrow().clone();
+                        match palette::generate_palette(&opts) {
+                            Some(vars) => {
+                                let kept = palette::apply_palette(&mut style_gen.borrow_mut().vars, vars, mode_gen.get() == 0, &vars_in_use(&css_gen));
+                                let _ = style_gen.borrow().save();
+                                if let Some(f) = &*refresh_gen.borrow() { f(); }
+                                toast_gen.add_toast(Toast::new(&palette_applied_message(&format!("Generated {} palette", opts.harmony.label()), &kept)));
+                            }
+                            None => toast_gen.add_toast(Toast::new("Invalid seed color")),
+                        }
+                    })
+                };
+
+                let gen_btns = GtkBox::new(Orientation::Horizontal, 6);
+                gen_btns.set_margin_top(6);
+                let generate_btn = Button::with_label("Generate");
+                generate_btn.add_css_class("suggested-action");
+                let apply_g = Rc::clone(&apply_generated);
+                generate_btn.connect_clicked(move |_| apply_g());
+                gen_btns.append(&generate_btn);
+                let shuffle_btn = Button::builder().icon_name("media-playlist-shuffle-symbolic").tooltip_text("Shuffle").build();
+                let apply_s = Rc::clone(&apply_generated);
+                let gen_shuffle = Rc::clone(&generator_opts);
+                shuffle_btn.connect_clicked(move |_| {
+                    gen_shuffle.borrow_mut().shuffle += 1;
+                    apply_s();
+                });
+                gen_btns.append(&shuffle_btn);
+                gen_group.add(&gen_btns);
+                styles_page.append(&gen_group);
                 
                 let color_group = PreferencesGroup::new();
                 color_group.set_title("Color Scheme");
@@ -1632,40 +1954,401 @@ fn build_ui(app: &Application) {
                 
                 let metrics_group = PreferencesGroup::new();
                 metrics_group.set_title("Layout Metrics");
-                let m_vars: Vec<(String, String)> = style_rc.borrow().vars.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
-                for (name, value) in m_vars {
-                    if name.contains("padding") || name.contains("margin") || name.contains("spacing") {
-                        let row = ActionRow::new();
-                        row.set_title(&name.replace("_", " ").to_uppercase());
-                        let initial_val = value.chars().filter(|c| c.is_digit(10)).collect::<String>().parse::<f64>().unwrap_or(0.0);
-                        let scale = gtk::Scale::with_range(Orientation::Horizontal, 0.0, 50.0, 1.0);
-                        scale.set_value(initial_val);
-                        scale.set_width_request(150);
-                        let name_inner = name.clone();
-                        let style_inner = Rc::clone(&style_rc);
-                        scale.connect_value_changed(move |s| {
-                            let val = s.value() as i32;
-                            style_inner.borrow_mut().vars.insert(name_inner.clone(), format!("{}px", val));
-                            let _ = style_inner.borrow().save();
-                        });
-                        row.add_suffix(&scale);
-                        metrics_group.add(&row);
-                    }
+                metrics_group.set_description(Some("Written as real CSS properties into your layout; unset metrics keep the layout's own values."));
+                let m_vars = style_rc.borrow().vars.clone();
+                for metric in css::METRICS {
+                    let row = ActionRow::new();
+                    row.set_title(metric.title);
+                    let current = css::metric_value(&m_vars, metric.key);
+                    if current.is_none() { row.set_subtitle("Layout default"); }
+                    let scale = gtk::Scale::with_range(Orientation::Horizontal, metric.min, metric.max, 1.0);
+                    scale.set_value(current.unwrap_or(metric.default));
+                    scale.set_width_request(150);
+                    scale.set_valign(gtk::Align::Center);
+                    let style_inner = Rc::clone(&style_rc);
+                    let config_m = Rc::clone(&config_rc);
+                    let lp_m = layout_css_path.clone();
+                    let row_m = row.clone();
+                    scale.connect_value_changed(move |s| {
+                        style_inner.borrow_mut().vars.insert(metric.key.to_string(), format!("{}px", s.value() as i32));
+                        let _ = style_inner.borrow().save();
+                        let css = fs::read_to_string(&lp_m).unwrap_or_default();
+                        let _ = fs::write(&lp_m, css::apply_metrics_block(&css, &style_inner.borrow().vars, config_m.borrow().bar.is_vertical()));
+                        row_m.set_subtitle("");
+                    });
+                    row.add_suffix(&scale);
+
+                    let reset_btn = Button::builder().icon_name("edit-undo-symbolic").has_frame(false).valign(gtk::Align::Center).tooltip_text("Use layout default").build();
+                    let style_reset = Rc::clone(&style_rc);
+                    let config_r = Rc::clone(&config_rc);
+                    let lp_r = layout_css_path.clone();
+                    let refresh_reset = Rc::clone(&refresh_self);
+                    reset_btn.connect_clicked(move |_| {
+                        style_reset.borrow_mut().vars.shift_remove(metric.key);
+                        let _ = style_reset.borrow().save();
+                        let css = fs::read_to_string(&lp_r).unwrap_or_default();
+                        let _ = fs::write(&lp_r, css::apply_metrics_block(&css, &style_reset.borrow().vars, config_r.borrow().bar.is_vertical()));
+                        if let Some(f) = &*refresh_reset.borrow() { f(); }
+                    });
+                    row.add_suffix(&reset_btn);
+                    metrics_group.add(&row);
                 }
                 styles_page.append(&metrics_group);
+
+                // --- Font ---
+                styles_page.append(&font_group(&layout_css_path, &config_rc, &font_filter));
+
+                // --- Tooltips ---
+                styles_page.append(&tooltip_group(&layout_css_path, &style_rc));
+
+                // --- Keyframe Animations ---
+                let refresh_kf = Rc::clone(&refresh_self);
+                let rebuild_kf: Rc<dyn Fn()> = Rc::new(move || { if let Some(f) = &*refresh_kf.borrow() { f(); } });
+                styles_page.append(&keyframes_editor(&layout_css_path, rebuild_kf));
+
+                // --- Palette Export (rofi, mako, kitty, GTK...) ---
+                let export_group = PreferencesGroup::new();
+                export_group.set_title("Palette Export");
+                export_group.set_description(Some("Render the current colors into other apps' config files using templates."));
+                let export_settings = ExportSettings::load();
+
+                let on_apply_row = ActionRow::new();
+                on_apply_row.set_title("Export on Apply");
+                let on_apply_sw = Switch::builder().active(export_settings.export_on_apply).valign(gtk::Align::Center).build();
+                on_apply_sw.connect_state_set(move |_, state| {
+                    let mut settings = ExportSettings::load();
+                    settings.export_on_apply = state;
+                    let _ = settings.save();
+                    glib::Propagation::Proceed
+                });
+                on_apply_row.add_suffix(&on_apply_sw);
+                export_group.add(&on_apply_row);
+
+                for (i, target) in export_settings.targets.iter().enumerate() {
+                    let row = ActionRow::new();
+                    row.set_title(&target.name);
+                    row.set_subtitle(&format!("{} → {}", target.template, target.output));
+                    let en_sw = Switch::builder().active(target.enabled).valign(gtk::Align::Center).build();
+                    en_sw.connect_state_set(move |_, state| {
+                        let mut settings = ExportSettings::load();
+                        if let Some(t) = settings.targets.get_mut(i) { t.enabled = state; }
+                        let _ = settings.save();
+                        glib::Propagation::Proceed
+                    });
+                    row.add_suffix(&en_sw);
+                    let del_btn = Button::builder().icon_name("user-trash-symbolic").has_frame(false).valign(gtk::Align::Center).build();
+                    let refresh_del = Rc::clone(&refresh_self);
+                    del_btn.connect_clicked(move |_| {
+                        let mut settings = ExportSettings::load();
+                        if i < settings.targets.len() { settings.targets.remove(i); }
+                        let _ = settings.save();
+                        if let Some(f) = &*refresh_del.borrow() { f(); }
+                    });
+                    row.add_suffix(&del_btn);
+                    export_group.add(&row);
+                }
+
+                let export_btns = GtkBox::new(Orientation::Horizontal, 6);
+                export_btns.set_margin_top(6);
+                let add_target_btn = Button::with_label("Add Target");
+                let refresh_add = Rc::clone(&refresh_self);
+                add_target_btn.connect_clicked(move |btn| {
+                    let dialog = MessageDialog::builder().heading("Add Export Target").body("Pick a bundled template or enter the path to your own.").build();
+                    if let Some(win) = btn.root().and_downcast::<gtk::Window>() { dialog.set_transient_for(Some(&win)); }
+                    let box_layout = GtkBox::new(Orientation::Vertical, 12);
+                    let names: Vec<&str> = export::BUNDLED_TEMPLATES.iter().map(|(n, _, _)| *n).collect();
+                    let template_drop = gtk::DropDown::from_strings(&names);
+                    let name_entry = Entry::builder().placeholder_text("Name").text(export::BUNDLED_TEMPLATES[0].0).build();
+                    let template_entry = Entry::builder().placeholder_text("Template (bundled name or path)").text(export::BUNDLED_TEMPLATES[0].1).build();
+                    let output_entry = Entry::builder().placeholder_text("Output path").text(export::BUNDLED_TEMPLATES[0].2).build();
+                    let (n_c, t_c, o_c) = (name_entry.clone(), template_entry.clone(), output_entry.clone());
+                    template_drop.connect_selected_notify(move |d| {
+                        if let Some((name, template, output)) = export::BUNDLED_TEMPLATES.get(d.selected() as usize) {
+                            n_c.set_text(name); t_c.set_text(template); o_c.set_text(output);
+                        }
+                    });
+                    box_layout.append(&template_drop);
+                    box_layout.append(&name_entry);
+                    box_layout.append(&template_entry);
+                    box_layout.append(&output_entry);
+                    dialog.set_extra_child(Some(&box_layout));
+                    dialog.add_response("cancel", "Cancel");
+                    dialog.add_response("add", "Add");
+                    dialog.set_response_appearance("add", adw::ResponseAppearance::Suggested);
+                    let refresh_d = Rc::clone(&refresh_add);
+                    dialog.connect_response(None, move |d, response| {
+                        if response == "add" && !template_entry.text().is_empty() && !output_entry.text().is_empty() {
+                            let mut settings = ExportSettings::load();
+                            settings.targets.push(ExportTarget {
+                                name: name_entry.text().to_string(),
+                                template: template_entry.text().to_string(),
+                                output: output_entry.text().to_string(),
+                                enabled: true,
+                            });
+                            let _ = settings.save();
+                            if let Some(f) = &*refresh_d.borrow() { f(); }
+                        }
+                        d.close();
+                    });
+                    dialog.present();
+                });
+                export_btns.append(&add_target_btn);
+
+                let export_now_btn = Button::with_label("Export Now");
+                export_now_btn.add_css_class("suggested-action");
+                let style_export = Rc::clone(&style_rc);
+                let toast_export = toast_styles.clone();
+                export_now_btn.connect_clicked(move |_| {
+                    let settings = ExportSettings::load();
+                    let (written, errors) = settings.export_all(&style_export.borrow().vars);
+                    let msg = if errors.is_empty() { format!("Exported palette to {} file(s)", written.len()) } else { format!("Export failed: {}", errors.join("; ")) };
+                    toast_export.add_toast(Toast::new(&glib::markup_escape_text(&msg)));
+                });
+                export_btns.append(&export_now_btn);
+                export_group.add(&export_btns);
+                styles_page.append(&export_group);
             }
         };
         *refresh_styles_fn.borrow_mut() = Some(Box::new(refresh_styles.clone()));
         refresh_styles();
     }
 
+    // --- Outputs (multi-monitor bars) ---
+    let refresh_outputs_fn = Rc::new(RefCell::new(None::<Box<dyn Fn()>>));
+    {
+        let bars_rc = Rc::clone(&bars_rc);
+        let config_rc = Rc::clone(&config_rc);
+        let outputs_page = outputs_page.clone();
+        let refresh_rc = Rc::clone(&refresh_rc);
+        let refresh_styles_fn = Rc::clone(&refresh_styles_fn);
+        let refresh_self = Rc::clone(&refresh_outputs_fn);
+        let sel_state = Rc::clone(&selected_module_state);
+        let toast_outputs = toast_overlay.clone();
+        // Detected monitors are cached until "Rescan" so rebuilding the page stays cheap.
+        let detected: Rc<RefCell<Option<outputs::Detection>>> = Rc::new(RefCell::new(None));
+
+        let refresh_outputs = move || {
+            while let Some(child) = outputs_page.first_child() { outputs_page.remove(&child); }
+            let title = Label::new(Some("Outputs"));
+            title.add_css_class("title-3");
+            outputs_page.append(&title);
+
+            if detected.borrow().is_none() {
+                *detected.borrow_mut() = Some(outputs::detect_monitors());
+            }
+            let (source, mut monitors) = detected.borrow().clone().flatten().unwrap_or(("", Vec::new()));
+            let manual = outputs::load_manual_monitors();
+            for name in &manual {
+                if !monitors.iter().any(|m| &m.name == name) {
+                    monitors.push(outputs::Monitor { name: name.clone(), description: String::new() });
+                }
+            }
+            let bars = bars_rc.borrow().all(&config_rc.borrow());
+            let active = bars_rc.borrow().active;
+
+            // Writes `output` of bar `idx`, which for the edited bar lives in `config_rc`.
+            let set_output: Rc<dyn Fn(usize, Option<config::OneOrMany>)> = {
+                let bars_rc = Rc::clone(&bars_rc);
+                let config_rc = Rc::clone(&config_rc);
+                let refresh_self = Rc::clone(&refresh_self);
+                let refresh_styles_fn = Rc::clone(&refresh_styles_fn);
+                Rc::new(move |idx, value| {
+                    if idx == bars_rc.borrow().active {
+                        config_rc.borrow_mut().bar.output = value;
+                        if let Some(f) = &*refresh_styles_fn.borrow() { f(); }
+                    } else if let Some(bar) = bars_rc.borrow_mut().bars.get_mut(idx) {
+                        bar.bar.output = value;
+                    }
+                    let refresh_self = Rc::clone(&refresh_self);
+                    glib::idle_add_local_once(move || { if let Some(f) = &*refresh_self.borrow() { f(); } });
+                })
+            };
+
+            // --- Monitors ---
+            let mon_group = PreferencesGroup::new();
+            mon_group.set_title("Monitors");
+            mon_group.set_description(Some(&if source.is_empty() {
+                "No compositor answered (hyprctl, swaymsg, niri). Add monitor names by hand.".to_string()
+            } else {
+                format!("Detected with {}", source)
+            }));
+            let rescan_btn = Button::builder().icon_name("view-refresh-symbolic").has_frame(false).tooltip_text("Rescan Monitors").build();
+            let detected_r = Rc::clone(&detected); let refresh_r = Rc::clone(&refresh_self);
+            rescan_btn.connect_clicked(move |_| {
+                *detected_r.borrow_mut() = None;
+                if let Some(f) = &*refresh_r.borrow() { f(); }
+            });
+            mon_group.set_header_suffix(Some(&rescan_btn));
+
+            for monitor in &monitors {
+                let row = ActionRow::builder().title(&monitor.name).build();
+                let is_manual = monitor.description.is_empty() && manual.contains(&monitor.name);
+                row.set_subtitle(if is_manual { "Added manually" } else { &monitor.description });
+                let shown: Vec<String> = bars.iter().enumerate()
+                    .filter(|(_, b)| outputs::shows_on(b.bar.output.as_ref(), monitor))
+                    .map(|(i, b)| BarSet::label(b, i))
+                    .collect();
+                let shown_label = Label::new(Some(&if shown.is_empty() { "No bar".to_string() } else { shown.join(", ") }));
+                shown_label.add_css_class("dim-label");
+                row.add_suffix(&shown_label);
+                if is_manual {
+                    let del_btn = Button::builder().icon_name("user-trash-symbolic").has_frame(false).valign(gtk::Align::Center).build();
+                    let name_d = monitor.name.clone(); let refresh_d = Rc::clone(&refresh_self);
+                    del_btn.connect_clicked(move |_| {
+                        let mut names = outputs::load_manual_monitors();
+                        names.retain(|n| n != &name_d);
+                        let _ = outputs::save_manual_monitors(&names);
+                        if let Some(f) = &*refresh_d.borrow() { f(); }
+                    });
+                    row.add_suffix(&del_btn);
+                }
+                mon_group.add(&row);
+            }
+
+            let add_mon_row = ActionRow::builder().title("Add Monitor").build();
+            let add_mon_en = Entry::builder().placeholder_text("e.g. DP-2").valign(gtk::Align::Center).build();
+            let add_mon_btn = Button::builder().icon_name("list-add-symbolic").has_frame(false).valign(gtk::Align::Center).build();
+            let add_manual = {
+                let en = add_mon_en.clone(); let refresh_a = Rc::clone(&refresh_self);
+                move || {
+                    let name = en.text().trim().to_string();
+                    if name.is_empty() { return; }
+                    let mut names = outputs::load_manual_monitors();
+                    if !names.contains(&name) { names.push(name); }
+                    let _ = outputs::save_manual_monitors(&names);
+                    if let Some(f) = &*refresh_a.borrow() { f(); }
+                }
+            };
+            let add_manual_btn = add_manual.clone();
+            add_mon_btn.connect_clicked(move |_| add_manual_btn());
+            add_mon_en.connect_activate(move |_| add_manual());
+            add_mon_row.add_suffix(&add_mon_en);
+            add_mon_row.add_suffix(&add_mon_btn);
+            mon_group.add(&add_mon_row);
+            outputs_page.append(&mon_group);
+
+            // --- Bars ---
+            let bars_group = PreferencesGroup::new();
+            bars_group.set_title("Bars");
+            bars_group.set_description(Some("Each bar has its own modules. Edit switches the columns and Properties to that bar; styles are shared (use Bar Name for per-bar CSS)."));
+            for (idx, bar) in bars.iter().enumerate() {
+                let output = bar.bar.output.clone();
+                let expander = adw::ExpanderRow::new();
+                expander.set_title(&BarSet::label(bar, idx));
+                expander.set_subtitle(&match &output {
+                    None => "All outputs".to_string(),
+                    Some(o) => glib::markup_escape_text(&o.to_vec().join(", ")).to_string(),
+                });
+                if idx == active {
+                    let icon = gtk::Image::from_icon_name("document-edit-symbolic");
+                    icon.set_tooltip_text(Some("Currently editing"));
+                    expander.add_prefix(&icon);
+                } else {
+                    let edit_btn = Button::builder().label("Edit").valign(gtk::Align::Center).build();
+                    let bars_e = Rc::clone(&bars_rc); let config_e = Rc::clone(&config_rc);
+                    let refresh_e = Rc::clone(&refresh_rc); let styles_e = Rc::clone(&refresh_styles_fn);
+                    let refresh_self_e = Rc::clone(&refresh_self); let sel_e = Rc::clone(&sel_state);
+                    let toast_e = toast_outputs.clone();
+                    edit_btn.connect_clicked(move |_| {
+                        bars_e.borrow_mut().switch_to(idx, &mut config_e.borrow_mut());
+                        *sel_e.borrow_mut() = None;
+                        refresh_e();
+                        if let Some(f) = &*styles_e.borrow() { f(); }
+                        if let Some(f) = &*refresh_self_e.borrow() { f(); }
+                        toast_e.add_toast(Toast::new(&format!("Editing Bar {}", idx + 1)));
+                    });
+                    expander.add_suffix(&edit_btn);
+                }
+                if bars.len() > 1 {
+                    let del_btn = Button::builder().icon_name("user-trash-symbolic").has_frame(false).valign(gtk::Align::Center).tooltip_text("Remove Bar").build();
+                    let bars_d = Rc::clone(&bars_rc); let config_d = Rc::clone(&config_rc);
+                    let refresh_d = Rc::clone(&refresh_rc); let styles_d = Rc::clone(&refresh_styles_fn);
+                    let refresh_self_d = Rc::clone(&refresh_self);
+                    del_btn.connect_clicked(move |_| {
+                        bars_d.borrow_mut().remove(idx, &mut config_d.borrow_mut());
+                        refresh_d();
+                        if let Some(f) = &*styles_d.borrow() { f(); }
+                        if let Some(f) = &*refresh_self_d.borrow() { f(); }
+                    });
+                    expander.add_suffix(&del_btn);
+                }
+
+                let assign_opts = ["Not listed", "Show", "Hide (!)"];
+                for monitor in &monitors {
+                    let row = ComboRow::new();
+                    row.set_title(&monitor.name);
+                    row.set_model(Some(&StringList::new(&assign_opts)));
+                    row.set_selected(match outputs::assignment(output.as_ref(), &monitor.name) {
+                        outputs::Assignment::Unlisted => 0,
+                        outputs::Assignment::Include => 1,
+                        outputs::Assignment::Exclude => 2,
+                    });
+                    row.set_subtitle(if outputs::shows_on(output.as_ref(), monitor) { "Bar shown here" } else { "Bar not shown here" });
+                    let set_o = Rc::clone(&set_output); let output_c = output.clone(); let name_c = monitor.name.clone();
+                    row.connect_selected_notify(move |r| {
+                        let to = match r.selected() { 1 => outputs::Assignment::Include, 2 => outputs::Assignment::Exclude, _ => outputs::Assignment::Unlisted };
+                        set_o(idx, outputs::set_assignment(output_c.as_ref(), &name_c, to));
+                    });
+                    expander.add_row(&row);
+                }
+
+                let wildcard_row = ActionRow::builder().title("All Other Outputs (*)").subtitle("Show on every monitor that is not hidden above").build();
+                let has_wildcard = output.as_ref().is_none_or(|o| o.to_vec().iter().any(|e| e == "*"));
+                let wildcard_sw = Switch::builder().active(has_wildcard).valign(gtk::Align::Center).build();
+                let set_w = Rc::clone(&set_output); let output_w = output.clone();
+                wildcard_sw.connect_state_set(move |_, state| {
+                    // With nothing listed, turning the wildcard off would still mean "everywhere".
+                    if state || output_w.is_some() {
+                        set_w(idx, outputs::set_wildcard(output_w.as_ref(), state));
+                    }
+                    glib::Propagation::Proceed
+                });
+                wildcard_row.add_suffix(&wildcard_sw);
+                expander.add_row(&wildcard_row);
+
+                let raw_row = ActionRow::builder().title("Output Value").subtitle("Comma-separated; press Enter to apply").build();
+                let raw_en = Entry::builder()
+                    .text(output.as_ref().map(|o| o.to_vec().join(", ")).unwrap_or_default())
+                    .placeholder_text("!DP-1, *")
+                    .valign(gtk::Align::Center)
+                    .build();
+                let set_raw = Rc::clone(&set_output);
+                raw_en.connect_activate(move |e| {
+                    let items = e.text().split(',').map(|s| s.trim().to_string()).collect();
+                    set_raw(idx, config::OneOrMany::from_vec(items));
+                });
+                raw_row.add_suffix(&raw_en);
+                expander.add_row(&raw_row);
+                bars_group.add(&expander);
+            }
+
+            let add_bar_btn = Button::with_label("Add Bar");
+            add_bar_btn.set_margin_top(6);
+            let bars_a = Rc::clone(&bars_rc); let config_a = Rc::clone(&config_rc); let refresh_a = Rc::clone(&refresh_self);
+            add_bar_btn.connect_clicked(move |_| {
+                // Start from a copy of the edited bar; it shows on every output until assigned.
+                let mut new_bar = config_a.borrow().clone();
+                new_bar.bar.output = None;
+                new_bar.bar.name = None;
+                bars_a.borrow_mut().add(&config_a.borrow(), new_bar);
+                if let Some(f) = &*refresh_a.borrow() { f(); }
+            });
+            bars_group.add(&add_bar_btn);
+            outputs_page.append(&bars_group);
+        };
+        *refresh_outputs_fn.borrow_mut() = Some(Box::new(refresh_outputs.clone()));
+        refresh_outputs();
+    }
+
     // --- Header Actions ---
     let win_rc: Rc<RefCell<Option<ApplicationWindow>>> = Rc::new(RefCell::new(None));
     let t_overlay = toast_overlay.clone();
 
     save_profile_btn.connect_clicked({
         let config_rc = Rc::clone(&config_rc);
+        let bars_rc = Rc::clone(&bars_rc);
         let style_rc = Rc::clone(&style_rc);
+        let targets_rc = Rc::clone(&targets_rc);
         let layout_css_path = layout_css_path.clone();
         let win_rc = Rc::clone(&win_rc);
         let t_save = t_overlay.clone();
@@ -1675,7 +2358,9 @@ fn build_ui(app: &Application) {
             let dialog = FileDialog::builder().title("Save Profile").default_filter(&filter).build();
             
             let config_rc = Rc::clone(&config_rc);
+            let bars_rc = Rc::clone(&bars_rc);
             let style_rc = Rc::clone(&style_rc);
+            let targets_rc = Rc::clone(&targets_rc);
             let layout_css_path = layout_css_path.clone();
             let t_s = t_save.clone();
             
@@ -1686,10 +2371,14 @@ fn build_ui(app: &Application) {
                             let mut path = path.to_path_buf();
                             if path.extension().and_then(|s| s.to_str()) != Some("wc") { path.set_extension("wc"); }
                             
+                            let mut bars = bars_rc.borrow().all(&config_rc.borrow());
+                            let extra_bars = bars.split_off(1);
                             let profile = WaybarProfile {
-                                config: config_rc.borrow().clone(),
+                                config: bars.remove(0),
                                 style_vars: style_rc.borrow().vars.clone(),
                                 layout_css: fs::read_to_string(&layout_css_path).unwrap_or_else(|_| DEFAULT_LAYOUT_CSS.to_string()),
+                                extra_bars,
+                                targets: targets_rc.borrow().clone(),
                             };
                             
                             if let Ok(_) = profile.save_to_file(path.to_str().unwrap()) {
@@ -1704,7 +2393,10 @@ fn build_ui(app: &Application) {
 
     load_profile_btn.connect_clicked({
         let config_rc = Rc::clone(&config_rc);
+        let bars_rc = Rc::clone(&bars_rc);
+        let refresh_outputs_fn = Rc::clone(&refresh_outputs_fn);
         let style_rc = Rc::clone(&style_rc);
+        let targets_rc = Rc::clone(&targets_rc);
         let layout_css_path = layout_css_path.clone();
         let win_rc = Rc::clone(&win_rc);
         let refresh_rc = Rc::clone(&refresh_rc);
@@ -1716,7 +2408,10 @@ fn build_ui(app: &Application) {
             let dialog = FileDialog::builder().title("Load Profile").default_filter(&filter).build();
             
             let config_rc = Rc::clone(&config_rc);
+            let bars_rc = Rc::clone(&bars_rc);
+            let refresh_outputs_fn = Rc::clone(&refresh_outputs_fn);
             let style_rc = Rc::clone(&style_rc);
+            let targets_rc = Rc::clone(&targets_rc);
             let layout_css_path = layout_css_path.clone();
             let refresh_rc = Rc::clone(&refresh_rc);
             let refresh_styles_fn = Rc::clone(&refresh_styles_fn);
@@ -1727,12 +2422,18 @@ fn build_ui(app: &Application) {
                     if let Ok(file) = res {
                         if let Some(path) = file.path() {
                             if let Ok(profile) = WaybarProfile::from_file(path.to_str().unwrap()) {
+                                let mut bars = vec![profile.config.clone()];
+                                bars.extend(profile.extra_bars);
+                                *bars_rc.borrow_mut() = BarSet::new(bars);
                                 *config_rc.borrow_mut() = profile.config;
                                 style_rc.borrow_mut().vars = profile.style_vars;
+                                style_rc.borrow_mut().path = profile.targets.palette();
+                                *targets_rc.borrow_mut() = profile.targets;
                                 let _ = fs::write(&layout_css_path, profile.layout_css);
                                 
                                 refresh_rc();
                                 if let Some(f) = &*refresh_styles_fn.borrow() { f(); }
+                                if let Some(f) = &*refresh_outputs_fn.borrow() { f(); }
                                 t_l.add_toast(Toast::new("Profile Loaded"));
                             }
                         }
@@ -1746,38 +2447,47 @@ fn build_ui(app: &Application) {
 
     apply_btn.connect_clicked({
         let config_rc = Rc::clone(&config_rc);
+        let bars_rc = Rc::clone(&bars_rc);
         let style_rc = Rc::clone(&style_rc);
         let layout_css_path_apply = layout_css_path.clone();
+        let targets_rc = Rc::clone(&targets_rc);
         let t_apply = t_overlay.clone();
         move |_| {
-            let home = std::env::var("HOME").unwrap_or_default();
-            let waybar_cfg_dir = PathBuf::from(home).join(".config/waybar");
-            let _ = fs::create_dir_all(&waybar_cfg_dir);
-            let target_cfg = waybar_cfg_dir.join("config.jsonc");
-            let target_style = waybar_cfg_dir.join("colors/wallpaper.css");
-            let target_layout = waybar_cfg_dir.join("style.css");
-            let _ = fs::create_dir_all(waybar_cfg_dir.join("colors"));
+            let targets = targets_rc.borrow().clone();
+            let target_cfg = targets.config();
+            let target_style = targets.palette();
+            let target_layout = targets.style();
+            for p in [&target_cfg, &target_style, &target_layout] {
+                if let Some(dir) = p.parent() { let _ = fs::create_dir_all(dir); }
+            }
             
-            // Sync layout templates to ~/.config/waybar/layouts
-            let layouts_src = PathBuf::from("presets/layouts");
-            let layouts_dst = waybar_cfg_dir.join("layouts");
+            // Render layout templates (with their parameters) next to the style sheet, which
+            // imports them as `layouts/<name>.css`
+            let layouts_dst = targets.style_dir().join("layouts");
             let _ = fs::create_dir_all(&layouts_dst);
-            if let Ok(entries) = fs::read_dir(layouts_src) {
-                for entry in entries.flatten() {
-                    let path = entry.path();
-                    if path.is_file() {
-                        if let Some(name) = path.file_name() {
-                            let _ = fs::copy(&path, layouts_dst.join(name));
-                        }
-                    }
-                }
+            let vars = style_rc.borrow().vars.clone();
+            let vertical = config_rc.borrow().bar.is_vertical();
+            for tpl in layout::list_layouts() {
+                let css = if vertical { css::swap_axes(&tpl.render(&vars)) } else { tpl.render(&vars) };
+                let _ = fs::write(layouts_dst.join(&tpl.file_name), css);
             }
 
-            let _ = config_rc.borrow().save_to_file(target_cfg.to_str().unwrap());
+            let bars = bars_rc.borrow().all(&config_rc.borrow());
+            let _ = WaybarConfig::save_all(&bars, &target_cfg);
             let _ = style_rc.borrow().save_to(&target_style);
-            
+
+            let export_settings = ExportSettings::load();
+            if export_settings.export_on_apply {
+                let (_, errors) = export_settings.export_all(&style_rc.borrow().vars);
+                if !errors.is_empty() {
+                    t_apply.add_toast(Toast::new(&glib::markup_escape_text(&format!("Palette export failed: {}", errors.join("; ")))));
+                }
+            }
+
             // Persist session CSS to the real Waybar path
             if let Ok(css) = fs::read_to_string(&layout_css_path_apply) {
+                let css = css::apply_metrics_block(&css, &style_rc.borrow().vars, config_rc.borrow().bar.is_vertical());
+                let css = targets::retarget_palette_import(&css, &targets.palette_import());
                 let _ = fs::write(&target_layout, css);
             } else {
                 let _ = fs::write(&target_layout, DEFAULT_LAYOUT_CSS);
@@ -1791,7 +2501,11 @@ fn build_ui(app: &Application) {
             } else {
                 // If waybar isn't running or reload failed, restart it.
                 let _ = Command::new("pkill").args(["-x", "waybar"]).status();
-                let _ = Command::new("waybar").spawn();
+                // Waybar only finds files outside its own search path when told where they are.
+                let mut waybar = Command::new("waybar");
+                if target_cfg != TargetPaths::default_config() { waybar.arg("-c").arg(&target_cfg); }
+                if target_layout != TargetPaths::default_style() { waybar.arg("-s").arg(&target_layout); }
+                let _ = waybar.spawn();
                 let escaped = glib::markup_escape_text("Applied & Started Waybar");
                 t_apply.add_toast(Toast::new(&escaped));
             }
@@ -1848,10 +2562,10 @@ fn build_ui(app: &Application) {
 
     refresh_ui();
 
-    let build_col = |name: &str, list: &ListBox, col_id: &str, config_rc: Rc<RefCell<WaybarConfig>>, refresh_rc: Rc<dyn Fn()>, options: &[&str], sel_state: Rc<RefCell<Option<(String, String)>>>| {
+    let build_col = |l: &Label, list: &ListBox, col_id: &str, config_rc: Rc<RefCell<WaybarConfig>>, refresh_rc: Rc<dyn Fn()>, options: &[&str], sel_state: Rc<RefCell<Option<(String, String)>>>| {
         let b = GtkBox::new(Orientation::Vertical, 6);
         let h = GtkBox::new(Orientation::Horizontal, 6);
-        let l = Label::new(Some(name)); l.add_css_class("title-4"); h.append(&l);
+        l.add_css_class("title-4"); h.append(l);
         
         let spacer = GtkBox::new(Orientation::Horizontal, 0);
         spacer.set_hexpand(true);
@@ -1894,6 +2608,14 @@ fn build_ui(app: &Application) {
         
         for opt in options {
             let r = ActionRow::builder().title(*opt).activatable(true).build();
+            if let (Some(target), Some(current)) = (compositor::module_target(opt), running_compositor) {
+                if target == current {
+                    r.set_subtitle(&format!("For {} (running)", current.label()));
+                } else {
+                    r.set_subtitle(&format!("For {}, not the running compositor", target.label()));
+                    r.add_css_class("dim-label");
+                }
+            }
             let opt_s = opt.to_string(); let cfg_pop = Rc::clone(&config_rc); let ref_pop = Rc::clone(&refresh_rc); let cid_pop = col_id.to_string(); let p_close = popover.clone();
             r.connect_activated(move |_| {
                 let mut cfg = cfg_pop.borrow_mut();
@@ -1966,10 +2688,10 @@ fn build_ui(app: &Application) {
         b
     };
 
-    let col_opts: Vec<&str> = module_options.iter().map(|s| *s).collect();
-    columns_box.append(&build_col("Left", &left_list, "left", Rc::clone(&config_rc), Rc::clone(&refresh_rc), &col_opts, Rc::clone(&selected_module_state)));
-    columns_box.append(&build_col("Center", &center_list, "center", Rc::clone(&config_rc), Rc::clone(&refresh_rc), &col_opts, Rc::clone(&selected_module_state)));
-    columns_box.append(&build_col("Right", &right_list, "right", Rc::clone(&config_rc), Rc::clone(&refresh_rc), &col_opts, Rc::clone(&selected_module_state)));
+    let col_opts: Vec<&str> = compositor::rank_modules(&module_options, running_compositor);
+    columns_box.append(&build_col(&column_labels[0], &left_list, "left", Rc::clone(&config_rc), Rc::clone(&refresh_rc), &col_opts, Rc::clone(&selected_module_state)));
+    columns_box.append(&build_col(&column_labels[1], &center_list, "center", Rc::clone(&config_rc), Rc::clone(&refresh_rc), &col_opts, Rc::clone(&selected_module_state)));
+    columns_box.append(&build_col(&column_labels[2], &right_list, "right", Rc::clone(&config_rc), Rc::clone(&refresh_rc), &col_opts, Rc::clone(&selected_module_state)));
 
     paned.set_start_child(Some(&columns_box));
     paned.set_end_child(Some(&settings_panel));
@@ -1980,7 +2702,7 @@ fn build_ui(app: &Application) {
     *win_rc.borrow_mut() = Some(win.clone());
 
     // --- Startup Check ---
-    if let Some(local_path) = get_waybar_config_path() {
+    if let Some(local_path) = targets::find_config() {
         let dialog = MessageDialog::builder()
             .transient_for(&win)
             .heading("Welcome to WaybarConf")
@@ -1994,32 +2716,50 @@ fn build_ui(app: &Application) {
         dialog.set_response_appearance("load", adw::ResponseAppearance::Suggested);
         
         let config_rc_startup = Rc::clone(&config_rc);
+        let bars_startup = Rc::clone(&bars_rc);
+        let refresh_outputs_startup = Rc::clone(&refresh_outputs_fn);
         let style_rc_startup = Rc::clone(&style_rc);
+        let targets_startup = Rc::clone(&targets_rc);
         let layout_css_path_startup = layout_css_path.clone();
         let refresh_ui_startup = Rc::clone(&refresh_rc);
         let refresh_styles_startup = Rc::clone(&refresh_styles_fn);
+        let toast_startup = toast_overlay.clone();
         
         dialog.connect_response(None, move |d, response| {
             match response {
                 "load" => {
-                    if let Ok(new_cfg) = WaybarConfig::from_file(&local_path) {
-                        *config_rc_startup.borrow_mut() = new_cfg;
+                    if let Some(bars) = WaybarConfig::load_all(&local_path).ok().filter(|b| !b.is_empty()) {
+                        *config_rc_startup.borrow_mut() = bars[0].clone();
+                        *bars_startup.borrow_mut() = BarSet::new(bars);
                         
-                        let home = std::env::var("HOME").unwrap_or_default();
-                        let local_style_path = PathBuf::from(home.clone()).join(".config/waybar/colors/wallpaper.css");
+                        let targets = targets_startup.borrow().clone();
+                        let local_style_path = targets.palette();
+                        let local_layout_path = targets.style();
+                        // Colors may live anywhere style.css imports from (pywal's cache, a theme
+                        // dir…); unless set, the palette target is the import that defines most of them.
                         if local_style_path.exists() {
                             *style_rc_startup.borrow_mut() = StyleConfig::from_file(&local_style_path);
                         }
+                        if local_layout_path.exists() {
+                            style_rc_startup.borrow_mut().adopt_imports(&local_layout_path, &layout_css_path_startup);
+                        }
                         
-                        let local_layout_path = PathBuf::from(home).join(".config/waybar/style.css");
+                        let mut loaded_css = false;
                         if local_layout_path.exists() {
                             if let Ok(css) = fs::read_to_string(&local_layout_path) {
-                                let _ = fs::write(&layout_css_path_startup, css);
+                                loaded_css = fs::write(&layout_css_path_startup, css).is_ok();
                             }
                         }
                         
                         refresh_ui_startup();
                         if let Some(f) = &*refresh_styles_startup.borrow() { f(); }
+
+                        // A hand-written style.css usually hard-codes its colors.
+                        if loaded_css && !recolor::find_colors(&fs::read_to_string(&layout_css_path_startup).unwrap_or_default()).is_empty() {
+                            let refresh_rc_c = Rc::clone(&refresh_styles_startup);
+                            let on_done: Rc<dyn Fn()> = Rc::new(move || { if let Some(f) = &*refresh_rc_c.borrow() { f(); } });
+                            recolor_dialog(d.transient_for(), &layout_css_path_startup, &style_rc_startup, &toast_startup, on_done);
+                        }
                     }
                 }
                 "template" => {
@@ -2034,7 +2774,7 @@ fn build_ui(app: &Application) {
                         modules_left: vec![],
                         modules_center: vec![],
                         modules_right: vec![],
-                        position: None,
+                        bar: config::BarSettings::default(),
                         module_definitions: indexmap::IndexMap::new(),
                     };
                     style_rc_startup.borrow_mut().vars = indexmap::IndexMap::new();
@@ -2044,6 +2784,10 @@ fn build_ui(app: &Application) {
                 }
                 _ => {}
             }
+            if response != "load" {
+                *bars_startup.borrow_mut() = BarSet::new(vec![config_rc_startup.borrow().clone()]);
+            }
+            if let Some(f) = &*refresh_outputs_startup.borrow() { f(); }
             d.close();
         });
         dialog.present();
@@ -2052,6 +2796,154 @@ fn build_ui(app: &Application) {
     win.present();
 }
 
+/// Palette variables the session stylesheet and its active layout template reference.
+fn vars_in_use(css_path: &Path) -> Vec<String> {
+    let session_css = fs::read_to_string(css_path).unwrap_or_default();
+    let mut names = palette::referenced_vars(&session_css);
+    if let Some(tpl) = layout::active_layout(&session_css) {
+        for name in palette::referenced_vars(&tpl.render(&indexmap::IndexMap::new())) {
+            if !names.contains(&name) { names.push(name); }
+        }
+    }
+    names
+}
+
+/// Toast text after a palette replace, naming the colors kept because the stylesheet uses them.
+fn palette_applied_message(msg: &str, kept: &[String]) -> String {
+    if kept.is_empty() { return msg.to_string(); }
+    let names: Vec<String> = kept.iter().map(|k| format!("@{}", k)).collect();
+    glib::markup_escape_text(&format!("{}; kept {} still used by style.css", msg, names.join(", "))).to_string()
+}
+
+/// A slider row bound to one property of a CSS rule in the session stylesheet. `to_value`
+/// turns the slider value into the CSS value (empty removes the property); `note` tells
+/// the user what the active layout template sets for the same property.
+#[allow(clippy::too_many_arguments)]
+fn css_scale_row(path: &Path, selector: &'static str, prop: &'static str, title: &str, min: f64, max: f64, current: f64, to_value: fn(f64) -> String, note: Option<String>) -> ActionRow {
+    let row = ActionRow::new();
+    row.set_title(title);
+    if let Some(n) = note { row.set_subtitle(&n); }
+    let scale = gtk::Scale::with_range(Orientation::Horizontal, min, max, 1.0);
+    scale.set_value(current);
+    scale.set_width_request(150);
+    scale.set_valign(gtk::Align::Center);
+    let path = path.to_path_buf();
+    scale.connect_value_changed(move |s| {
+        update_selector_css(&path, selector, prop, &to_value(s.value()));
+    });
+    row.add_suffix(&scale);
+    row
+}
+
+/// A strip of equally wide color swatches, used for palette previews.
+fn swatch_strip(colors: Vec<gdk::RGBA>) -> gtk::DrawingArea {
+    let strip = gtk::DrawingArea::new();
+    strip.set_draw_func(move |_, cr, w, h| {
+        if colors.is_empty() { return; }
+        let step = w as f64 / colors.len() as f64;
+        for (i, c) in colors.iter().enumerate() {
+            cr.set_source_rgba(c.red() as f64, c.green() as f64, c.blue() as f64, c.alpha() as f64);
+            cr.rectangle(i as f64 * step, 0.0, step + 0.5, h as f64);
+            let _ = cr.fill();
+        }
+    });
+    strip
+}
+
+/// Header button with the files Apply writes. Each entry shows the resolved default as its
+/// placeholder; clearing it goes back to that default.
+fn target_paths_button(targets_rc: &Rc<RefCell<TargetPaths>>, style_rc: &Rc<RefCell<StyleConfig>>) -> gtk::MenuButton {
+    let list = ListBox::new();
+    list.add_css_class("boxed-list");
+    list.set_selection_mode(gtk::SelectionMode::None);
+    list.set_width_request(460);
+    type Field = (&'static str, &'static str, fn(&TargetPaths) -> Option<PathBuf>, fn(&TargetPaths) -> PathBuf, fn(&mut TargetPaths, Option<PathBuf>));
+    let fields: [Field; 3] = [
+        ("Config", "Bars and modules", |t| t.config.clone(), |_| TargetPaths::default_config(), |t, p| t.config = p),
+        ("Style Sheet", "style.css with the layout and overrides", |t| t.style.clone(), |_| TargetPaths::default_style(), |t, p| t.style = p),
+        ("Palette", "@define-color variables, by default the file style.css imports them from", |t| t.palette.clone(), |t| TargetPaths::palette_for(&t.style()), |t, p| t.palette = p),
+    ];
+    let entries: Vec<(Entry, Field)> = fields.into_iter().map(|field| {
+        let (title, subtitle, _, _, set) = field;
+        let row = ActionRow::builder().title(title).subtitle(subtitle).build();
+        let entry = Entry::builder().valign(gtk::Align::Center).hexpand(true).build();
+        let targets_c = Rc::clone(targets_rc); let style_c = Rc::clone(style_rc);
+        entry.connect_changed(move |e| {
+            let mut targets = targets_c.borrow_mut();
+            set(&mut targets, TargetPaths::parse(&e.text()));
+            style_c.borrow_mut().path = targets.palette();
+        });
+        row.add_suffix(&entry);
+        list.append(&row);
+        (entry, field)
+    }).collect();
+
+    let reset = Button::with_label("Reset to Defaults");
+    reset.set_halign(gtk::Align::End);
+    let vbox = GtkBox::new(Orientation::Vertical, 8);
+    vbox.set_margin_top(8); vbox.set_margin_bottom(8); vbox.set_margin_start(8); vbox.set_margin_end(8);
+    let heading = Label::new(Some("Apply writes to these files. Empty fields follow Waybar's search path under $XDG_CONFIG_HOME."));
+    heading.set_wrap(true); heading.set_max_width_chars(50); heading.set_xalign(0.0); heading.add_css_class("dim-label");
+    vbox.append(&heading);
+    vbox.append(&list);
+    vbox.append(&reset);
+    let popover = gtk::Popover::builder().child(&vbox).build();
+
+    // Fill from the current targets each time, since loading a profile replaces them.
+    let entries = Rc::new(entries);
+    let targets_c = Rc::clone(targets_rc);
+    let entries_c = Rc::clone(&entries);
+    popover.connect_show(move |_| {
+        let targets = targets_c.borrow().clone();
+        for (entry, (_, _, get, default, _)) in entries_c.iter() {
+            entry.set_placeholder_text(Some(&imports::display_path(&default(&targets))));
+            entry.set_text(&get(&targets).map(|p| imports::display_path(&p)).unwrap_or_default());
+        }
+    });
+    reset.connect_clicked(move |_| {
+        for (entry, _) in entries.iter() { entry.set_text(""); }
+    });
+    gtk::MenuButton::builder().icon_name("document-save-as-symbolic").tooltip_text("Target Files").popover(&popover).build()
+}
+
+/// The `@import` graph of the stylesheet as an indented list: each file with the color
+/// variables it defines, flagging missing files and ones imported twice. A definition
+/// that a later one overrides is struck through.
+fn import_tree_list(tree: &imports::ImportNode) -> ListBox {
+    fn add(list: &ListBox, node: &imports::ImportNode, winners: &indexmap::IndexMap<String, (String, PathBuf)>, depth: u32) {
+        let row = ActionRow::builder().title(glib::markup_escape_text(&imports::display_path(&node.path)).as_str()).build();
+        let (icon, subtitle) = match node.status {
+            imports::ImportStatus::Missing => ("dialog-warning-symbolic", "File not found".to_string()),
+            imports::ImportStatus::Repeated => ("view-refresh-symbolic", "Already imported above".to_string()),
+            imports::ImportStatus::Ok if node.defines.is_empty() => ("text-x-generic-symbolic", "No color variables".to_string()),
+            imports::ImportStatus::Ok => {
+                let names: Vec<String> = node.defines.iter().map(|(name, _)| {
+                    let shadowed = winners.get(name).is_some_and(|(_, src)| *src != node.path);
+                    let name = glib::markup_escape_text(&format!("@{}", name)).to_string();
+                    if shadowed { format!("<s>{}</s>", name) } else { name }
+                }).collect();
+                ("preferences-color-symbolic", format!("{} color{}: {}", names.len(), if names.len() == 1 { "" } else { "s" }, names.join(", ")))
+            }
+        };
+        row.add_prefix(&gtk::Image::from_icon_name(icon));
+        row.set_subtitle(&subtitle);
+        if node.status == imports::ImportStatus::Missing { row.add_css_class("error"); }
+        if depth > 0 {
+            row.set_margin_start((depth * 20) as i32);
+            row.add_css_class("nested-row");
+        }
+        list.append(&row);
+        for child in &node.children { add(list, child, winners, depth + 1); }
+    }
+    let list = ListBox::new();
+    list.add_css_class("boxed-list");
+    list.set_selection_mode(gtk::SelectionMode::None);
+    list.set_margin_top(8); list.set_margin_bottom(8); list.set_margin_start(8); list.set_margin_end(8);
+    let winners = imports::definitions(tree);
+    add(&list, tree, &winners, 0);
+    list
+}
+
 fn create_module_row(n: &str, depth: u32) -> ActionRow {
     let is_group = n.starts_with("group/");
     let icon = if is_group { "folder-symbolic" } 
@@ -2140,3 +3032,1785 @@ fn get_flat_mapping(cfg: &WaybarConfig, col: &str) -> Vec<(Option<String>, Strin
     walk(cfg, root, None, &mut mapping);
     mapping
 }
+
+/// Runs `f` on the object stored under `field` in a module definition, creating both when
+/// missing. An object left empty is removed again.
+fn edit_module_map(config_rc: &Rc<RefCell<WaybarConfig>>, mod_name: &str, field: &str, f: impl FnOnce(&mut serde_json::Map<String, serde_json::Value>)) {
+    let mut cfg = config_rc.borrow_mut();
+    let def = cfg.module_definitions.entry(mod_name.to_string()).or_insert_with(|| serde_json::json!({}));
+    let Some(obj) = def.as_object_mut() else { return };
+    let mut map = obj.get(field).and_then(|v| v.as_object()).cloned().unwrap_or_default();
+    f(&mut map);
+    if map.is_empty() { obj.remove(field); } else { obj.insert(field.to_string(), serde_json::Value::Object(map)); }
+}
+
+/// Sets (or with an empty `value`, removes) a string option of a module.
+fn set_module_string(config_rc: &Rc<RefCell<WaybarConfig>>, mod_name: &str, key: &str, value: &str) {
+    let mut cfg = config_rc.borrow_mut();
+    let def = cfg.module_definitions.entry(mod_name.to_string()).or_insert_with(|| serde_json::json!({}));
+    if let Some(obj) = def.as_object_mut() {
+        if value.is_empty() { obj.remove(key); } else { obj.insert(key.to_string(), serde_json::json!(value)); }
+    }
+}
+
+/// An expander listing the entries of a JSON object option (`format-icons`,
+/// `window-rewrite`...) as editable key/value rows, plus a row to add one.
+#[allow(clippy::too_many_arguments)]
+fn map_editor(title: &str, subtitle: &str, config_rc: &Rc<RefCell<WaybarConfig>>, mod_name: &str, field: &'static str,
+              placeholders: (&str, &str), to_text: fn(&serde_json::Value) -> String, from_text: fn(&str) -> serde_json::Value,
+              rebuild: Rc<dyn Fn()>) -> adw::ExpanderRow {
+    let expander = adw::ExpanderRow::new();
+    expander.set_title(title);
+    expander.set_subtitle(subtitle);
+    let entries = config_rc.borrow().module_definitions.get(mod_name)
+        .and_then(|d| d.get(field)).and_then(|v| v.as_object()).cloned().unwrap_or_default();
+
+    for (key, value) in entries {
+        let row = ActionRow::new();
+        let key_en = Entry::builder().text(&key).valign(gtk::Align::Center).hexpand(true).build();
+        let value_en = Entry::builder().text(to_text(&value)).valign(gtk::Align::Center).width_chars(10).build();
+        let current_key = Rc::new(RefCell::new(key));
+
+        let cfg_k = Rc::clone(config_rc); let mn_k = mod_name.to_string(); let ck = Rc::clone(&current_key);
+        key_en.connect_changed(move |e| {
+            let new_key = e.text().to_string();
+            if new_key.is_empty() { return; }
+            let old_key = ck.borrow().clone();
+            let mut renamed = false;
+            edit_module_map(&cfg_k, &mn_k, field, |m| renamed = workspaces::rename_key(m, &old_key, &new_key));
+            // A key another entry already has is left unapplied; this row keeps editing its own entry.
+            if renamed {
+                e.remove_css_class("error");
+                *ck.borrow_mut() = new_key;
+            } else {
+                e.add_css_class("error");
+            }
+        });
+        let cfg_v = Rc::clone(config_rc); let mn_v = mod_name.to_string(); let ck_v = Rc::clone(&current_key);
+        value_en.connect_changed(move |e| {
+            let key = ck_v.borrow().clone();
+            edit_module_map(&cfg_v, &mn_v, field, |m| { m.insert(key, from_text(&e.text())); });
+        });
+        let del_btn = Button::builder().icon_name("user-trash-symbolic").has_frame(false).valign(gtk::Align::Center).build();
+        let cfg_d = Rc::clone(config_rc); let mn_d = mod_name.to_string(); let rebuild_d = Rc::clone(&rebuild);
+        del_btn.connect_clicked(move |_| {
+            let key = current_key.borrow().clone();
+            edit_module_map(&cfg_d, &mn_d, field, |m| { m.shift_remove(&key); });
+            rebuild_d();
+        });
+        row.add_prefix(&key_en);
+        row.add_suffix(&value_en);
+        row.add_suffix(&del_btn);
+        expander.add_row(&row);
+    }
+
+    let add_row = ActionRow::new();
+    let new_key = Entry::builder().placeholder_text(placeholders.0).valign(gtk::Align::Center).hexpand(true).build();
+    let new_value = Entry::builder().placeholder_text(placeholders.1).valign(gtk::Align::Center).width_chars(10).build();
+    let add_btn = Button::builder().icon_name("list-add-symbolic").has_frame(false).valign(gtk::Align::Center).build();
+    let cfg_a = Rc::clone(config_rc); let mn_a = mod_name.to_string();
+    let key_a = new_key.clone(); let value_a = new_value.clone();
+    add_btn.connect_clicked(move |_| {
+        let key = key_a.text().trim().to_string();
+        if key.is_empty() { return; }
+        let value = from_text(&value_a.text());
+        edit_module_map(&cfg_a, &mn_a, field, |m| { m.insert(key, value); });
+        rebuild();
+    });
+    add_row.add_prefix(&new_key);
+    add_row.add_suffix(&new_value);
+    add_row.add_suffix(&add_btn);
+    expander.add_row(&add_row);
+    expander
+}
+
+/// Editor for `hyprland/workspaces` and `sway/workspaces`: icons per workspace or state,
+/// persistent workspaces and `window-rewrite` rules with a tester.
+fn workspaces_editor(mod_name: &str, config_rc: &Rc<RefCell<WaybarConfig>>, rebuild: Rc<dyn Fn()>) -> PreferencesGroup {
+    let hyprland = mod_name.starts_with("hyprland/");
+    let group = PreferencesGroup::new();
+    group.set_title("Workspaces");
+    let def = config_rc.borrow().module_definitions.get(mod_name).cloned().unwrap_or_else(|| serde_json::json!({}));
+    let as_text: fn(&serde_json::Value) -> String = |v| v.as_str().map(|s| s.to_string()).unwrap_or_else(|| v.to_string());
+    let as_string: fn(&str) -> serde_json::Value = |t| serde_json::json!(t);
+
+    group.add(&map_editor("Workspace Icons", &format!("format-icons: a workspace name or a state ({})", workspaces::STATE_ICON_KEYS.join(", ")),
+        config_rc, mod_name, "format-icons", ("1, active, default…", "icon"), as_text, as_string, Rc::clone(&rebuild)));
+
+    let parse: fn(&str) -> serde_json::Value = if hyprland {
+        |t| workspaces::parse_persistent(t, true)
+    } else {
+        |t| workspaces::parse_persistent(t, false)
+    };
+    let (persist_sub, persist_ph) = if hyprland {
+        ("Monitor (or * for all) → workspace count or list of ids", ("* or DP-1", "5 or 1, 2, 3"))
+    } else {
+        ("Workspace → outputs it is created on (empty = all)", ("workspace", "DP-1, HDMI-A-1"))
+    };
+    group.add(&map_editor("Persistent Workspaces", persist_sub, config_rc, mod_name, "persistent-workspaces",
+        persist_ph, workspaces::persistent_text, parse, Rc::clone(&rebuild)));
+
+    let format = def.get("format").and_then(|v| v.as_str()).unwrap_or("");
+    let rewrite_sub = if format.contains("{windows}") {
+        "Each key is a regex searched in class<CLASS> title<TITLE>; rules naming the title win".to_string()
+    } else {
+        "Add {windows} to the format to show these icons".to_string()
+    };
+    let rewrite = map_editor("Window Rewrite", &rewrite_sub, config_rc, mod_name, "window-rewrite",
+        ("class<firefox>", "icon"), as_text, as_string, Rc::clone(&rebuild));
+
+    for (key, label) in [("window-rewrite-default", "Default Window Icon"), ("format-window-separator", "Window Separator")] {
+        let row = ActionRow::builder().title(label).subtitle(key).build();
+        let en = Entry::builder().text(def.get(key).and_then(|v| v.as_str()).unwrap_or("")).valign(gtk::Align::Center).width_chars(10).build();
+        let cfg_s = Rc::clone(config_rc); let mn_s = mod_name.to_string();
+        en.connect_changed(move |e| set_module_string(&cfg_s, &mn_s, key, &e.text()));
+        row.add_suffix(&en);
+        rewrite.add_row(&row);
+    }
+
+    let rules = def.get("window-rewrite").and_then(|v| v.as_object()).cloned().unwrap_or_default();
+    for (key, err) in workspaces::rule_errors(&rules) {
+        let row = ActionRow::builder().title(glib::markup_escape_text(&format!("Invalid rule: {}", key))).subtitle(glib::markup_escape_text(err.lines().last().unwrap_or(""))).build();
+        row.add_css_class("error");
+        rewrite.add_row(&row);
+    }
+
+    // Tester: runs a sample window through the rules as currently edited.
+    let test_row = ActionRow::builder().title("Test Window").build();
+    let class_en = Entry::builder().placeholder_text("class / app_id").valign(gtk::Align::Center).width_chars(10).build();
+    let title_en = Entry::builder().placeholder_text("title").valign(gtk::Align::Center).width_chars(10).build();
+    test_row.add_suffix(&class_en);
+    test_row.add_suffix(&title_en);
+    rewrite.add_row(&test_row);
+    let result_row = ActionRow::builder().title("Result").subtitle("Enter a class and title to test").build();
+    rewrite.add_row(&result_row);
+    let run_test = {
+        let cfg_t = Rc::clone(config_rc); let mn_t = mod_name.to_string();
+        let class_en = class_en.clone(); let title_en = title_en.clone();
+        move || {
+            let cfg = cfg_t.borrow();
+            let def = cfg.module_definitions.get(&mn_t);
+            let rules = def.and_then(|d| d.get("window-rewrite")).and_then(|v| v.as_object()).cloned().unwrap_or_default();
+            let default = def.and_then(|d| d.get("window-rewrite-default")).and_then(|v| v.as_str());
+            let text = match workspaces::rewrite(&rules, default, &class_en.text(), &title_en.text()) {
+                workspaces::RewriteOutcome::Rule { key, icon } => format!("{}  (rule {})", icon, key),
+                workspaces::RewriteOutcome::Default(icon) => format!("{}  (no rule matched, default icon)", icon),
+                workspaces::RewriteOutcome::NoMatch => "No rule matched and no default icon".to_string(),
+            };
+            result_row.set_subtitle(&glib::markup_escape_text(&text));
+        }
+    };
+    let run_class = run_test.clone();
+    class_en.connect_changed(move |_| run_class());
+    title_en.connect_changed(move |_| run_test());
+    group.add(&rewrite);
+    group
+}
+
+/// Current time in the module's first timezone (local time when none is listed).
+fn clock_now(def: Option<&serde_json::Value>) -> Option<glib::DateTime> {
+    let tz = def.and_then(|d| d.get("timezones")).and_then(|v| v.as_array())
+        .and_then(|a| a.first()).and_then(|v| v.as_str())
+        .or_else(|| def.and_then(|d| d.get("timezone")).and_then(|v| v.as_str()))
+        .filter(|t| !t.is_empty());
+    match tz {
+        Some(t) => glib::DateTime::now(&glib::TimeZone::new(Some(t))).ok(),
+        None => glib::DateTime::now_local().ok(),
+    }
+}
+
+fn calendar_style(def: Option<&serde_json::Value>) -> clock::CalendarStyle {
+    let cal = def.and_then(|d| d.get("calendar"));
+    let get = |k: &str| cal.and_then(|c| c.get(k));
+    let fmt = |k: &str| cal.and_then(|c| c.get("format")).and_then(|f| f.get(k)).and_then(|v| v.as_str()).map(|s| s.to_string());
+    clock::CalendarStyle {
+        year_mode: get("mode").and_then(|v| v.as_str()) == Some("year"),
+        months_per_row: get("mode-mon-col").and_then(|v| v.as_u64()).unwrap_or(3) as u32,
+        weeks_left: match get("weeks-pos").and_then(|v| v.as_str()) { Some("left") => Some(true), Some("right") => Some(false), _ => None },
+        fmt_months: fmt("months"),
+        fmt_days: fmt("days"),
+        fmt_weeks: fmt("weeks"),
+        fmt_weekdays: fmt("weekdays"),
+        fmt_today: fmt("today"),
+    }
+}
+
+/// Sets `calendar.<key>` (or `calendar.format.<key>` when `in_format`); `None` removes it.
+fn set_calendar_option(config_rc: &Rc<RefCell<WaybarConfig>>, mod_name: &str, key: &str, in_format: bool, value: Option<serde_json::Value>) {
+    edit_module_map(config_rc, mod_name, "calendar", |cal| {
+        if !in_format {
+            match value { Some(v) => { cal.insert(key.to_string(), v); } None => { cal.shift_remove(key); } }
+            return;
+        }
+        let mut fmt = cal.get("format").and_then(|f| f.as_object()).cloned().unwrap_or_default();
+        match value { Some(v) => { fmt.insert(key.to_string(), v); } None => { fmt.shift_remove(key); } }
+        if fmt.is_empty() { cal.shift_remove("format"); } else { cal.insert("format".to_string(), serde_json::Value::Object(fmt)); }
+    });
+}
+
+/// Label showing Pango markup, falling back to plain text when the markup is invalid.
+fn set_markup_or_text(label: &Label, text: &str) {
+    if gtk::pango::parse_markup(text, '\0').is_ok() { label.set_markup(text); } else { label.set_text(text); }
+}
+
+/// Button with a popover of strftime tokens and clock placeholders; picking one inserts
+/// it at the cursor of `entry`.
+fn token_picker(entry: &Entry) -> gtk::MenuButton {
+    let list = ListBox::new();
+    list.set_selection_mode(gtk::SelectionMode::None);
+    for (token, desc) in clock::STRFTIME_TOKENS.iter().chain(clock::CLOCK_PLACEHOLDERS) {
+        let row = ActionRow::builder().title(*desc).subtitle(*token).activatable(true).build();
+        let entry_c = entry.clone();
+        row.connect_activated(move |_| {
+            let (text, pos) = clock::insert_token(&entry_c.text(), entry_c.position().max(0) as usize, token);
+            entry_c.set_text(&text);
+            entry_c.set_position(pos as i32);
+        });
+        list.append(&row);
+    }
+    let popover = gtk::Popover::builder()
+        .child(&ScrolledWindow::builder().child(&list).max_content_height(300).propagate_natural_height(true).build())
+        .build();
+    gtk::MenuButton::builder().icon_name("list-add-symbolic").tooltip_text("Insert Token").popover(&popover).valign(gtk::Align::Center).build()
+}
+
+/// Nerd Font browser for `entry`: search by name, filter by category, and insert at the
+/// cursor.
+fn glyph_picker(entry: &Entry) -> gtk::MenuButton {
+    let vbox = GtkBox::new(Orientation::Vertical, 6);
+    vbox.set_margin_top(8); vbox.set_margin_bottom(8); vbox.set_margin_start(8); vbox.set_margin_end(8);
+    let search = SearchEntry::builder().placeholder_text("Search glyphs (battery, wifi, arch…)").build();
+    let categories = glyphs::categories();
+    let mut cat_labels = vec!["All Categories"];
+    cat_labels.extend(categories.iter().copied());
+    let cat_drop = gtk::DropDown::from_strings(&cat_labels);
+    let top = GtkBox::new(Orientation::Horizontal, 6);
+    search.set_hexpand(true);
+    top.append(&search);
+    top.append(&cat_drop);
+    vbox.append(&top);
+
+    let recent_label = Label::builder().label("Recent").xalign(0.0).build();
+    recent_label.add_css_class("dim-label");
+    let recent_flow = gtk::FlowBox::builder().max_children_per_line(10).min_children_per_line(10).selection_mode(gtk::SelectionMode::None).build();
+    vbox.append(&recent_label);
+    vbox.append(&recent_flow);
+
+    let flow = gtk::FlowBox::builder().max_children_per_line(10).min_children_per_line(10).selection_mode(gtk::SelectionMode::None).valign(gtk::Align::Start).build();
+    vbox.append(&ScrolledWindow::builder().child(&flow).min_content_height(260).min_content_width(380).build());
+    let count_label = Label::builder().xalign(0.0).build();
+    count_label.add_css_class("dim-label");
+    vbox.append(&count_label);
+
+    let popover = gtk::Popover::builder().child(&vbox).build();
+
+    let insert: Rc<dyn Fn(&str)> = {
+        let entry = entry.clone(); let pop_weak = popover.downgrade();
+        Rc::new(move |glyph: &str| {
+            let (text, pos) = glyphs::insert_glyph(&entry.text(), entry.position().max(0) as usize, glyph);
+            entry.set_text(&text);
+            entry.set_position(pos as i32);
+            let _ = glyphs::push_recent(glyph);
+            if let Some(p) = pop_weak.upgrade() { p.popdown(); }
+        })
+    };
+    let glyph_button = {
+        let insert = Rc::clone(&insert);
+        move |glyph: &str| {
+            let btn = Button::with_label(glyph);
+            btn.add_css_class("flat");
+            let c = glyph.chars().next().unwrap_or(' ');
+            let name = glyphs::name_of(c).unwrap_or("");
+            btn.set_tooltip_text(Some(format!("{} (U+{:04X})", name, c as u32).trim_start()));
+            let insert = Rc::clone(&insert); let g = glyph.to_string();
+            btn.connect_clicked(move |_| insert(&g));
+            btn
+        }
+    };
+
+    let refresh: Rc<dyn Fn()> = {
+        let flow = flow.clone(); let search = search.clone(); let cat_drop = cat_drop.clone();
+        let count_label = count_label.clone(); let glyph_button = glyph_button.clone();
+        Rc::new(move || {
+            while let Some(child) = flow.first_child() { flow.remove(&child); }
+            let category = (cat_drop.selected() as usize).checked_sub(1).and_then(|i| categories.get(i).copied());
+            let found = glyphs::search(&search.text(), category);
+            for g in &found {
+                flow.insert(&glyph_button(&g.glyph.to_string()), -1);
+            }
+            count_label.set_label(&format!("{} glyphs", found.len()));
+        })
+    };
+    refresh();
+    let r = Rc::clone(&refresh);
+    search.connect_search_changed(move |_| r());
+    let r = Rc::clone(&refresh);
+    cat_drop.connect_selected_notify(move |_| r());
+
+    {
+        let recent_flow = recent_flow.clone(); let recent_label = recent_label.clone();
+        popover.connect_show(move |_| {
+            while let Some(child) = recent_flow.first_child() { recent_flow.remove(&child); }
+            let recent = glyphs::load_recent();
+            for g in &recent {
+                recent_flow.insert(&glyph_button(g), -1);
+            }
+            recent_label.set_visible(!recent.is_empty());
+            recent_flow.set_visible(!recent.is_empty());
+        });
+    }
+    gtk::MenuButton::builder().icon_name("face-smile-symbolic").tooltip_text("Insert Glyph").popover(&popover).valign(gtk::Align::Center).build()
+}
+
+/// Editor for a module option holding a list of strings, one entry per item so items keep
+/// their spaces (`"󰂎 "`). `format-icons` lists also offer the ready-made ramps.
+fn string_list_row(config_rc: &Rc<RefCell<WaybarConfig>>, mod_name: &str, key: &str, items: &[String], refresh: Rc<dyn Fn()>, rebuild: Rc<dyn Fn()>) -> adw::ExpanderRow {
+    fn edit(config_rc: &Rc<RefCell<WaybarConfig>>, mod_name: &str, key: &str, f: impl FnOnce(&mut Vec<serde_json::Value>)) {
+        let mut cfg = config_rc.borrow_mut();
+        let Some(o) = cfg.module_definitions.get_mut(mod_name).and_then(|d| d.as_object_mut()) else { return };
+        let mut list = o.get(key).and_then(|v| v.as_array()).cloned().unwrap_or_default();
+        f(&mut list);
+        o.insert(key.to_string(), serde_json::Value::Array(list));
+    }
+    let expander = adw::ExpanderRow::new();
+    expander.set_title(key);
+    expander.set_subtitle(&format!("{} item{}: {}", items.len(), if items.len() == 1 { "" } else { "s" }, glib::markup_escape_text(&items.join(" "))));
+
+    if key == "format-icons" {
+        let cfg_s = Rc::clone(config_rc); let mn_s = mod_name.to_string(); let key_s = key.to_string(); let rebuild_s = Rc::clone(&rebuild);
+        expander.add_suffix(&icon_set_picker(Rc::new(move |icons: Vec<String>| {
+            edit(&cfg_s, &mn_s, &key_s, |l| *l = icons.into_iter().map(serde_json::Value::String).collect());
+            rebuild_s();
+        })));
+    }
+    let del_btn = Button::builder().icon_name("user-trash-symbolic").has_frame(false).valign(gtk::Align::Center).build();
+    let cfg_d = Rc::clone(config_rc); let mn_d = mod_name.to_string(); let key_d = key.to_string(); let rebuild_d = Rc::clone(&rebuild);
+    del_btn.connect_clicked(move |_| {
+        if let Some(o) = cfg_d.borrow_mut().module_definitions.get_mut(&mn_d).and_then(|d| d.as_object_mut()) { o.remove(&key_d); }
+        refresh();
+        rebuild_d();
+    });
+    expander.add_suffix(&del_btn);
+
+    for (i, item) in items.iter().enumerate() {
+        let row = ActionRow::new();
+        let en = Entry::builder().text(item).valign(gtk::Align::Center).hexpand(true).build();
+        let cfg_e = Rc::clone(config_rc); let mn_e = mod_name.to_string(); let key_e = key.to_string();
+        en.connect_changed(move |e| {
+            let text = e.text().to_string();
+            edit(&cfg_e, &mn_e, &key_e, |l| if let Some(slot) = l.get_mut(i) { *slot = serde_json::Value::String(text); });
+        });
+        let remove_btn = Button::builder().icon_name("list-remove-symbolic").has_frame(false).valign(gtk::Align::Center).build();
+        let cfg_r = Rc::clone(config_rc); let mn_r = mod_name.to_string(); let key_r = key.to_string(); let rebuild_r = Rc::clone(&rebuild);
+        remove_btn.connect_clicked(move |_| {
+            edit(&cfg_r, &mn_r, &key_r, |l| if i < l.len() { l.remove(i); });
+            rebuild_r();
+        });
+        row.add_prefix(&en);
+        row.add_suffix(&glyph_picker(&en));
+        row.add_suffix(&remove_btn);
+        expander.add_row(&row);
+    }
+
+    let add_row = ActionRow::builder().title("Add Item").activatable(true).build();
+    add_row.add_prefix(&gtk::Image::from_icon_name("list-add-symbolic"));
+    let cfg_a = Rc::clone(config_rc); let mn_a = mod_name.to_string(); let key_a = key.to_string();
+    add_row.connect_activated(move |_| {
+        edit(&cfg_a, &mn_a, &key_a, |l| l.push(serde_json::Value::String(String::new())));
+        rebuild();
+    });
+    expander.add_row(&add_row);
+    expander
+}
+
+/// Menu of ready-made `format-icons` ramps; picking one hands its glyphs to `on_pick`.
+fn icon_set_picker(on_pick: Rc<dyn Fn(Vec<String>)>) -> gtk::MenuButton {
+    let list = ListBox::new();
+    list.set_selection_mode(gtk::SelectionMode::None);
+    let popover = gtk::Popover::builder().child(&list).build();
+    for (name, names) in glyphs::ICON_SETS {
+        let icons = glyphs::icon_set(names);
+        let row = ActionRow::builder().title(*name).subtitle(icons.join(" ").as_str()).activatable(true).build();
+        let on_pick = Rc::clone(&on_pick); let pop_weak = popover.downgrade();
+        row.connect_activated(move |_| {
+            on_pick(icons.clone());
+            if let Some(p) = pop_weak.upgrade() { p.popdown(); }
+        });
+        list.append(&row);
+    }
+    gtk::MenuButton::builder().icon_name("view-list-symbolic").tooltip_text("Icon Set").popover(&popover).valign(gtk::Align::Center).build()
+}
+
+/// Editor for `clock`: formats with a token picker and live preview, `timezones`, and the
+/// `calendar` object with a rendered preview of the tooltip.
+fn clock_editor(mod_name: &str, config_rc: &Rc<RefCell<WaybarConfig>>, rebuild: Rc<dyn Fn()>) -> PreferencesGroup {
+    let group = PreferencesGroup::new();
+    group.set_title("Clock");
+    let def = config_rc.borrow().module_definitions.get(mod_name).cloned();
+    let def_str = |k: &str| def.as_ref().and_then(|d| d.get(k)).and_then(|v| v.as_str()).unwrap_or("").to_string();
+
+    let tooltip_preview = Label::builder().xalign(0.0).selectable(true).wrap(false).build();
+    tooltip_preview.add_css_class("monospace");
+    let format_rows: Rc<RefCell<Vec<(&'static str, ActionRow)>>> = Rc::new(RefCell::new(Vec::new()));
+
+    // Re-renders every preview from the config as currently edited.
+    let update_preview: Rc<dyn Fn()> = {
+        let cfg = Rc::clone(config_rc); let mn = mod_name.to_string();
+        let tooltip_preview = tooltip_preview.clone(); let format_rows = Rc::clone(&format_rows);
+        Rc::new(move || {
+            let cfg = cfg.borrow();
+            let def = cfg.module_definitions.get(&mn);
+            let Some(now) = clock_now(def) else { return };
+            let strftime = |f: &str| now.format(f).map(|s| s.to_string()).unwrap_or_default();
+            let (y, m, d) = now.ymd();
+            let calendar = clock::render_calendar((y, m as u32, d as u32), &calendar_style(def));
+            let tz_list = def.and_then(|d| d.get("timezones")).and_then(|v| v.as_array())
+                .map(|a| a.iter().filter_map(|v| v.as_str())
+                    .filter_map(|t| glib::DateTime::now(&glib::TimeZone::new(Some(t))).ok())
+                    .map(|dt| dt.format("%H:%M %Z").map(|s| s.to_string()).unwrap_or_default())
+                    .collect::<Vec<_>>().join("\n"))
+                .unwrap_or_default();
+            for (key, row) in format_rows.borrow().iter() {
+                let fmt = def.and_then(|d| d.get(*key)).and_then(|v| v.as_str()).unwrap_or("");
+                let text = if fmt.is_empty() { "Not set".to_string() } else { clock::render_format(fmt, &strftime, "[calendar]", "[timezones]") };
+                row.set_subtitle(&glib::markup_escape_text(&text));
+            }
+            let tooltip = def.and_then(|d| d.get("tooltip-format")).and_then(|v| v.as_str()).unwrap_or("{calendar}");
+            set_markup_or_text(&tooltip_preview, &clock::render_format(tooltip, &strftime, &calendar, &tz_list));
+        })
+    };
+
+    for (key, title) in [("format", "Format"), ("format-alt", "Alternate Format (click)"), ("tooltip-format", "Tooltip Format")] {
+        let row = ActionRow::builder().title(title).build();
+        let en = Entry::builder().text(def_str(key)).valign(gtk::Align::Center).width_chars(16).build();
+        let cfg_f = Rc::clone(config_rc); let mn_f = mod_name.to_string(); let upd = Rc::clone(&update_preview);
+        en.connect_changed(move |e| {
+            set_module_string(&cfg_f, &mn_f, key, &e.text());
+            upd();
+        });
+        row.add_suffix(&en);
+        row.add_suffix(&token_picker(&en));
+        group.add(&row);
+        format_rows.borrow_mut().push((key, row));
+    }
+
+    // --- Timezones ---
+    let tz_expander = adw::ExpanderRow::new();
+    tz_expander.set_title("Timezones");
+    tz_expander.set_subtitle("The first one is shown; scroll on the clock to switch");
+    let timezones: Vec<String> = def.as_ref().and_then(|d| d.get("timezones")).and_then(|v| v.as_array())
+        .map(|a| a.iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect()).unwrap_or_default();
+    let save_timezones = {
+        let cfg = Rc::clone(config_rc); let mn = mod_name.to_string();
+        move |list: Vec<String>| {
+            let mut c = cfg.borrow_mut();
+            let def = c.module_definitions.entry(mn.clone()).or_insert_with(|| serde_json::json!({}));
+            if let Some(o) = def.as_object_mut() {
+                if list.is_empty() { o.remove("timezones"); } else { o.insert("timezones".to_string(), serde_json::json!(list)); }
+            }
+        }
+    };
+    for (i, tz) in timezones.iter().enumerate() {
+        let row = ActionRow::builder().title(tz).build();
+        if let Ok(now) = glib::DateTime::now(&glib::TimeZone::new(Some(tz))) {
+            row.set_subtitle(&now.format("%H:%M %Z (%z)").map(|s| s.to_string()).unwrap_or_default());
+        }
+        let del_btn = Button::builder().icon_name("user-trash-symbolic").has_frame(false).valign(gtk::Align::Center).build();
+        let mut rest = timezones.clone(); rest.remove(i);
+        let save_d = save_timezones.clone(); let rebuild_d = Rc::clone(&rebuild);
+        del_btn.connect_clicked(move |_| { save_d(rest.clone()); rebuild_d(); });
+        row.add_suffix(&del_btn);
+        tz_expander.add_row(&row);
+    }
+    let add_tz_row = ActionRow::builder().title("Add Timezone").build();
+    let tz_en = Entry::builder().placeholder_text("Europe/Berlin").valign(gtk::Align::Center).build();
+    let add_tz_btn = Button::builder().icon_name("list-add-symbolic").has_frame(false).valign(gtk::Align::Center).build();
+    let tz_en_c = tz_en.clone(); let rebuild_tz = Rc::clone(&rebuild);
+    add_tz_btn.connect_clicked(move |_| {
+        let name = tz_en_c.text().trim().to_string();
+        if !clock::is_valid_timezone(&name) {
+            tz_en_c.add_css_class("error");
+            return;
+        }
+        let mut list = timezones.clone();
+        if !list.contains(&name) { list.push(name); }
+        save_timezones(list);
+        rebuild_tz();
+    });
+    tz_en.connect_changed(|e| e.remove_css_class("error"));
+    add_tz_row.add_suffix(&tz_en);
+    add_tz_row.add_suffix(&add_tz_btn);
+    tz_expander.add_row(&add_tz_row);
+    group.add(&tz_expander);
+
+    // --- Calendar ---
+    let cal_expander = adw::ExpanderRow::new();
+    cal_expander.set_title("Calendar");
+    cal_expander.set_subtitle("Shown where the tooltip format uses {calendar}");
+    let cal = def.as_ref().and_then(|d| d.get("calendar")).cloned().unwrap_or_else(|| serde_json::json!({}));
+
+    // (key, title, options, value written for the selected option)
+    type CalendarChoice = (&'static str, &'static str, &'static [&'static str], fn(usize) -> Option<serde_json::Value>);
+    let choices: [CalendarChoice; 3] = [
+        ("mode", "Mode", &["month", "year"], |i| if i == 1 { Some(serde_json::json!("year")) } else { None }),
+        ("weeks-pos", "Week Numbers", &["hidden", "left", "right"], |i| match i { 1 => Some(serde_json::json!("left")), 2 => Some(serde_json::json!("right")), _ => None }),
+        ("on-scroll", "Scroll Step", &["off", "1 month forward", "1 month back"], |i| match i { 1 => Some(serde_json::json!(1)), 2 => Some(serde_json::json!(-1)), _ => None }),
+    ];
+    for (key, title, options, to_value) in choices {
+        let row = ComboRow::new();
+        row.set_title(title);
+        row.set_model(Some(&StringList::new(options)));
+        let current = cal.get(key).cloned();
+        row.set_selected((0..options.len()).find(|&i| to_value(i) == current && current.is_some()).unwrap_or(0) as u32);
+        let cfg_c = Rc::clone(config_rc); let mn_c = mod_name.to_string(); let upd = Rc::clone(&update_preview);
+        row.connect_selected_notify(move |r| {
+            set_calendar_option(&cfg_c, &mn_c, key, false, to_value(r.selected() as usize));
+            upd();
+        });
+        cal_expander.add_row(&row);
+    }
+
+    let cols_row = ActionRow::builder().title("Months per Row").subtitle("mode-mon-col, year mode").build();
+    let cols_spin = gtk::SpinButton::with_range(1.0, 12.0, 1.0);
+    cols_spin.set_valign(gtk::Align::Center);
+    cols_spin.set_value(cal.get("mode-mon-col").and_then(|v| v.as_f64()).unwrap_or(3.0));
+    let cfg_cols = Rc::clone(config_rc); let mn_cols = mod_name.to_string(); let upd_cols = Rc::clone(&update_preview);
+    cols_spin.connect_value_changed(move |s| {
+        set_calendar_option(&cfg_cols, &mn_cols, "mode-mon-col", false, Some(serde_json::json!(s.value() as i64)));
+        upd_cols();
+    });
+    cols_row.add_suffix(&cols_spin);
+    cal_expander.add_row(&cols_row);
+
+    // Scrolling and right click only act on the calendar when bound in `actions`.
+    let actions = def.as_ref().and_then(|d| d.get("actions")).cloned().unwrap_or_else(|| serde_json::json!({}));
+    type ActionBinding = (&'static str, &'static str, &'static [(&'static str, &'static str)]);
+    let action_rows: [ActionBinding; 2] = [
+        ("Scroll Changes Month", "actions: on-scroll-up/down → shift_up/shift_down", &[("on-scroll-up", "shift_up"), ("on-scroll-down", "shift_down")]),
+        ("Right Click Toggles Mode", "actions: on-click-right → mode", &[("on-click-right", "mode")]),
+    ];
+    for (title, subtitle, bindings) in action_rows {
+        let row = ActionRow::builder().title(title).subtitle(subtitle).build();
+        let active = bindings.iter().all(|(ev, act)| actions.get(*ev).and_then(|v| v.as_str()) == Some(*act));
+        let sw = Switch::builder().active(active).valign(gtk::Align::Center).build();
+        let cfg_a = Rc::clone(config_rc); let mn_a = mod_name.to_string();
+        sw.connect_state_set(move |_, state| {
+            edit_module_map(&cfg_a, &mn_a, "actions", |m| {
+                for (ev, act) in bindings {
+                    if state { m.insert(ev.to_string(), serde_json::json!(act)); } else { m.shift_remove(*ev); }
+                }
+            });
+            glib::Propagation::Proceed
+        });
+        row.add_suffix(&sw);
+        cal_expander.add_row(&row);
+    }
+
+    for (key, title, example) in [
+        ("months", "Month Title", "<span color='#ffead3'><b>{}</b></span>"),
+        ("weekdays", "Weekday Header", "<span color='#ffcc66'><b>{}</b></span>"),
+        ("days", "Days", "<span color='#ecc6d9'><b>{}</b></span>"),
+        ("weeks", "Week Numbers", "<span color='#99ffdd'><b>W{}</b></span>"),
+        ("today", "Today", "<span color='#ff6699'><b><u>{}</u></b></span>"),
+    ] {
+        let row = ActionRow::builder().title(title).subtitle(format!("format.{}", key)).build();
+        let value = cal.get("format").and_then(|f| f.get(key)).and_then(|v| v.as_str()).unwrap_or("");
+        let en = Entry::builder().text(value).placeholder_text(example).valign(gtk::Align::Center).width_chars(16).build();
+        let cfg_f = Rc::clone(config_rc); let mn_f = mod_name.to_string(); let upd = Rc::clone(&update_preview);
+        en.connect_changed(move |e| {
+            let text = e.text().to_string();
+            set_calendar_option(&cfg_f, &mn_f, key, true, if text.is_empty() { None } else { Some(serde_json::json!(text)) });
+            upd();
+        });
+        row.add_suffix(&en);
+        cal_expander.add_row(&row);
+    }
+    group.add(&cal_expander);
+
+    let preview_title = Label::new(Some("Tooltip Preview"));
+    preview_title.add_css_class("heading");
+    preview_title.set_xalign(0.0);
+    preview_title.set_margin_top(12);
+    group.add(&preview_title);
+    group.add(&tooltip_preview);
+    update_preview();
+    group
+}
+
+fn rgba_to_hex(rgba: &gdk::RGBA) -> String {
+    format!("#{:02x}{:02x}{:02x}", (rgba.red() * 255.0) as u8, (rgba.green() * 255.0) as u8, (rgba.blue() * 255.0) as u8)
+}
+
+/// A color button for `prop` in the rule `#module{suffix}`, showing the current value with
+/// palette variables resolved.
+fn css_color_button(css_path: &Path, mod_name: &str, suffix: &str, prop: &'static str, style_rc: &Rc<RefCell<StyleConfig>>, rebuild: &Rc<dyn Fn()>) -> ColorButton {
+    let btn = ColorButton::builder().valign(gtk::Align::Center).build();
+    if let Some(val) = get_module_css_prop(css_path, mod_name, suffix, prop) {
+        let resolved = palette::resolve_vars(&style_rc.borrow().vars);
+        let val = val.strip_prefix('@').and_then(|v| resolved.get(v).cloned()).unwrap_or(val);
+        if let Ok(c) = gdk::RGBA::parse(&val) { btn.set_rgba(&c); }
+    }
+    let path = css_path.to_path_buf(); let mn = mod_name.to_string(); let suffix = suffix.to_string(); let rebuild = Rc::clone(rebuild);
+    btn.connect_color_set(move |b| {
+        update_module_css(&path, &mn, &suffix, prop, &rgba_to_hex(&b.rgba()));
+        rebuild();
+    });
+    btn
+}
+
+/// Text color, background and animation rows for the rule `#module{suffix}`.
+fn add_style_rows(expander: &adw::ExpanderRow, css_path: &Path, mod_name: &str, suffix: &str, style_rc: &Rc<RefCell<StyleConfig>>, rebuild: &Rc<dyn Fn()>) {
+    for (title, prop) in [("Text Color", "color"), ("Background Color", "background-color")] {
+        let row = ActionRow::new();
+        row.set_title(title);
+        row.add_suffix(&css_color_button(css_path, mod_name, suffix, prop, style_rc, rebuild));
+        let clear_btn = Button::builder().icon_name("edit-clear-symbolic").has_frame(false).valign(gtk::Align::Center).tooltip_text("Remove").build();
+        let path_c = css_path.to_path_buf(); let mn_c = mod_name.to_string(); let suf_c = suffix.to_string(); let rebuild_c = Rc::clone(rebuild);
+        clear_btn.connect_clicked(move |_| { update_module_css(&path_c, &mn_c, &suf_c, prop, ""); rebuild_c(); });
+        row.add_suffix(&clear_btn);
+        expander.add_row(&row);
+    }
+
+    let names = keyframe_names(css_path);
+    let anim_row = ComboRow::new();
+    anim_row.set_title("Animation");
+    let mut labels = vec!["none"];
+    labels.extend(names.iter().map(|n| n.as_str()));
+    anim_row.set_model(Some(&StringList::new(&labels)));
+    let current = get_module_css_prop(css_path, mod_name, suffix, "animation")
+        .and_then(|a| animation::AnimationSpec::parse(&a))
+        .and_then(|spec| names.iter().position(|n| *n == spec.name).map(|i| i + 1))
+        .unwrap_or(0);
+    anim_row.set_selected(current as u32);
+    let path_a = css_path.to_path_buf(); let mn_a = mod_name.to_string(); let suf_a = suffix.to_string(); let rebuild_a = Rc::clone(rebuild);
+    anim_row.connect_selected_notify(move |row| {
+        let value = (row.selected() as usize).checked_sub(1).and_then(|i| names.get(i))
+            .map(|name| animation::AnimationSpec::default_for(name).to_css())
+            .unwrap_or_default();
+        update_module_css(&path_a, &mn_a, &suf_a, "animation", &value);
+        rebuild_a();
+    });
+    expander.add_row(&anim_row);
+    if let Some(timing) = animation_timing_row(css_path, mod_name, suffix) { expander.add_row(&timing); }
+}
+
+/// Editor for the `states` of modules that have them: each named state gets a threshold and
+/// its own `#module.state` CSS block with colors and an animation.
+fn states_editor(mod_name: &str, config_rc: &Rc<RefCell<WaybarConfig>>, css_path: &Path, style_rc: &Rc<RefCell<StyleConfig>>, rebuild: Rc<dyn Fn()>) -> PreferencesGroup {
+    let value_name = states::state_value(mod_name).unwrap_or("value");
+    let lower = states::lower_is_worse(mod_name);
+    let selector = css::module_selector(mod_name);
+    let group = PreferencesGroup::new();
+    group.set_title("Module States and Thresholds");
+    group.set_description(Some(&format!(
+        "Adds the state as a class ({}.name) when the {} is {} the threshold",
+        selector, value_name, if lower { "at or below" } else { "at or above" })));
+    let states = config_rc.borrow().module_definitions.get(mod_name)
+        .and_then(|d| d.get("states")).and_then(|v| v.as_object()).cloned().unwrap_or_default();
+    let cmp = if lower { "≤" } else { "≥" };
+
+    for (name, threshold) in &states {
+        let suffix = format!(".{}", name);
+        let expander = adw::ExpanderRow::new();
+        expander.set_title(name);
+        expander.set_subtitle(&format!("{} {} {}", value_name, cmp, threshold));
+
+        let spin = gtk::SpinButton::with_range(0.0, 1000.0, 1.0);
+        spin.set_valign(gtk::Align::Center);
+        spin.set_value(threshold.as_f64().unwrap_or(0.0));
+        let cfg_t = Rc::clone(config_rc); let mn_t = mod_name.to_string(); let name_t = name.clone(); let exp_t = expander.clone();
+        spin.connect_value_changed(move |s| {
+            let value = s.value() as i64;
+            edit_module_map(&cfg_t, &mn_t, "states", |m| { m.insert(name_t.clone(), serde_json::json!(value)); });
+            exp_t.set_subtitle(&format!("{} {} {}", value_name, cmp, value));
+        });
+        expander.add_suffix(&spin);
+
+        let del_btn = Button::builder().icon_name("user-trash-symbolic").has_frame(false).valign(gtk::Align::Center).build();
+        let cfg_d = Rc::clone(config_rc); let mn_d = mod_name.to_string(); let name_d = name.clone();
+        let path_d = css_path.to_path_buf(); let sel_d = format!("{}{}", selector, suffix); let rebuild_d = Rc::clone(&rebuild);
+        del_btn.connect_clicked(move |_| {
+            edit_module_map(&cfg_d, &mn_d, "states", |m| { m.shift_remove(&name_d); });
+            if let Ok(css) = fs::read_to_string(&path_d) { let _ = fs::write(&path_d, css::remove_rule(&css, &sel_d)); }
+            rebuild_d();
+        });
+        expander.add_suffix(&del_btn);
+
+        add_style_rows(&expander, css_path, mod_name, &suffix, style_rc, &rebuild);
+        group.add(&expander);
+    }
+
+    // New state: name + threshold; its CSS block starts with a palette color.
+    let add_row = ActionRow::new();
+    let name_en = Entry::builder().placeholder_text("State name (e.g. warning)").valign(gtk::Align::Center).hexpand(true).build();
+    let add_spin = gtk::SpinButton::with_range(0.0, 1000.0, 1.0);
+    add_spin.set_valign(gtk::Align::Center);
+    add_spin.set_value(if lower { 30.0 } else { 80.0 });
+    let add_btn = Button::builder().icon_name("list-add-symbolic").has_frame(false).valign(gtk::Align::Center).build();
+    let cfg_a = Rc::clone(config_rc); let mn_a = mod_name.to_string(); let path_a = css_path.to_path_buf();
+    let name_a = name_en.clone(); let spin_a = add_spin.clone(); let rebuild_a = Rc::clone(&rebuild); let style_a = Rc::clone(style_rc);
+    add_btn.connect_clicked(move |_| {
+        let name = name_a.text().trim().to_string();
+        if !css::is_identifier(&name) { name_a.add_css_class("error"); return; }
+        let value = spin_a.value() as i64;
+        edit_module_map(&cfg_a, &mn_a, "states", |m| { m.insert(name.clone(), serde_json::json!(value)); });
+        let suffix = format!(".{}", name);
+        if get_module_css_prop(&path_a, &mn_a, &suffix, "color").is_none() {
+            update_module_css(&path_a, &mn_a, &suffix, "color", states::default_color(&name, &style_a.borrow().vars));
+        }
+        rebuild_a();
+    });
+    name_en.connect_changed(|e| e.remove_css_class("error"));
+    add_row.add_prefix(&name_en);
+    add_row.add_suffix(&add_spin);
+    add_row.add_suffix(&add_btn);
+    group.add(&add_row);
+
+    if states.is_empty() {
+        let suggested = states::suggested_states(mod_name);
+        let suggest_row = ActionRow::new();
+        suggest_row.set_title("Add Suggested States");
+        suggest_row.set_subtitle(&suggested.iter().map(|(n, t)| format!("{} {} {}", n, cmp, t)).collect::<Vec<_>>().join(", "));
+        let suggest_btn = Button::builder().label("Add").valign(gtk::Align::Center).build();
+        let cfg_s = Rc::clone(config_rc); let mn_s = mod_name.to_string(); let path_s = css_path.to_path_buf(); let rebuild_s = Rc::clone(&rebuild);
+        let style_s = Rc::clone(style_rc);
+        suggest_btn.connect_clicked(move |_| {
+            edit_module_map(&cfg_s, &mn_s, "states", |m| {
+                for (n, t) in suggested { m.insert(n.to_string(), serde_json::json!(t)); }
+            });
+            for (n, _) in suggested {
+                let suffix = format!(".{}", n);
+                if get_module_css_prop(&path_s, &mn_s, &suffix, "color").is_none() {
+                    update_module_css(&path_s, &mn_s, &suffix, "color", states::default_color(n, &style_s.borrow().vars));
+                }
+            }
+            rebuild_s();
+        });
+        suggest_row.add_suffix(&suggest_btn);
+        group.add(&suggest_row);
+    } else {
+        // Which class a given value would get.
+        let test_row = ActionRow::new();
+        test_row.set_title(&format!("Test a {}", value_name));
+        let test_scale = Scale::with_range(gtk::Orientation::Horizontal, 0.0, 100.0, 1.0);
+        test_scale.set_width_request(150);
+        test_scale.set_draw_value(true);
+        let cfg_t = Rc::clone(config_rc); let mn_t = mod_name.to_string();
+        let describe = move |row: &ActionRow, value: i64| {
+            let states = cfg_t.borrow().module_definitions.get(&mn_t)
+                .and_then(|d| d.get("states")).and_then(|v| v.as_object()).cloned().unwrap_or_default();
+            row.set_subtitle(&match states::active_state(&states, value, lower) {
+                Some(s) => format!("{} gets .{}", value, s),
+                None => format!("{}: no state", value),
+            });
+        };
+        describe(&test_row, 50);
+        test_scale.set_value(50.0);
+        let row_t = test_row.clone();
+        test_scale.connect_value_changed(move |s| describe(&row_t, s.value() as i64));
+        test_row.add_suffix(&test_scale);
+        group.add(&test_row);
+    }
+    group
+}
+
+/// The classes Waybar toggles on a module by itself (`.muted`, `.charging`, workspace
+/// `button.active`...), each with its own colors and animation.
+fn conditions_editor(mod_name: &str, css_path: &Path, style_rc: &Rc<RefCell<StyleConfig>>, rebuild: Rc<dyn Fn()>) -> Option<PreferencesGroup> {
+    let (child, classes) = states::status_classes(mod_name);
+    if classes.is_empty() { return None; }
+    let group = PreferencesGroup::new();
+    group.set_title("Conditions");
+    group.set_description(Some("Style the classes Waybar adds to this module on its own"));
+    let css_text = fs::read_to_string(css_path).unwrap_or_default();
+
+    for (class, about) in classes {
+        let suffix = format!("{}.{}", child, class);
+        let selector = format!("{}{}", css::module_selector(mod_name), suffix);
+        let styled = css_text.lines().any(|l| css::opens_rule(l, &selector));
+        let expander = adw::ExpanderRow::new();
+        expander.set_title(&selector);
+        expander.set_subtitle(&if styled { format!("{} · styled", about) } else { about.to_string() });
+        if styled {
+            let clear_btn = Button::builder().icon_name("user-trash-symbolic").has_frame(false).valign(gtk::Align::Center).tooltip_text("Remove this rule").build();
+            let path_c = css_path.to_path_buf(); let rebuild_c = Rc::clone(&rebuild);
+            clear_btn.connect_clicked(move |_| {
+                if let Ok(css) = fs::read_to_string(&path_c) { let _ = fs::write(&path_c, css::remove_rule(&css, &selector)); }
+                rebuild_c();
+            });
+            expander.add_suffix(&clear_btn);
+        }
+        add_style_rows(&expander, css_path, mod_name, &suffix, style_rc, &rebuild);
+        group.add(&expander);
+    }
+    Some(group)
+}
+
+/// Names of the `@keyframes` in the session stylesheet, built-ins included.
+fn keyframe_names(css_path: &Path) -> Vec<String> {
+    let css = animation::ensure_builtin(&fs::read_to_string(css_path).unwrap_or_default());
+    let mut names: Vec<String> = animation::parse_keyframes(&css).into_iter().map(|k| k.name).collect();
+    names.dedup();
+    names
+}
+
+/// Duration, easing, iteration count and direction of the animation assigned to
+/// `#module{suffix}`, or None when it has none.
+fn animation_timing_row(css_path: &Path, mod_name: &str, suffix: &str) -> Option<adw::ExpanderRow> {
+    let spec = animation::AnimationSpec::parse(&get_module_css_prop(css_path, mod_name, suffix, "animation")?)?;
+    let expander = adw::ExpanderRow::new();
+    expander.set_title("Timing");
+    expander.set_subtitle(&spec.to_css());
+
+    let path = css_path.to_path_buf(); let mn = mod_name.to_string(); let suffix = suffix.to_string(); let exp = expander.clone();
+    let edit = Rc::new(move |f: &dyn Fn(&mut animation::AnimationSpec)| {
+        let Some(mut spec) = get_module_css_prop(&path, &mn, &suffix, "animation").and_then(|a| animation::AnimationSpec::parse(&a)) else { return };
+        f(&mut spec);
+        update_module_css(&path, &mn, &suffix, "animation", &spec.to_css());
+        exp.set_subtitle(&spec.to_css());
+    });
+
+    let duration_row = ActionRow::new();
+    duration_row.set_title("Duration (seconds)");
+    let duration = gtk::SpinButton::with_range(0.05, 60.0, 0.05);
+    duration.set_digits(2);
+    duration.set_valign(gtk::Align::Center);
+    duration.set_value(spec.duration);
+    let edit_d = Rc::clone(&edit);
+    duration.connect_value_changed(move |s| { let v = s.value(); edit_d(&|spec| spec.duration = v); });
+    duration_row.add_suffix(&duration);
+    expander.add_row(&duration_row);
+
+    let mut easings: Vec<String> = animation::EASINGS.iter().map(|e| e.to_string()).collect();
+    if !easings.contains(&spec.easing) { easings.push(spec.easing.clone()); }
+    let easing_row = ComboRow::new();
+    easing_row.set_title("Easing");
+    let easing_labels: Vec<&str> = easings.iter().map(|e| e.as_str()).collect();
+    easing_row.set_model(Some(&StringList::new(&easing_labels)));
+    easing_row.set_selected(easings.iter().position(|e| *e == spec.easing).unwrap_or(0) as u32);
+    let edit_e = Rc::clone(&edit);
+    easing_row.connect_selected_notify(move |row| {
+        if let Some(e) = easings.get(row.selected() as usize).cloned() { edit_e(&|spec| spec.easing = e.clone()); }
+    });
+    expander.add_row(&easing_row);
+
+    let iter_row = ActionRow::new();
+    iter_row.set_title("Iterations");
+    iter_row.set_subtitle("0 repeats forever");
+    let iterations = gtk::SpinButton::with_range(0.0, 1000.0, 1.0);
+    iterations.set_valign(gtk::Align::Center);
+    iterations.set_value(spec.iterations.unwrap_or(0.0));
+    let edit_i = Rc::clone(&edit);
+    iterations.connect_value_changed(move |s| {
+        let v = s.value();
+        edit_i(&|spec| spec.iterations = if v > 0.0 { Some(v) } else { None });
+    });
+    iter_row.add_suffix(&iterations);
+    expander.add_row(&iter_row);
+
+    let direction_row = ComboRow::new();
+    direction_row.set_title("Direction");
+    direction_row.set_model(Some(&StringList::new(animation::DIRECTIONS)));
+    direction_row.set_selected(animation::DIRECTIONS.iter().position(|d| *d == spec.direction).unwrap_or(0) as u32);
+    direction_row.connect_selected_notify(move |row| {
+        if let Some(d) = animation::DIRECTIONS.get(row.selected() as usize) { edit(&|spec| spec.direction = d.to_string()); }
+    });
+    expander.add_row(&direction_row);
+    Some(expander)
+}
+
+/// Lists the `@keyframes` of the session stylesheet for editing: rename, stops with their
+/// declarations, and new animations. Built-ins can be edited but not removed.
+/// Runs `commit` when the user is done with `entry`: on Enter, or when focus leaves it.
+fn on_entry_commit(entry: &Entry, commit: impl Fn(&Entry) + 'static) {
+    let commit = Rc::new(commit);
+    let commit_a = Rc::clone(&commit);
+    entry.connect_activate(move |e| commit_a(e));
+    let focus = gtk::EventControllerFocus::new();
+    focus.connect_leave(move |ctl| {
+        if let Some(e) = ctl.widget().and_downcast::<Entry>() { commit(&e); }
+    });
+    entry.add_controller(focus);
+}
+
+fn keyframes_editor(css_path: &Path, rebuild: Rc<dyn Fn()>) -> PreferencesGroup {
+    let group = PreferencesGroup::new();
+    group.set_title("Keyframe Animations");
+    group.set_description(Some("The @keyframes offered by the hover, constant and state animation pickers"));
+    let css = animation::ensure_builtin(&fs::read_to_string(css_path).unwrap_or_default());
+
+    for kf in animation::parse_keyframes(&css) {
+        let builtin = animation::is_builtin(&kf.name);
+        let expander = adw::ExpanderRow::new();
+        expander.set_title(&kf.name);
+        expander.set_subtitle(&format!("{} stops{}", kf.stops.len(), if builtin { " · built-in" } else { "" }));
+        let current = Rc::new(RefCell::new(kf));
+
+        // Writes the edited keyframes back in place; `old_name` is the name in the file.
+        let path_s = css_path.to_path_buf();
+        let save = Rc::new(move |old_name: &str, kf: &animation::Keyframes| {
+            let css = animation::ensure_builtin(&fs::read_to_string(&path_s).unwrap_or_default());
+            let _ = fs::write(&path_s, animation::set_keyframes(&css, old_name, kf));
+        });
+
+        if !builtin {
+            let name_row = ActionRow::new();
+            name_row.set_title("Name");
+            let name_en = Entry::builder().text(&current.borrow().name).valign(gtk::Align::Center).build();
+            let cur_n = Rc::clone(&current); let save_n = Rc::clone(&save); let path_n = css_path.to_path_buf(); let rebuild_n = Rc::clone(&rebuild);
+            name_en.connect_activate(move |e| {
+                let new_name = e.text().trim().to_string();
+                let old_name = cur_n.borrow().name.clone();
+                if new_name == old_name { return; }
+                if !css::is_identifier(&new_name) || keyframe_names(&path_n).contains(&new_name) { e.add_css_class("error"); return; }
+                cur_n.borrow_mut().name = new_name.clone();
+                save_n(&old_name, &cur_n.borrow());
+                if let Ok(css) = fs::read_to_string(&path_n) { let _ = fs::write(&path_n, animation::rename_in_animations(&css, &old_name, &new_name)); }
+                rebuild_n();
+            });
+            name_en.connect_changed(|e| e.remove_css_class("error"));
+            name_row.add_suffix(&name_en);
+            expander.add_row(&name_row);
+
+            let del_btn = Button::builder().icon_name("user-trash-symbolic").has_frame(false).valign(gtk::Align::Center).tooltip_text("Delete animation").build();
+            let cur_d = Rc::clone(&current); let path_d = css_path.to_path_buf(); let rebuild_d = Rc::clone(&rebuild);
+            del_btn.connect_clicked(move |_| {
+                if let Ok(css) = fs::read_to_string(&path_d) { let _ = fs::write(&path_d, animation::remove_keyframes(&css, &cur_d.borrow().name)); }
+                rebuild_d();
+            });
+            expander.add_suffix(&del_btn);
+        }
+
+        let stop_count = current.borrow().stops.len();
+        for i in 0..stop_count {
+            let stop = current.borrow().stops[i].clone();
+            let row = ActionRow::new();
+            let offset_en = Entry::builder().text(&stop.offset).valign(gtk::Align::Center).width_chars(6).max_width_chars(8).build();
+            let decls_en = Entry::builder().text(animation::decls_text(&stop.decls)).placeholder_text("color: red; margin-top: -2px;").valign(gtk::Align::Center).hexpand(true).build();
+
+            // Written on Enter or focus-out only, so half-typed stops never reach Waybar.
+            let cur_o = Rc::clone(&current); let save_o = Rc::clone(&save);
+            on_entry_commit(&offset_en, move |e| {
+                let offset = e.text().trim().to_string();
+                if !animation::is_offset(&offset) { e.add_css_class("error"); return; }
+                if cur_o.borrow().stops[i].offset == offset { return; }
+                let name = cur_o.borrow().name.clone();
+                cur_o.borrow_mut().stops[i].offset = offset;
+                save_o(&name, &cur_o.borrow());
+            });
+            offset_en.connect_changed(|e| e.remove_css_class("error"));
+            let cur_v = Rc::clone(&current); let save_v = Rc::clone(&save);
+            on_entry_commit(&decls_en, move |e| {
+                let decls = animation::parse_decls(&e.text());
+                if cur_v.borrow().stops[i].decls == decls { return; }
+                let name = cur_v.borrow().name.clone();
+                cur_v.borrow_mut().stops[i].decls = decls;
+                save_v(&name, &cur_v.borrow());
+            });
+            let del_stop = Button::builder().icon_name("list-remove-symbolic").has_frame(false).valign(gtk::Align::Center).tooltip_text("Remove stop").build();
+            let cur_r = Rc::clone(&current); let save_r = Rc::clone(&save); let rebuild_r = Rc::clone(&rebuild);
+            del_stop.connect_clicked(move |_| {
+                let name = cur_r.borrow().name.clone();
+                cur_r.borrow_mut().stops.remove(i);
+                save_r(&name, &cur_r.borrow());
+                rebuild_r();
+            });
+            row.add_prefix(&offset_en);
+            row.add_suffix(&decls_en);
+            row.add_suffix(&del_stop);
+            expander.add_row(&row);
+        }
+
+        let add_stop = Button::builder().label("Add Stop").halign(gtk::Align::Start).margin_top(6).margin_bottom(6).margin_start(12).build();
+        let cur_a = Rc::clone(&current); let rebuild_a = Rc::clone(&rebuild);
+        add_stop.connect_clicked(move |_| {
+            let name = cur_a.borrow().name.clone();
+            cur_a.borrow_mut().stops.push(animation::Stop { offset: "100%".into(), decls: Vec::new() });
+            save(&name, &cur_a.borrow());
+            rebuild_a();
+        });
+        expander.add_row(&add_stop);
+        group.add(&expander);
+    }
+
+    let new_row = ActionRow::new();
+    new_row.set_title("New Animation");
+    let new_name = Entry::builder().placeholder_text("name").valign(gtk::Align::Center).build();
+    let new_btn = Button::builder().icon_name("list-add-symbolic").has_frame(false).valign(gtk::Align::Center).build();
+    let path_new = css_path.to_path_buf(); let name_new = new_name.clone();
+    new_btn.connect_clicked(move |_| {
+        let name = name_new.text().trim().to_string();
+        if !css::is_identifier(&name) || keyframe_names(&path_new).contains(&name) { name_new.add_css_class("error"); return; }
+        let stop = |offset: &str, opacity: &str| animation::Stop { offset: offset.into(), decls: vec![("opacity".into(), opacity.into())] };
+        let kf = animation::Keyframes { name, stops: vec![stop("0%", "1"), stop("50%", "0.5"), stop("100%", "1")] };
+        let css = animation::ensure_builtin(&fs::read_to_string(&path_new).unwrap_or_default());
+        let _ = fs::write(&path_new, animation::set_keyframes(&css, &kf.name, &kf));
+        rebuild();
+    });
+    new_name.connect_changed(|e| e.remove_css_class("error"));
+    new_row.add_suffix(&new_name);
+    new_row.add_suffix(&new_btn);
+    group.add(&new_row);
+    group
+}
+
+/// Applies `f(css, selector)` to the session stylesheet, for edits that touch more than one
+/// property of the module's rule.
+fn edit_module_rule(path: &Path, mod_name: &str, f: impl FnOnce(&str, &str) -> String) {
+    let full_css = fs::read_to_string(path).unwrap_or_else(|_| DEFAULT_LAYOUT_CSS.to_string());
+    let _ = fs::write(path, f(&full_css, &css::module_selector(mod_name)));
+}
+
+fn format_px(v: f64) -> String {
+    format!("{}px", (v * 100.0).round() / 100.0)
+}
+
+/// `v` in `unit`, px when the value had none.
+fn format_length(v: f64, unit: &str) -> String {
+    format!("{}{}", (v * 100.0).round() / 100.0, if unit.is_empty() { "px" } else { unit })
+}
+
+/// How a length in `unit` maps onto the px ranges of the sliders, or None for units they
+/// cannot represent (`%`, `vw`...), which are shown read-only.
+fn unit_scale(unit: &str) -> Option<f64> {
+    match unit {
+        "" | "px" => Some(1.0),
+        "em" | "rem" => Some(1.0 / 16.0),
+        "pt" => Some(0.75),
+        _ => None,
+    }
+}
+
+/// The current value of a length control: number, unit and range factor. None when the
+/// value is set but cannot be edited as a single length (`calc()`, `50%`).
+fn length_control(value: Option<&str>, fallback: f64) -> Option<(f64, String, f64)> {
+    match value {
+        None => Some((fallback, "px".to_string(), 1.0)),
+        Some(v) => {
+            let (n, unit) = css::split_length(v)?;
+            let unit = if unit.is_empty() { "px".to_string() } else { unit };
+            Some((n, unit.clone(), unit_scale(&unit)?))
+        }
+    }
+}
+
+/// A slider for one length property of `#module`, in the unit the value is written in.
+#[allow(clippy::too_many_arguments)]
+fn css_length_row(title: &str, css_path: &Path, mod_name: &str, prop: &'static str, min: f64, max: f64, step: f64, fallback: f64) -> ActionRow {
+    let row = ActionRow::new();
+    row.set_title(title);
+    let current = get_module_css_prop(css_path, mod_name, "", prop);
+    let Some((value, unit, f)) = length_control(current.as_deref(), fallback) else {
+        row.set_subtitle(&glib::markup_escape_text(&format!("{} (edit in the Code tab)", current.unwrap_or_default())));
+        return row;
+    };
+    if unit != "px" { row.set_subtitle(&format!("in {}", unit)); }
+    let scale = gtk::Scale::with_range(Orientation::Horizontal, min * f, max * f, step * f);
+    scale.set_digits(if f < 1.0 { 2 } else { 1 });
+    scale.set_value(value);
+    scale.set_width_request(150);
+    scale.set_valign(gtk::Align::Center);
+    scale.set_draw_value(true);
+    let lp = css_path.to_path_buf(); let mn = mod_name.to_string();
+    scale.connect_value_changed(move |s| update_module_css(&lp, &mn, "", prop, &format_length(s.value(), &unit)));
+    row.add_suffix(&scale);
+    row
+}
+
+/// Per-side editor for `margin` or `padding`, or per-corner for `border-radius`. Values come
+/// from longhands or the shorthand, and an existing shorthand is edited in place rather
+/// than split up. Each side keeps the unit it is written in.
+fn box_sides_row(title: &str, css_path: &Path, mod_name: &str, prop: &'static str, min: f64) -> adw::ExpanderRow {
+    let expander = adw::ExpanderRow::new();
+    expander.set_title(title);
+    let selector = css::module_selector(mod_name);
+    let labels: [&str; 4] = if prop == "border-radius" { ["Top Left", "Top Right", "Bottom Right", "Bottom Left"] } else { ["Top", "Right", "Bottom", "Left"] };
+    let summary = move |path: &Path| {
+        let css = fs::read_to_string(path).unwrap_or_default();
+        (0..4).map(|i| format!("{} {}", labels[i].to_lowercase(), css::get_side(&css, &selector, prop, i).unwrap_or_else(|| "–".into())))
+            .collect::<Vec<_>>().join(" · ")
+    };
+    expander.set_subtitle(&summary(css_path));
+    let summary = Rc::new(summary);
+
+    let css = fs::read_to_string(css_path).unwrap_or_default();
+    for (i, side) in labels.into_iter().enumerate() {
+        let row = ActionRow::new();
+        row.set_title(side);
+        let current = css::get_side(&css, &css::module_selector(mod_name), prop, i);
+        let Some((value, unit, f)) = length_control(current.as_deref(), 0.0) else {
+            row.set_subtitle(&glib::markup_escape_text(&format!("{} (edit in the Code tab)", current.unwrap_or_default())));
+            expander.add_row(&row);
+            continue;
+        };
+        if unit != "px" { row.set_subtitle(&format!("in {}", unit)); }
+        let spin = gtk::SpinButton::with_range(min * f, 50.0 * f, f);
+        spin.set_digits(if f < 1.0 { 2 } else { 0 });
+        spin.set_valign(gtk::Align::Center);
+        spin.set_value(value);
+        let lp = css_path.to_path_buf(); let mn = mod_name.to_string(); let exp = expander.clone(); let summary = Rc::clone(&summary);
+        spin.connect_value_changed(move |s| {
+            let v = s.value();
+            let value = if v == 0.0 { "0".to_string() } else { format_length(v, &unit) };
+            edit_module_rule(&lp, &mn, |css, sel| css::set_side(css, sel, prop, i, &value));
+            exp.set_subtitle(&summary(&lp));
+        });
+        row.add_suffix(&spin);
+        expander.add_row(&row);
+    }
+    expander
+}
+
+/// Border width, style and color, kept in a `border` shorthand when the rule uses one.
+fn border_row(css_path: &Path, mod_name: &str, style_rc: &Rc<RefCell<StyleConfig>>) -> adw::ExpanderRow {
+    let expander = adw::ExpanderRow::new();
+    expander.set_title("Border");
+    let css = fs::read_to_string(css_path).unwrap_or_default();
+    let selector = css::module_selector(mod_name);
+    let part = |p: &str| css::get_border_part(&css, &selector, p);
+    let describe = |w: Option<String>, s: Option<String>, c: Option<String>| {
+        let text = [w, s, c].into_iter().flatten().collect::<Vec<_>>().join(" ");
+        if text.is_empty() { "Layout default".to_string() } else { text }
+    };
+    expander.set_subtitle(&describe(part("width"), part("style"), part("color")));
+
+    let refresh_subtitle = {
+        let lp = css_path.to_path_buf(); let sel = selector.clone(); let exp = expander.clone();
+        Rc::new(move || {
+            let css = fs::read_to_string(&lp).unwrap_or_default();
+            let p = |x: &str| css::get_border_part(&css, &sel, x);
+            exp.set_subtitle(&describe(p("width"), p("style"), p("color")));
+        })
+    };
+
+    let width_row = ActionRow::new();
+    width_row.set_title("Width");
+    let width = gtk::SpinButton::with_range(0.0, 10.0, 1.0);
+    width.set_valign(gtk::Align::Center);
+    width.set_value(part("width").and_then(|v| css::parse_length(&v)).unwrap_or(0.0));
+    let lp_w = css_path.to_path_buf(); let mn_w = mod_name.to_string(); let rs_w = Rc::clone(&refresh_subtitle);
+    width.connect_value_changed(move |s| {
+        let v = s.value();
+        let value = if v == 0.0 { "0".to_string() } else { format_px(v) };
+        edit_module_rule(&lp_w, &mn_w, |css, sel| css::set_border_part(css, sel, "width", &value));
+        rs_w();
+    });
+    width_row.add_suffix(&width);
+    expander.add_row(&width_row);
+
+    let style_row = ComboRow::new();
+    style_row.set_title("Style");
+    let mut styles = vec!["default"];
+    styles.extend(css::BORDER_STYLES);
+    style_row.set_model(Some(&StringList::new(&styles)));
+    let current_style = part("style").and_then(|s| styles.iter().position(|x| *x == s)).unwrap_or(0);
+    style_row.set_selected(current_style as u32);
+    let lp_s = css_path.to_path_buf(); let mn_s = mod_name.to_string(); let rs_s = Rc::clone(&refresh_subtitle);
+    style_row.connect_selected_notify(move |row| {
+        let value = match row.selected() { 0 => "", i => css::BORDER_STYLES.get(i as usize - 1).copied().unwrap_or("") };
+        edit_module_rule(&lp_s, &mn_s, |css, sel| css::set_border_part(css, sel, "style", value));
+        rs_s();
+    });
+    expander.add_row(&style_row);
+
+    let color_row = ActionRow::new();
+    color_row.set_title("Color");
+    let color_btn = ColorButton::builder().valign(gtk::Align::Center).build();
+    if let Some(val) = part("color") {
+        let resolved = palette::resolve_vars(&style_rc.borrow().vars);
+        let val = val.strip_prefix('@').and_then(|v| resolved.get(v).cloned()).unwrap_or(val);
+        if let Ok(c) = gdk::RGBA::parse(&val) { color_btn.set_rgba(&c); }
+    }
+    let lp_c = css_path.to_path_buf(); let mn_c = mod_name.to_string(); let rs_c = Rc::clone(&refresh_subtitle);
+    color_btn.connect_color_set(move |b| {
+        let hex = rgba_to_hex(&b.rgba());
+        edit_module_rule(&lp_c, &mn_c, |css, sel| css::set_border_part(css, sel, "color", &hex));
+        rs_c();
+    });
+    color_row.add_suffix(&color_btn);
+    expander.add_row(&color_row);
+    expander
+}
+
+fn font_family_row(css_path: &Path, mod_name: &str) -> ActionRow {
+    let row = ActionRow::new();
+    row.set_title("Font Family");
+    let entry = Entry::builder()
+        .text(get_module_css_prop(css_path, mod_name, "", "font-family").unwrap_or_default())
+        .placeholder_text("Layout default")
+        .valign(gtk::Align::Center)
+        .build();
+    let lp = css_path.to_path_buf(); let mn = mod_name.to_string();
+    entry.connect_changed(move |e| update_module_css(&lp, &mn, "", "font-family", e.text().trim()));
+    row.add_suffix(&entry);
+    row
+}
+
+fn font_weight_row(css_path: &Path, mod_name: &str) -> ComboRow {
+    const WEIGHTS: &[&str] = &["default", "normal", "bold", "lighter", "bolder", "100", "200", "300", "400", "500", "600", "700", "800", "900"];
+    let row = ComboRow::new();
+    row.set_title("Font Weight");
+    row.set_model(Some(&StringList::new(WEIGHTS)));
+    let current = get_module_css_prop(css_path, mod_name, "", "font-weight").and_then(|w| WEIGHTS.iter().position(|x| *x == w)).unwrap_or(0);
+    row.set_selected(current as u32);
+    let lp = css_path.to_path_buf(); let mn = mod_name.to_string();
+    row.connect_selected_notify(move |r| {
+        let value = match r.selected() { 0 => "", i => WEIGHTS[i as usize] };
+        update_module_css(&lp, &mn, "", "font-weight", value);
+    });
+    row
+}
+
+/// Font family for every module (the `*` rule), picked from the installed families, plus
+/// a check of the glyphs used in formats against that font.
+fn font_group(css_path: &Path, config_rc: &Rc<RefCell<WaybarConfig>>, filter: &Rc<std::cell::Cell<Option<fonts::FamilyFilter>>>) -> PreferencesGroup {
+    let group = PreferencesGroup::new();
+    group.set_title("Font");
+    group.set_description(Some("Font family of every module. Icons in formats need a Nerd Font."));
+    let families = fonts::families();
+    let current = get_selector_css_prop(css_path, "*", "font-family");
+    let stack = current.as_deref().map(fonts::font_stack).unwrap_or_default();
+
+    let stack_row = ActionRow::new();
+    stack_row.set_title("Font Stack");
+    stack_row.set_subtitle(current.as_deref().unwrap_or("Layout default"));
+
+    if families.is_empty() {
+        let row = ActionRow::new();
+        row.set_title("Fontconfig not available");
+        row.set_subtitle("Install fontconfig (fc-list) to pick from the installed fonts");
+        group.add(&row);
+    } else {
+        let has_nerd = families.iter().any(|f| f.nerd);
+        if filter.get().is_none() {
+            filter.set(Some(if has_nerd { fonts::FamilyFilter::NerdFonts } else { fonts::FamilyFilter::All }));
+        }
+        if !has_nerd {
+            let row = ActionRow::new();
+            row.set_title("No Nerd Font installed");
+            row.set_subtitle("Icons from the icon picker will show as empty boxes");
+            row.add_prefix(&gtk::Image::from_icon_name("dialog-warning-symbolic"));
+            group.add(&row);
+        }
+
+        let filter_row = ComboRow::new();
+        filter_row.set_title("Show");
+        let labels: Vec<&str> = fonts::FamilyFilter::ALL.iter().map(|f| f.label()).collect();
+        filter_row.set_model(Some(&StringList::new(&labels)));
+        filter_row.set_selected(fonts::FamilyFilter::ALL.iter().position(|f| Some(*f) == filter.get()).unwrap_or(0) as u32);
+        group.add(&filter_row);
+
+        let family_row = ComboRow::new();
+        family_row.set_title("Family");
+        family_row.set_enable_search(true);
+        family_row.set_expression(Some(gtk::PropertyExpression::new(gtk::StringObject::static_type(), None::<gtk::Expression>, "string")));
+        group.add(&family_row);
+
+        // Shown names; the current primary family stays listed even when the filter hides it.
+        let shown = Rc::new(RefCell::new(Vec::<String>::new()));
+        let updating = Rc::new(std::cell::Cell::new(false));
+        let primary = stack.first().cloned();
+        let fill = {
+            let shown = Rc::clone(&shown); let updating = Rc::clone(&updating); let family_row = family_row.clone();
+            Rc::new(move |f: fonts::FamilyFilter| {
+                let mut names: Vec<String> = families.iter().filter(|x| f.accepts(x)).map(|x| x.name.clone()).collect();
+                if let Some(p) = &primary {
+                    if !names.contains(p) { names.insert(0, p.clone()); }
+                }
+                updating.set(true);
+                let labels: Vec<&str> = names.iter().map(|n| n.as_str()).collect();
+                family_row.set_model(Some(&StringList::new(&labels)));
+                family_row.set_selected(primary.as_ref().and_then(|p| names.iter().position(|n| n == p)).map(|i| i as u32).unwrap_or(gtk::INVALID_LIST_POSITION));
+                updating.set(false);
+                *shown.borrow_mut() = names;
+            })
+        };
+        fill(filter.get().unwrap_or(fonts::FamilyFilter::All));
+
+        let fill_f = Rc::clone(&fill); let filter_f = Rc::clone(filter);
+        filter_row.connect_selected_notify(move |row| {
+            let f = fonts::FamilyFilter::ALL[row.selected() as usize % 3];
+            filter_f.set(Some(f));
+            fill_f(f);
+        });
+
+        let lp = css_path.to_path_buf(); let stack_r = stack_row.clone();
+        family_row.connect_selected_notify(move |row| {
+            if updating.get() { return; }
+            let Some(name) = shown.borrow().get(row.selected() as usize).cloned() else { return };
+            let value = fonts::with_primary(get_selector_css_prop(&lp, "*", "font-family").as_deref(), &name);
+            update_selector_css(&lp, "*", "font-family", &value);
+            stack_r.set_subtitle(&value);
+        });
+    }
+    group.add(&stack_row);
+
+    // --- Glyph coverage ---
+    let glyphs = fonts::glyphs_in_config(&config_rc.borrow());
+    let coverage_row = adw::ExpanderRow::new();
+    coverage_row.set_title("Glyph Coverage");
+    coverage_row.set_subtitle(&format!("{} icons and symbols used in formats and format-icons", glyphs.len()));
+    let check_btn = Button::builder().label("Check").valign(gtk::Align::Center).sensitive(!glyphs.is_empty() && !families.is_empty()).build();
+    let results: Rc<RefCell<Vec<ActionRow>>> = Rc::new(RefCell::new(Vec::new()));
+    let lp_c = css_path.to_path_buf(); let cfg_c = Rc::clone(config_rc); let exp = coverage_row.clone();
+    check_btn.connect_clicked(move |_| {
+        for row in results.borrow_mut().drain(..) { exp.remove(&row); }
+        let stack = fonts::resolve_stack(&get_selector_css_prop(&lp_c, "*", "font-family").as_deref().map(fonts::font_stack).unwrap_or_default());
+        let (mut missing, mut fallback) = (0, 0);
+        for (glyph, modules) in fonts::glyphs_in_config(&cfg_c.borrow()) {
+            let Some(cov) = fonts::coverage(glyph, &stack) else { continue };
+            let row = ActionRow::new();
+            row.set_title(&format!("{}  U+{:04X}", glyph, glyph as u32));
+            let used = modules.join(", ");
+            match cov {
+                fonts::Coverage::InStack(f) => row.set_subtitle(&format!("{} · {}", f, used)),
+                fonts::Coverage::Fallback(f) => {
+                    fallback += 1;
+                    row.set_subtitle(&format!("Not in the font stack, available in {} · {}", f, used));
+                    row.add_prefix(&gtk::Image::from_icon_name("dialog-information-symbolic"));
+                }
+                fonts::Coverage::Missing => {
+                    missing += 1;
+                    row.set_subtitle(&format!("No installed font has this glyph; it shows as a box · {}", used));
+                    row.add_prefix(&gtk::Image::from_icon_name("dialog-warning-symbolic"));
+                    row.add_css_class("error");
+                }
+            }
+            exp.add_row(&row);
+            results.borrow_mut().push(row);
+        }
+        exp.set_subtitle(&if missing + fallback == 0 {
+            "Every glyph is in the font stack".to_string()
+        } else {
+            format!("{} missing (tofu), {} from fallback fonts", missing, fallback)
+        });
+        exp.set_expanded(true);
+    });
+    coverage_row.add_suffix(&check_btn);
+    group.add(&coverage_row);
+    group
+}
+
+/// The `tooltip` switch and every `tooltip-format` variant of a module, each previewed as
+/// Pango markup with sample values filled in.
+fn tooltip_editor(mod_name: &str, config_rc: &Rc<RefCell<WaybarConfig>>, rebuild: Rc<dyn Fn()>) -> PreferencesGroup {
+    let group = PreferencesGroup::new();
+    group.set_title("Tooltip");
+    group.set_description(Some("Formats may use Pango markup (bold, italic, small, span colors). Previews fill in sample values."));
+    let def = config_rc.borrow().module_definitions.get(mod_name).cloned().unwrap_or_else(|| serde_json::json!({}));
+
+    let show_row = ActionRow::builder().title("Show Tooltip").build();
+    let show_sw = Switch::builder().active(def.get("tooltip").and_then(|v| v.as_bool()).unwrap_or(true)).valign(gtk::Align::Center).build();
+    let cfg_s = Rc::clone(config_rc); let mn_s = mod_name.to_string();
+    show_sw.connect_state_set(move |_, on| {
+        if let Some(o) = cfg_s.borrow_mut().module_definitions.get_mut(&mn_s).and_then(|d| d.as_object_mut()) {
+            if on { o.remove("tooltip"); } else { o.insert("tooltip".to_string(), serde_json::Value::Bool(false)); }
+        }
+        glib::Propagation::Proceed
+    });
+    show_row.add_suffix(&show_sw);
+    group.add(&show_row);
+
+    // `{icon}` previews as the fullest entry of `format-icons`.
+    let icon = match def.get("format-icons") {
+        Some(serde_json::Value::Array(a)) => a.last(),
+        Some(serde_json::Value::Object(o)) => o.get("default").map(|d| d.as_array().and_then(|a| a.last()).unwrap_or(d)),
+        other => other,
+    }.and_then(|v| v.as_str()).unwrap_or("").to_string();
+    let preview = Rc::new(move |row: &ActionRow, entry: &Entry, text: &str| {
+        entry.remove_css_class("error");
+        if text.is_empty() { row.set_subtitle("Not set"); return; }
+        let rendered = tooltip::render_sample(text, &icon);
+        match gtk::pango::parse_markup(&rendered, '\0') {
+            Ok(_) => row.set_subtitle(&rendered),
+            Err(e) => {
+                row.set_subtitle(&glib::markup_escape_text(&format!("Invalid markup: {}", e.message())));
+                entry.add_css_class("error");
+            }
+        }
+    });
+
+    let keys = tooltip::format_keys(mod_name);
+    let mut missing = Vec::new();
+    for (i, key) in keys.iter().enumerate() {
+        let value = def.get(*key).and_then(|v| v.as_str());
+        if i > 0 && value.is_none() { missing.push(*key); continue; }
+        let row = ActionRow::builder().title(*key).build();
+        let en = Entry::builder().text(value.unwrap_or("")).valign(gtk::Align::Center).width_chars(16).build();
+        preview(&row, &en, value.unwrap_or(""));
+        let cfg_f = Rc::clone(config_rc); let mn_f = mod_name.to_string(); let row_f = row.clone(); let preview_f = Rc::clone(&preview);
+        en.connect_changed(move |e| {
+            set_module_string(&cfg_f, &mn_f, key, &e.text());
+            preview_f(&row_f, e, &e.text());
+        });
+        row.add_suffix(&en);
+        row.add_suffix(&glyph_picker(&en));
+        if i > 0 {
+            let del_btn = Button::builder().icon_name("user-trash-symbolic").has_frame(false).valign(gtk::Align::Center).tooltip_text("Remove variant").build();
+            let cfg_d = Rc::clone(config_rc); let mn_d = mod_name.to_string(); let rebuild_d = Rc::clone(&rebuild);
+            del_btn.connect_clicked(move |_| { set_module_string(&cfg_d, &mn_d, key, ""); rebuild_d(); });
+            row.add_suffix(&del_btn);
+        }
+        group.add(&row);
+    }
+
+    if !missing.is_empty() {
+        let add_row = ActionRow::builder().title("Add Variant").subtitle("Used instead of the plain format in that status").build();
+        let drop = gtk::DropDown::from_strings(&missing);
+        drop.set_valign(gtk::Align::Center);
+        let add_btn = Button::builder().icon_name("list-add-symbolic").has_frame(false).valign(gtk::Align::Center).build();
+        let cfg_a = Rc::clone(config_rc); let mn_a = mod_name.to_string(); let drop_a = drop.clone();
+        add_btn.connect_clicked(move |_| {
+            let Some(key) = missing.get(drop_a.selected() as usize) else { return };
+            // Start from the plain tooltip format, or the bar label when there is none.
+            let start = ["tooltip-format", "format"].iter()
+                .find_map(|k| cfg_a.borrow().module_definitions.get(&mn_a).and_then(|d| d.get(*k)).and_then(|v| v.as_str()).map(|s| s.to_string()))
+                .unwrap_or_else(|| "{}".to_string());
+            set_module_string(&cfg_a, &mn_a, key, &start);
+            rebuild();
+        });
+        add_row.add_suffix(&drop);
+        add_row.add_suffix(&add_btn);
+        group.add(&add_row);
+    }
+    group
+}
+
+/// Picker for one property of `selector` offering every palette variable; "default"
+/// removes the property.
+fn palette_var_row(title: &str, css_path: &Path, selector: &str, prop: &'static str, style_rc: &Rc<RefCell<StyleConfig>>) -> ComboRow {
+    let mut options: Vec<String> = vec!["default".to_string()];
+    options.extend(style_rc.borrow().vars.keys().map(|k| format!("@{}", k)));
+    let current = get_selector_css_prop(css_path, selector, prop);
+    if let Some(c) = &current { if !options.contains(c) { options.push(c.clone()); } }
+    let row = ComboRow::new();
+    row.set_title(title);
+    let labels: Vec<&str> = options.iter().map(|s| s.as_str()).collect();
+    row.set_model(Some(&StringList::new(&labels)));
+    row.set_selected(current.and_then(|c| options.iter().position(|o| *o == c)).unwrap_or(0) as u32);
+    let lp = css_path.to_path_buf(); let selector = selector.to_string();
+    row.connect_selected_notify(move |r| {
+        let value = match r.selected() { 0 => "", i => options[i as usize].as_str() };
+        update_selector_css(&lp, &selector, prop, value);
+    });
+    row
+}
+
+/// Styles for `tooltip` (the window) and `tooltip label` (its text).
+fn tooltip_group(css_path: &Path, style_rc: &Rc<RefCell<StyleConfig>>) -> PreferencesGroup {
+    let group = PreferencesGroup::new();
+    group.set_title("Tooltips");
+    group.set_description(Some("Styles the tooltip window and its text for every module."));
+    let css = fs::read_to_string(css_path).unwrap_or_default();
+    let spin_row = |title: &str, selector: &'static str, prop: &'static str, max: f64| {
+        let row = ActionRow::builder().title(title).build();
+        let spin = gtk::SpinButton::with_range(0.0, max, 1.0);
+        spin.set_valign(gtk::Align::Center);
+        spin.set_value(css::get_rule_prop(&css, selector, prop).and_then(|v| css::parse_length(&v)).unwrap_or(0.0));
+        let lp = css_path.to_path_buf();
+        spin.connect_value_changed(move |s| {
+            let value = if s.value() == 0.0 { String::new() } else { format_px(s.value()) };
+            update_selector_css(&lp, selector, prop, &value);
+        });
+        row.add_suffix(&spin);
+        row
+    };
+
+    // Background is a palette variable, wrapped in alpha() below full opacity.
+    let background = css::get_rule_prop(&css, "tooltip", "background");
+    let alpha_re = regex::Regex::new(r"^alpha\(\s*(@[\w-]+)\s*,\s*([\d.]+)\s*\)$").unwrap();
+    let (bg_var, bg_opacity) = match background.as_deref() {
+        Some(v) => match alpha_re.captures(v) {
+            Some(c) => (c[1].to_string(), c[2].parse::<f64>().unwrap_or(1.0) * 100.0),
+            None => (v.to_string(), 100.0),
+        },
+        None => (String::new(), 100.0),
+    };
+    let bg_state = Rc::new(RefCell::new((bg_var.clone(), bg_opacity)));
+    let write_bg = {
+        let lp = css_path.to_path_buf(); let state = Rc::clone(&bg_state);
+        Rc::new(move || {
+            let (var, opacity) = state.borrow().clone();
+            let value = if var.is_empty() { String::new() } else if opacity >= 100.0 { var } else { format!("alpha({}, {:.2})", var, opacity / 100.0) };
+            update_selector_css(&lp, "tooltip", "background", &value);
+        })
+    };
+    let mut bg_options: Vec<String> = vec!["default".to_string()];
+    bg_options.extend(style_rc.borrow().vars.keys().map(|k| format!("@{}", k)));
+    if !bg_var.is_empty() && !bg_options.contains(&bg_var) { bg_options.push(bg_var.clone()); }
+    let bg_row = ComboRow::new();
+    bg_row.set_title("Background");
+    let labels: Vec<&str> = bg_options.iter().map(|s| s.as_str()).collect();
+    bg_row.set_model(Some(&StringList::new(&labels)));
+    bg_row.set_selected(bg_options.iter().position(|o| *o == bg_var).unwrap_or(0) as u32);
+    let state_b = Rc::clone(&bg_state); let write_b = Rc::clone(&write_bg);
+    bg_row.connect_selected_notify(move |r| {
+        state_b.borrow_mut().0 = match r.selected() { 0 => String::new(), i => bg_options[i as usize].clone() };
+        write_b();
+    });
+    group.add(&bg_row);
+
+    let opacity_row = ActionRow::builder().title("Background Opacity (%)").build();
+    let opacity = gtk::Scale::with_range(Orientation::Horizontal, 0.0, 100.0, 1.0);
+    opacity.set_value(bg_opacity);
+    opacity.set_width_request(150);
+    opacity.set_valign(gtk::Align::Center);
+    let state_o = Rc::clone(&bg_state);
+    opacity.connect_value_changed(move |s| {
+        state_o.borrow_mut().1 = s.value();
+        write_bg();
+    });
+    opacity_row.add_suffix(&opacity);
+    group.add(&opacity_row);
+
+    group.add(&palette_var_row("Text Color", css_path, "tooltip label", "color", style_rc));
+
+    let border_width = ActionRow::builder().title("Border Width").build();
+    let width = gtk::SpinButton::with_range(0.0, 10.0, 1.0);
+    width.set_valign(gtk::Align::Center);
+    width.set_value(css::get_border_part(&css, "tooltip", "width").and_then(|v| css::parse_length(&v)).unwrap_or(0.0));
+    let lp_w = css_path.to_path_buf();
+    width.connect_value_changed(move |s| {
+        let value = if s.value() == 0.0 { "0".to_string() } else { format_px(s.value()) };
+        let full = fs::read_to_string(&lp_w).unwrap_or_default();
+        let _ = fs::write(&lp_w, css::set_border_part(&full, "tooltip", "width", &value));
+    });
+    border_width.add_suffix(&width);
+    group.add(&border_width);
+
+    let border_color = ComboRow::new();
+    border_color.set_title("Border Color");
+    let mut color_options: Vec<String> = vec!["default".to_string()];
+    color_options.extend(style_rc.borrow().vars.keys().map(|k| format!("@{}", k)));
+    let current_color = css::get_border_part(&css, "tooltip", "color");
+    if let Some(c) = &current_color { if !color_options.contains(c) { color_options.push(c.clone()); } }
+    let labels: Vec<&str> = color_options.iter().map(|s| s.as_str()).collect();
+    border_color.set_model(Some(&StringList::new(&labels)));
+    border_color.set_selected(current_color.and_then(|c| color_options.iter().position(|o| *o == c)).unwrap_or(0) as u32);
+    let lp_c = css_path.to_path_buf();
+    border_color.connect_selected_notify(move |r| {
+        let value = match r.selected() { 0 => "", i => color_options[i as usize].as_str() };
+        let full = fs::read_to_string(&lp_c).unwrap_or_default();
+        let _ = fs::write(&lp_c, css::set_border_part(&full, "tooltip", "color", value));
+    });
+    group.add(&border_color);
+
+    group.add(&spin_row("Corner Radius", "tooltip", "border-radius", 30.0));
+    group.add(&spin_row("Padding", "tooltip", "padding", 30.0));
+
+    let family_row = ActionRow::builder().title("Font Family").build();
+    let family = Entry::builder()
+        .text(css::get_rule_prop(&css, "tooltip label", "font-family").unwrap_or_default())
+        .placeholder_text("Bar font")
+        .valign(gtk::Align::Center)
+        .build();
+    let lp_f = css_path.to_path_buf();
+    family.connect_changed(move |e| update_selector_css(&lp_f, "tooltip label", "font-family", e.text().trim()));
+    family_row.add_suffix(&family);
+    group.add(&family_row);
+    group.add(&spin_row("Font Size", "tooltip label", "font-size", 40.0));
+    group
+}
+
+/// Styling for a group as a unit: its own box (`#group-name`), a shared background
+/// behind the members, the leader (first module) and, in drawer mode, the children that
+/// slide out, found through `drawer.children-class`.
+fn group_style_editor(mod_name: &str, config_rc: &Rc<RefCell<WaybarConfig>>, css_path: &Path, style_rc: &Rc<RefCell<StyleConfig>>, rebuild: Rc<dyn Fn()>) -> PreferencesGroup {
+    let group = PreferencesGroup::new();
+    group.set_title("Group Styling");
+    let scope = css::module_selector(mod_name);
+    group.set_description(Some(&format!("Rules under {} only affect this group.", scope)));
+    let def = config_rc.borrow().module_definitions.get(mod_name).cloned().unwrap_or_else(|| serde_json::json!({}));
+    let members: Vec<String> = def.get("modules").and_then(|v| v.as_array())
+        .map(|a| a.iter().filter_map(|m| m.as_str().map(|s| s.to_string())).collect()).unwrap_or_default();
+    let member_selectors: Vec<String> = members.iter().map(|m| format!("{} {}", scope, css::module_selector(m))).collect();
+    let css = fs::read_to_string(css_path).unwrap_or_default();
+
+    group.add(&palette_var_row("Background", css_path, &scope, "background", style_rc));
+    group.add(&css_length_row("Corner Radius", css_path, mod_name, "border-radius", 0.0, 30.0, 1.0, 0.0));
+
+    let shared_row = ActionRow::builder().title("Shared Background").subtitle("One pill behind all members; their own backgrounds turn transparent").build();
+    let shared = !member_selectors.is_empty()
+        && member_selectors.iter().all(|s| css::get_rule_prop(&css, s, "background").as_deref() == Some("transparent"));
+    let shared_sw = Switch::builder().active(shared).valign(gtk::Align::Center).sensitive(!members.is_empty()).build();
+    let lp_s = css_path.to_path_buf(); let scope_s = scope.clone(); let rebuild_s = Rc::clone(&rebuild);
+    shared_sw.connect_state_set(move |_, on| {
+        let mut full = fs::read_to_string(&lp_s).unwrap_or_default();
+        for sel in &member_selectors {
+            full = css::set_rule_prop(&full, sel, "background", if on { "transparent" } else { "" });
+            full = css::set_rule_prop(&full, sel, "box-shadow", if on { "none" } else { "" });
+            if !on { full = css::remove_rule_if_empty(&full, sel); }
+        }
+        if on && css::get_rule_prop(&full, &scope_s, "background").is_none() {
+            full = css::set_rule_prop(&full, &scope_s, "background", "@module_bg");
+        }
+        let _ = fs::write(&lp_s, full);
+        let rebuild = Rc::clone(&rebuild_s);
+        glib::idle_add_local_once(move || rebuild());
+        glib::Propagation::Proceed
+    });
+    shared_row.add_suffix(&shared_sw);
+    group.add(&shared_row);
+
+    if let Some(leader) = members.first() {
+        let leader_sel = format!("{} {}", scope, css::module_selector(leader));
+        let expander = adw::ExpanderRow::new();
+        expander.set_title("Leader");
+        expander.set_subtitle(&format!("{} · always visible, opens the drawer", leader));
+        expander.add_row(&palette_var_row("Text Color", css_path, &leader_sel, "color", style_rc));
+        expander.add_row(&palette_var_row("Background", css_path, &leader_sel, "background", style_rc));
+        group.add(&expander);
+    }
+
+    let Some(drawer) = def.get("drawer").and_then(|d| d.as_object()) else { return group };
+    let class = drawer.get("children-class").and_then(|v| v.as_str()).unwrap_or("drawer-child").to_string();
+    let children_sel = format!("{} .{}", scope, class);
+    let expander = adw::ExpanderRow::new();
+    expander.set_title("Drawer Children");
+    expander.set_subtitle(&format!("{} · hidden until the drawer opens", children_sel));
+
+    let class_row = ActionRow::builder().title("Children Class").subtitle("Press Enter to rename; existing rules follow").build();
+    let class_en = Entry::builder().text(&class).placeholder_text("drawer-child").valign(gtk::Align::Center).build();
+    let cfg_c = Rc::clone(config_rc); let mn_c = mod_name.to_string(); let lp_c = css_path.to_path_buf(); let scope_c = scope.clone();
+    class_en.connect_activate(move |e| {
+        let text = e.text().trim().to_string();
+        let new_class = if text.is_empty() { "drawer-child".to_string() } else { text };
+        if new_class == class { return; }
+        if !css::is_identifier(&new_class) { e.add_css_class("error"); return; }
+        edit_module_map(&cfg_c, &mn_c, "drawer", |d| {
+            if new_class == "drawer-child" { d.remove("children-class"); } else { d.insert("children-class".to_string(), serde_json::json!(new_class)); }
+        });
+        if let Ok(full) = fs::read_to_string(&lp_c) { let _ = fs::write(&lp_c, css::rename_class(&full, &scope_c, &class, &new_class)); }
+        rebuild();
+    });
+    class_en.connect_changed(|e| e.remove_css_class("error"));
+    class_row.add_suffix(&class_en);
+    expander.add_row(&class_row);
+
+    expander.add_row(&palette_var_row("Text Color", css_path, &children_sel, "color", style_rc));
+    expander.add_row(&palette_var_row("Background", css_path, &children_sel, "background", style_rc));
+
+    let opacity_row = ActionRow::builder().title("Opacity (%)").build();
+    let opacity = gtk::Scale::with_range(Orientation::Horizontal, 10.0, 100.0, 1.0);
+    opacity.set_value(css::get_rule_prop(&css, &children_sel, "opacity").and_then(|v| v.parse::<f64>().ok()).map(|v| v * 100.0).unwrap_or(100.0));
+    opacity.set_width_request(150);
+    opacity.set_valign(gtk::Align::Center);
+    let lp_o = css_path.to_path_buf();
+    opacity.connect_value_changed(move |s| {
+        let value = if s.value() >= 100.0 { String::new() } else { format!("{:.2}", s.value() / 100.0) };
+        update_selector_css(&lp_o, &children_sel, "opacity", &value);
+    });
+    opacity_row.add_suffix(&opacity);
+    expander.add_row(&opacity_row);
+    group.add(&expander);
+    group
+}
+
+/// Offers to replace the hard-coded colors of the session stylesheet with palette
+/// variables: near-identical colors are grouped, each group gets a proposed variable that
+/// can be renamed or skipped, and nothing changes until the user confirms.
+fn recolor_dialog(parent: Option<gtk::Window>, css_path: &Path, style_rc: &Rc<RefCell<StyleConfig>>, toast: &ToastOverlay, on_done: Rc<dyn Fn()>) {
+    let css = fs::read_to_string(css_path).unwrap_or_default();
+    let uses = recolor::find_colors(&css);
+    if uses.is_empty() {
+        toast.add_toast(Toast::new("No hard-coded colors found"));
+        return;
+    }
+    let clusters = recolor::propose(&css, &uses, &style_rc.borrow().vars);
+    let dialog = MessageDialog::builder()
+        .heading("Convert Colors to Palette Variables")
+        .body(format!("Found {} hard-coded colors in {} groups. Converted colors follow theme presets and wallpaper extraction.", uses.len(), clusters.len()))
+        .build();
+    if let Some(win) = parent { dialog.set_transient_for(Some(&win)); }
+
+    let list = ListBox::new();
+    list.add_css_class("boxed-list");
+    list.set_selection_mode(gtk::SelectionMode::None);
+    let mut controls: Vec<(gtk::CheckButton, Entry, ActionRow, String)> = Vec::new();
+    for cluster in &clusters {
+        let row = ActionRow::new();
+        row.set_title(&glib::markup_escape_text(&cluster.literals.join(", ")));
+        let first = &uses[cluster.uses[0]];
+        let subtitle = format!("{} uses · {} {}", cluster.uses.len(), first.selector, first.prop);
+        let check = gtk::CheckButton::builder().active(true).valign(gtk::Align::Center).build();
+        row.add_prefix(&check);
+        if let Ok(c) = gdk::RGBA::parse(cluster.color.as_str()) {
+            let swatch = swatch_strip(vec![c]);
+            swatch.set_size_request(24, 24);
+            swatch.set_valign(gtk::Align::Center);
+            row.add_prefix(&swatch);
+        }
+        let entry = Entry::builder().text(&cluster.var).width_chars(12).valign(gtk::Align::Center).build();
+        row.add_suffix(&entry);
+        list.append(&row);
+        controls.push((check, entry, row, subtitle));
+    }
+    dialog.set_extra_child(Some(&ScrolledWindow::builder().child(&list).max_content_height(360).propagate_natural_height(true).min_content_width(420).build()));
+    dialog.add_response("cancel", "Keep as Is");
+    dialog.add_response("convert", "Convert");
+    dialog.set_response_appearance("convert", adw::ResponseAppearance::Suggested);
+
+    // A name must be valid and unique among the checked groups; Convert stays disabled
+    // until every checked one is. Naming a group after a palette variable with another
+    // color (the role names usually are) gives that variable the loaded color.
+    let controls = Rc::new(controls);
+    let resolved = palette::resolve_vars(&style_rc.borrow().vars);
+    let colors: Vec<String> = clusters.iter().map(|c| c.color.clone()).collect();
+    let validate = {
+        let controls = Rc::clone(&controls); let d = dialog.clone();
+        Rc::new(move || {
+            let names: Vec<Option<String>> = controls.iter().map(|(check, entry, _, _)| check.is_active().then(|| entry.text().trim().to_string())).collect();
+            let mut ok = true;
+            for (i, (_, entry, row, subtitle)) in controls.iter().enumerate() {
+                let name = entry.text().trim().to_string();
+                let problem = if !css::is_identifier(&name) {
+                    Some("Not a valid variable name".to_string())
+                } else if names[i].is_none() {
+                    None
+                } else if names.iter().enumerate().any(|(j, n)| j != i && n.as_deref() == Some(name.as_str())) {
+                    Some("Another group uses this name".to_string())
+                } else {
+                    None
+                };
+                let replaces = resolved.get(&name).filter(|v| names[i].is_some() && !v.eq_ignore_ascii_case(&colors[i]));
+                row.set_subtitle(&glib::markup_escape_text(&match replaces {
+                    Some(v) => format!("{} · replaces @{} {}", subtitle, name, v),
+                    None => subtitle.clone(),
+                }));
+                if problem.is_some() && names[i].is_some() { ok = false; }
+                match problem {
+                    Some(msg) => { entry.add_css_class("error"); entry.set_tooltip_text(Some(&msg)); }
+                    None => { entry.remove_css_class("error"); entry.set_tooltip_text(None); }
+                }
+            }
+            d.set_response_enabled("convert", ok);
+        })
+    };
+    for (check, entry, _, _) in controls.iter() {
+        let v = Rc::clone(&validate); check.connect_toggled(move |_| v());
+        let v = Rc::clone(&validate); entry.connect_changed(move |_| v());
+    }
+    validate();
+
+    let lp = css_path.to_path_buf(); let style_d = Rc::clone(style_rc); let toast_d = toast.clone();
+    dialog.connect_response(None, move |d, response| {
+        if response == "convert" {
+            let chosen: Vec<recolor::Cluster> = clusters.iter().zip(controls.iter())
+                .filter(|(_, (check, entry, _, _))| check.is_active() && css::is_identifier(entry.text().trim()))
+                .map(|(c, (_, entry, _, _))| recolor::Cluster { var: entry.text().trim().to_string(), ..c.clone() })
+                .collect();
+            if !chosen.is_empty() {
+                {
+                    let mut style = style_d.borrow_mut();
+                    for c in &chosen { style.vars.insert(c.var.clone(), c.color.clone()); }
+                    let _ = style.save();
+                }
+                let _ = fs::write(&lp, recolor::rewrite(&css, &uses, &chosen));
+                toast_d.add_toast(Toast::new(&format!("Converted {} colors to palette variables", chosen.len())));
+                on_done();
+            }
+        }
+        d.close();
+    });
+    dialog.present();
+}
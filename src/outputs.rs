@@ -0,0 +1,217 @@
+use serde_json::Value;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use crate::config::OneOrMany;
+use crate::export::app_config_dir;
+
+/// A monitor as reported by the compositor, or entered by hand.
+#[derive(Debug, Clone)]
+pub struct Monitor {
+    pub name: String,
+    /// `make model serial`, which Waybar also accepts in `output`.
+    pub description: String,
+}
+
+fn run_json(cmd: &str, args: &[&str]) -> Option<Value> {
+    let out = Command::new(cmd).args(args).output().ok()?;
+    if !out.status.success() { return None; }
+    serde_json::from_slice(&out.stdout).ok()
+}
+
+fn monitor_from(v: &Value, fallback_name: &str) -> Monitor {
+    let field = |k: &str| v.get(k).and_then(|x| x.as_str()).unwrap_or("").to_string();
+    let description = match v.get("description").and_then(|d| d.as_str()) {
+        Some(d) => d.to_string(),
+        None => [field("make"), field("model"), field("serial")].iter()
+            .filter(|s| !s.is_empty() && s.as_str() != "Unknown")
+            .cloned().collect::<Vec<_>>().join(" "),
+    };
+    let name = field("name");
+    Monitor { name: if name.is_empty() { fallback_name.to_string() } else { name }, description }
+}
+
+/// The IPC tool that answered and the monitors it listed.
+pub type Detection = Option<(&'static str, Vec<Monitor>)>;
+
+/// Asks Hyprland, Sway and niri in turn.
+pub fn detect_monitors() -> Detection {
+    if let Some(Value::Array(list)) = run_json("hyprctl", &["monitors", "-j"]) {
+        return Some(("hyprctl", list.iter().map(|m| monitor_from(m, "")).collect()));
+    }
+    if let Some(Value::Array(list)) = run_json("swaymsg", &["-t", "get_outputs", "-r"]) {
+        return Some(("swaymsg", list.iter().map(|m| monitor_from(m, "")).collect()));
+    }
+    if let Some(Value::Object(map)) = run_json("niri", &["msg", "--json", "outputs"]) {
+        return Some(("niri", map.iter().map(|(k, m)| monitor_from(m, k)).collect()));
+    }
+    None
+}
+
+fn manual_path() -> PathBuf {
+    app_config_dir().join("outputs.json")
+}
+
+/// Monitor names added by hand, for when no compositor IPC is available.
+pub fn load_manual_monitors() -> Vec<String> {
+    fs::read_to_string(manual_path())
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_manual_monitors(names: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let path = manual_path();
+    if let Some(dir) = path.parent() { fs::create_dir_all(dir)?; }
+    fs::write(path, serde_json::to_string_pretty(names)?)?;
+    Ok(())
+}
+
+/// Whether a bar with this `output` value appears on `monitor`, following Waybar's rules:
+/// no `output` means every monitor; a string must equal the monitor name or description,
+/// or exclude it with `!name`; list entries are checked in order, `!name` excludes, `*`
+/// matches any monitor not excluded before it, and anything else must equal the monitor
+/// name or description.
+pub fn shows_on(output: Option<&OneOrMany>, monitor: &Monitor) -> bool {
+    let is_monitor = |s: &str| s == monitor.name || (!monitor.description.is_empty() && s == monitor.description);
+    match output {
+        None => true,
+        Some(OneOrMany::One(s)) => match s.strip_prefix('!') {
+            Some(excluded) => !is_monitor(excluded),
+            None => is_monitor(s),
+        },
+        Some(OneOrMany::Many(list)) => {
+            for entry in list {
+                if let Some(excluded) = entry.strip_prefix('!') {
+                    if is_monitor(excluded) { return false; }
+                } else if entry.starts_with('*') || is_monitor(entry) {
+                    return true;
+                }
+            }
+            false
+        }
+    }
+}
+
+/// How a bar's `output` list refers to one monitor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Assignment {
+    Unlisted,
+    Include,
+    Exclude,
+}
+
+pub fn assignment(output: Option<&OneOrMany>, name: &str) -> Assignment {
+    let list = output.map(|o| o.to_vec()).unwrap_or_default();
+    if list.iter().any(|e| e == name) {
+        Assignment::Include
+    } else if list.iter().any(|e| e.strip_prefix('!') == Some(name)) {
+        Assignment::Exclude
+    } else {
+        Assignment::Unlisted
+    }
+}
+
+/// Rewrites `output` so `name` is included, excluded or not mentioned. Exclusions are kept
+/// in front of the `*` wildcard so they take effect.
+pub fn set_assignment(output: Option<&OneOrMany>, name: &str, to: Assignment) -> Option<OneOrMany> {
+    let mut list: Vec<String> = output.map(|o| o.to_vec()).unwrap_or_default();
+    list.retain(|e| e != name && e.strip_prefix('!') != Some(name));
+    match to {
+        Assignment::Include => {
+            let at = list.iter().position(|e| e == "*").unwrap_or(list.len());
+            list.insert(at, name.to_string());
+        }
+        Assignment::Exclude => list.insert(0, format!("!{}", name)),
+        Assignment::Unlisted => {}
+    }
+    OneOrMany::from_vec(list)
+}
+
+/// Adds or removes the trailing `*` entry ("every other monitor").
+pub fn set_wildcard(output: Option<&OneOrMany>, on: bool) -> Option<OneOrMany> {
+    let mut list: Vec<String> = output.map(|o| o.to_vec()).unwrap_or_default();
+    list.retain(|e| e != "*");
+    if on { list.push("*".to_string()); }
+    OneOrMany::from_vec(list)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn one(s: &str) -> Option<OneOrMany> { Some(OneOrMany::One(s.to_string())) }
+    fn many(items: &[&str]) -> Option<OneOrMany> { Some(OneOrMany::Many(items.iter().map(|s| s.to_string()).collect())) }
+    fn monitor(name: &str) -> Monitor { Monitor { name: name.to_string(), description: String::new() } }
+
+    #[test]
+    fn from_vec_keeps_wildcards_and_exclusions_in_a_list() {
+        let cases: &[(&[&str], Option<OneOrMany>)] = &[
+            (&[], None),
+            (&[""], None),
+            (&["DP-1"], one("DP-1")),
+            (&["*"], many(&["*"])),
+            (&["!DP-1"], many(&["!DP-1", "*"])),
+            (&["!DP-1", "!HDMI-A-1"], many(&["!DP-1", "!HDMI-A-1", "*"])),
+            (&["!DP-1", "*"], many(&["!DP-1", "*"])),
+            (&["DP-1", "HDMI-A-1"], many(&["DP-1", "HDMI-A-1"])),
+        ];
+        for (items, expected) in cases {
+            assert_eq!(&OneOrMany::from_vec(items.iter().map(|s| s.to_string()).collect()), expected, "{:?}", items);
+        }
+    }
+
+    #[test]
+    fn shows_on_follows_waybar() {
+        let cases: &[(Option<OneOrMany>, &str, bool)] = &[
+            (None, "DP-1", true),
+            (one("DP-1"), "DP-1", true),
+            (one("DP-1"), "HDMI-A-1", false),
+            (one("*"), "DP-1", false),
+            (one("!DP-1"), "DP-1", false),
+            (one("!DP-1"), "HDMI-A-1", true),
+            (many(&["*"]), "DP-1", true),
+            (many(&["!DP-1"]), "HDMI-A-1", false),
+            (many(&["!DP-1", "*"]), "DP-1", false),
+            (many(&["!DP-1", "*"]), "HDMI-A-1", true),
+            (many(&["*", "!DP-1"]), "DP-1", true),
+            (many(&["DP-1", "HDMI-A-1"]), "HDMI-A-1", true),
+            (many(&["DP-1", "HDMI-A-1"]), "eDP-1", false),
+        ];
+        for (output, name, expected) in cases {
+            assert_eq!(shows_on(output.as_ref(), &monitor(name)), *expected, "{:?} on {}", output, name);
+        }
+    }
+
+    #[test]
+    fn set_assignment_keeps_the_bar_visible() {
+        let cases: &[(Option<OneOrMany>, &str, Assignment, Option<OneOrMany>)] = &[
+            (None, "DP-1", Assignment::Include, one("DP-1")),
+            (None, "DP-1", Assignment::Exclude, many(&["!DP-1", "*"])),
+            (one("DP-1"), "DP-1", Assignment::Unlisted, None),
+            (one("DP-1"), "DP-1", Assignment::Exclude, many(&["!DP-1", "*"])),
+            (many(&["!DP-1", "*"]), "DP-1", Assignment::Unlisted, many(&["*"])),
+            (many(&["!DP-1", "*"]), "DP-1", Assignment::Include, many(&["DP-1", "*"])),
+            (many(&["DP-1", "*"]), "HDMI-A-1", Assignment::Exclude, many(&["!HDMI-A-1", "DP-1", "*"])),
+            (many(&["DP-1", "HDMI-A-1"]), "DP-1", Assignment::Exclude, many(&["!DP-1", "HDMI-A-1"])),
+        ];
+        for (output, name, to, expected) in cases {
+            assert_eq!(&set_assignment(output.as_ref(), name, *to), expected, "{:?} {} {:?}", output, name, to);
+        }
+    }
+
+    #[test]
+    fn set_wildcard_never_writes_a_bare_star() {
+        let cases: &[(Option<OneOrMany>, bool, Option<OneOrMany>)] = &[
+            (None, true, many(&["*"])),
+            (None, false, None),
+            (one("DP-1"), true, many(&["DP-1", "*"])),
+            (many(&["DP-1", "*"]), false, one("DP-1")),
+            (many(&["*"]), false, None),
+            (many(&["!DP-1", "*"]), false, many(&["!DP-1", "*"])),
+        ];
+        for (output, on, expected) in cases {
+            assert_eq!(&set_wildcard(output.as_ref(), *on), expected, "{:?} {}", output, on);
+        }
+    }
+}
@@ -0,0 +1,317 @@
+use indexmap::IndexMap;
+use std::fs;
+use std::path::PathBuf;
+use crate::resources::{self, Layer, PresetFile};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PresetSource {
+    Bundled,
+    User,
+}
+
+#[derive(Debug, Clone)]
+pub struct ColorPreset {
+    pub name: String,
+    pub file: PresetFile,
+    pub source: PresetSource,
+}
+
+impl ColorPreset {
+    pub fn label(&self) -> String {
+        match self.source {
+            PresetSource::Bundled => self.name.clone(),
+            PresetSource::User => format!("{} (user)", self.name),
+        }
+    }
+
+    pub fn load_vars(&self) -> IndexMap<String, String> {
+        self.file.read().map(|c| parse_style_vars(&c)).unwrap_or_default()
+    }
+}
+
+pub fn parse_style_vars(content: &str) -> IndexMap<String, String> {
+    let mut vars = IndexMap::new();
+    for line in content.lines() {
+        let mut line = line.trim();
+        // Metrics are written as `/* --name: value; */`
+        if let Some(inner) = line.strip_prefix("/*").and_then(|l| l.strip_suffix("*/")) {
+            line = inner.trim();
+        }
+        if let Some(rest) = line.strip_prefix("@define-color") {
+            // The value runs up to the `;`, spaces included: `alpha(@bar_bg, 0.8)`.
+            if let Some((name, value)) = rest.trim_start().split_once(char::is_whitespace) {
+                let value = value.split(';').next().unwrap_or("").trim();
+                if !value.is_empty() { vars.insert(name.to_string(), value.to_string()); }
+            }
+        } else if line.starts_with("--") {
+            let parts: Vec<&str> = line.split(':').collect();
+            if parts.len() >= 2 {
+                let name = parts[0].trim_matches('-').trim().to_string();
+                let value = parts[1].trim_matches(';').trim().to_string();
+                vars.insert(name, value);
+            }
+        }
+    }
+    vars
+}
+
+/// Follows `@name` references (e.g. `module_fg @bar_fg`) until a literal value is reached.
+pub fn resolve_vars(vars: &IndexMap<String, String>) -> IndexMap<String, String> {
+    vars.iter().map(|(k, v)| {
+        let mut value = v.clone();
+        for _ in 0..8 {
+            match value.strip_prefix('@').and_then(|r| vars.get(r)) {
+                Some(next) => value = next.clone(),
+                None => break,
+            }
+        }
+        (k.clone(), value)
+    }).collect()
+}
+
+pub fn hex_to_rgb(hex: &str) -> Option<(u8, u8, u8)> {
+    let h = hex.strip_prefix('#')?;
+    let expanded: String = match h.len() {
+        3 | 4 => h.chars().take(3).flat_map(|c| [c, c]).collect(),
+        6 | 8 => h[..6].to_string(),
+        _ => return None,
+    };
+    let n = u32::from_str_radix(&expanded, 16).ok()?;
+    Some(((n >> 16) as u8, (n >> 8) as u8, n as u8))
+}
+
+pub fn rgb_to_hsl((r, g, b): (u8, u8, u8)) -> (f64, f64, f64) {
+    let (r, g, b) = (r as f64 / 255.0, g as f64 / 255.0, b as f64 / 255.0);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let l = (max + min) / 2.0;
+    if (max - min).abs() < f64::EPSILON {
+        return (0.0, 0.0, l);
+    }
+    let d = max - min;
+    let s = if l > 0.5 { d / (2.0 - max - min) } else { d / (max + min) };
+    let h = if max == r {
+        (g - b) / d + if g < b { 6.0 } else { 0.0 }
+    } else if max == g {
+        (b - r) / d + 2.0
+    } else {
+        (r - g) / d + 4.0
+    };
+    (h * 60.0, s, l)
+}
+
+pub fn hsl_to_hex(h: f64, s: f64, l: f64) -> String {
+    let h = h.rem_euclid(360.0) / 360.0;
+    let (s, l) = (s.clamp(0.0, 1.0), l.clamp(0.0, 1.0));
+    let channel = |t: f64| -> u8 {
+        let q = if l < 0.5 { l * (1.0 + s) } else { l + s - l * s };
+        let p = 2.0 * l - q;
+        let t = t.rem_euclid(1.0);
+        let v = if t < 1.0 / 6.0 { p + (q - p) * 6.0 * t }
+            else if t < 0.5 { q }
+            else if t < 2.0 / 3.0 { p + (q - p) * (2.0 / 3.0 - t) * 6.0 }
+            else { p };
+        (v * 255.0).round() as u8
+    };
+    format!("#{:02x}{:02x}{:02x}", channel(h + 1.0 / 3.0), channel(h), channel(h - 1.0 / 3.0))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Harmony {
+    Complementary,
+    Analogous,
+    Triadic,
+    Monochrome,
+}
+
+impl Harmony {
+    pub const ALL: [Harmony; 4] = [Harmony::Complementary, Harmony::Analogous, Harmony::Triadic, Harmony::Monochrome];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Harmony::Complementary => "Complementary",
+            Harmony::Analogous => "Analogous",
+            Harmony::Triadic => "Triadic",
+            Harmony::Monochrome => "Monochrome",
+        }
+    }
+
+    /// Hue offsets (degrees) of the accent colors derived from the seed.
+    fn offsets(&self) -> [f64; 3] {
+        match self {
+            Harmony::Complementary => [0.0, 180.0, 150.0],
+            Harmony::Analogous => [0.0, 30.0, -30.0],
+            Harmony::Triadic => [0.0, 120.0, 240.0],
+            Harmony::Monochrome => [0.0, 0.0, 0.0],
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GeneratorOptions {
+    pub seed: String,
+    pub harmony: Harmony,
+    pub dark: bool,
+    /// Bumped by "Shuffle"; picks a different (but deterministic) arrangement of the same harmony.
+    pub shuffle: u32,
+}
+
+impl Default for GeneratorOptions {
+    fn default() -> Self {
+        Self { seed: "#89b4fa".into(), harmony: Harmony::Complementary, dark: true, shuffle: 0 }
+    }
+}
+
+/// Builds the full set of role variables (`bar_bg`, `module_fg`, `hover_bg`...) around a seed color.
+pub fn generate_palette(opts: &GeneratorOptions) -> Option<IndexMap<String, String>> {
+    let (h, s, _) = rgb_to_hsl(hex_to_rgb(&opts.seed)?);
+    let s = s.max(0.35);
+
+    // Small xorshift so each shuffle step is repeatable.
+    let mut state = opts.shuffle.wrapping_mul(2654435761).wrapping_add(1);
+    let mut next = || { state ^= state << 13; state ^= state >> 17; state ^= state << 5; (state % 1000) as f64 / 1000.0 };
+
+    let mut hues = opts.harmony.offsets().map(|o| h + o);
+    if opts.shuffle > 0 {
+        // Rotate which harmony hue takes which role and jitter the base hue a little.
+        let rot = (opts.shuffle as usize) % 3;
+        hues.rotate_left(rot);
+        let jitter = (next() - 0.5) * 20.0;
+        for hue in hues.iter_mut() { *hue += jitter; }
+    }
+    let [accent, second, third] = hues;
+    let mono = opts.harmony == Harmony::Monochrome;
+    let tint = if mono { s * 0.35 } else { s * 0.2 } + next() * 0.05;
+
+    let (bg_l, module_l, fg_l, accent_l, border_l) = if opts.dark {
+        (0.10, 0.17 + next() * 0.04, 0.90, 0.70 + next() * 0.08, 0.45)
+    } else {
+        (0.95, 0.88 - next() * 0.04, 0.15, 0.45 + next() * 0.08, 0.60)
+    };
+    let on_accent_l = if accent_l > 0.55 { 0.12 } else { 0.96 };
+
+    let mut vars = IndexMap::new();
+    let mut set = |k: &str, v: String| { vars.insert(k.to_string(), v); };
+    set("bar_bg", hsl_to_hex(accent, tint, bg_l));
+    set("bar_fg", hsl_to_hex(accent, 0.15, fg_l));
+    set("module_bg", hsl_to_hex(accent, tint, module_l));
+    set("module_fg", hsl_to_hex(accent, 0.15, fg_l));
+    set("hover_bg", hsl_to_hex(accent, s, accent_l));
+    set("hover_fg", hsl_to_hex(accent, 0.3, on_accent_l));
+    set("border_color", hsl_to_hex(second, if mono { s * 0.4 } else { s * 0.6 }, border_l));
+    set("launcher", hsl_to_hex(second, s, accent_l));
+    set("special", hsl_to_hex(third, if mono { s * 0.5 } else { s * 0.7 }, if opts.dark { 0.35 } else { 0.75 }));
+    set("special_fg", hsl_to_hex(third, 0.2, fg_l));
+    set("power", hsl_to_hex(if mono { accent } else { 0.0 }, 0.75, if opts.dark { 0.72 } else { 0.50 }));
+    Some(vars)
+}
+
+/// Metric vars (`8px`, `0.5`) are stored alongside colors but are not part of a palette.
+pub fn is_metric(value: &str) -> bool {
+    value.ends_with("px") || value.parse::<f64>().is_ok()
+}
+
+/// User-writable color presets live in `$XDG_DATA_HOME/waybarconf/colors`.
+pub fn user_colors_dir() -> PathBuf {
+    resources::user_dir("colors")
+}
+
+/// Bundled (built-in and system) presets first, then the user's own. A user preset
+/// shadows a bundled one with the same name.
+pub fn list_color_presets() -> Vec<ColorPreset> {
+    let mut presets: Vec<ColorPreset> = resources::list("colors").into_iter()
+        .filter(|f| f.extension() == Some("css"))
+        .map(|file| {
+            let source = if file.layer == Layer::User { PresetSource::User } else { PresetSource::Bundled };
+            ColorPreset { name: file.stem().to_string(), file, source }
+        })
+        .collect();
+    presets.sort_by_key(|p| p.source == PresetSource::User);
+    presets
+}
+
+/// Names referenced as `@name` in `css` (at-rule keywords excluded).
+pub fn referenced_vars(css: &str) -> Vec<String> {
+    let re = regex::Regex::new(r"@([A-Za-z_][\w-]*)").unwrap();
+    let mut names: Vec<String> = Vec::new();
+    for c in re.captures_iter(css) {
+        let name = &c[1];
+        if matches!(name, "import" | "keyframes" | "define-color" | "media" | "font-face") { continue; }
+        if !names.iter().any(|n| n == name) { names.push(name.to_string()); }
+    }
+    names
+}
+
+/// Applies a preset palette. `replace` drops every current color var first, except metrics
+/// and the colors in `in_use` (referenced by the stylesheet) that the preset doesn't define,
+/// so no `@var` is left undefined; otherwise the preset is merged over the current palette.
+/// Returns the colors kept that way.
+pub fn apply_palette(vars: &mut IndexMap<String, String>, preset: IndexMap<String, String>, replace: bool, in_use: &[String]) -> Vec<String> {
+    let mut kept = Vec::new();
+    if replace {
+        vars.retain(|k, v| {
+            if is_metric(v) { return true; }
+            let keep = in_use.contains(k) && !preset.contains_key(k);
+            if keep { kept.push(k.clone()); }
+            keep
+        });
+    }
+    for (k, v) in preset {
+        vars.insert(k, v);
+    }
+    kept
+}
+
+/// True when every color of `preset` resolves to the same value in `current`.
+pub fn preset_matches(preset: &IndexMap<String, String>, current: &IndexMap<String, String>) -> bool {
+    let preset = resolve_vars(preset);
+    let current = resolve_vars(current);
+    !preset.is_empty() && preset.iter().all(|(k, v)| {
+        current.get(k).is_some_and(|c| c.eq_ignore_ascii_case(v))
+    })
+}
+
+fn sanitize_name(name: &str) -> String {
+    name.trim().to_lowercase().replace(' ', "_")
+        .chars()
+        .filter(|c| c.is_alphanumeric() || *c == '-' || *c == '_')
+        .collect()
+}
+
+pub fn palette_to_css(vars: &IndexMap<String, String>) -> String {
+    let mut content = String::new();
+    for (k, v) in vars {
+        if !is_metric(v) {
+            content.push_str(&format!("@define-color {} {};\n", k, v));
+        }
+    }
+    content
+}
+
+pub fn save_user_preset(name: &str, vars: &IndexMap<String, String>) -> Result<PathBuf, String> {
+    let file_name = sanitize_name(name);
+    if file_name.is_empty() { return Err("Preset name is empty".into()); }
+    let dir = user_colors_dir();
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let path = dir.join(format!("{}.css", file_name));
+    if path.exists() { return Err(format!("A preset named {} already exists", file_name)); }
+    // The name goes into a comment, which a `*/` in it would end early.
+    fs::write(&path, format!("/* {} */\n{}", name.trim().replace("*/", "* /"), palette_to_css(vars))).map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+pub fn rename_user_preset(preset: &ColorPreset, new_name: &str) -> Result<PathBuf, String> {
+    if preset.source != PresetSource::User { return Err("Bundled presets cannot be renamed".into()); }
+    let file_name = sanitize_name(new_name);
+    if file_name.is_empty() { return Err("Preset name is empty".into()); }
+    let new_path = user_colors_dir().join(format!("{}.css", file_name));
+    if new_path.exists() { return Err(format!("A preset named {} already exists", file_name)); }
+    let old_path = preset.file.path().ok_or("Bundled presets cannot be renamed")?;
+    fs::rename(old_path, &new_path).map_err(|e| e.to_string())?;
+    Ok(new_path)
+}
+
+pub fn delete_user_preset(preset: &ColorPreset) -> Result<(), String> {
+    if preset.source != PresetSource::User { return Err("Bundled presets cannot be deleted".into()); }
+    fs::remove_file(preset.file.path().ok_or("Bundled presets cannot be deleted")?).map_err(|e| e.to_string())
+}
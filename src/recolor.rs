@@ -0,0 +1,228 @@
+use indexmap::IndexMap;
+use regex::Regex;
+use crate::palette::hex_to_rgb;
+
+/// One hard-coded color in a rule body, with where it sits.
+#[derive(Debug, Clone)]
+pub struct ColorUse {
+    /// Byte range of the literal in the stylesheet.
+    pub start: usize,
+    pub end: usize,
+    pub rgb: (u8, u8, u8),
+    /// Below 1.0 for `rgba()` and 8-digit hex; kept as `alpha(@var, a)` on rewrite.
+    pub alpha: f64,
+    pub selector: String,
+    pub prop: String,
+}
+
+/// Literal colors that are close enough to share one palette variable.
+#[derive(Debug, Clone)]
+pub struct Cluster {
+    /// `#rrggbb` of the most used member; the value the variable gets.
+    pub color: String,
+    /// Distinct literals as written in the file.
+    pub literals: Vec<String>,
+    /// Indices into the uses passed to `propose`.
+    pub uses: Vec<usize>,
+    /// Proposed variable name, without `@`.
+    pub var: String,
+}
+
+/// Colors closer than this (RGB distance) are merged.
+const CLUSTER_DISTANCE: f64 = 16.0;
+
+/// Byte ranges of `/* ... */` comments.
+fn comment_spans(css: &str) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut from = 0;
+    while let Some(rel) = css[from..].find("/*") {
+        let start = from + rel;
+        let end = css[start + 2..].find("*/").map(|i| start + 2 + i + 2).unwrap_or(css.len());
+        spans.push((start, end));
+        from = end;
+    }
+    spans
+}
+
+/// Every `{ ... }` block as (header, open brace, close brace), braces in comments ignored.
+fn block_spans(css: &str, comments: &[(usize, usize)]) -> Vec<(String, usize, usize)> {
+    let mut blocks = Vec::new();
+    let mut stack: Vec<(usize, usize)> = Vec::new();
+    let mut header_start = 0;
+    for (i, c) in css.char_indices() {
+        if comments.iter().any(|(s, e)| i >= *s && i < *e) { continue; }
+        match c {
+            '{' => { stack.push((header_start, i)); header_start = i + 1; }
+            '}' => {
+                if let Some((h, open)) = stack.pop() {
+                    let header = strip_comments(&css[h..open], h, comments).trim().to_string();
+                    blocks.push((header, open, i));
+                }
+                header_start = i + 1;
+            }
+            ';' => header_start = i + 1,
+            _ => {}
+        }
+    }
+    blocks
+}
+
+fn strip_comments(text: &str, offset: usize, comments: &[(usize, usize)]) -> String {
+    text.char_indices()
+        .filter(|(i, _)| !comments.iter().any(|(s, e)| offset + i >= *s && offset + i < *e))
+        .map(|(_, c)| c)
+        .collect()
+}
+
+/// Hard-coded colors (`#hex`, `rgb()`, `rgba()`) in rule bodies. `@define-color` lines,
+/// comments and `@keyframes` (whose colors are usually deliberate) are left out.
+pub fn find_colors(css: &str) -> Vec<ColorUse> {
+    let comments = comment_spans(css);
+    let blocks = block_spans(css, &comments);
+    let re = Regex::new(r"#([0-9a-fA-F]{8}|[0-9a-fA-F]{6}|[0-9a-fA-F]{3,4})\b|rgba?\(\s*(\d{1,3})\s*,\s*(\d{1,3})\s*,\s*(\d{1,3})\s*(?:,\s*([\d.]+)\s*)?\)").unwrap();
+    let mut out = Vec::new();
+    for m in re.captures_iter(css) {
+        let whole = m.get(0).unwrap();
+        let pos = whole.start();
+        if comments.iter().any(|(s, e)| pos >= *s && pos < *e) { continue; }
+        let enclosing: Vec<&(String, usize, usize)> = blocks.iter().filter(|(_, o, c)| pos > *o && pos < *c).collect();
+        if enclosing.iter().any(|(h, _, _)| h.starts_with("@keyframes")) { continue; }
+        // Innermost block is the one that opens last.
+        let Some((selector, open, _)) = enclosing.iter().max_by_key(|(_, o, _)| *o) else { continue };
+        let decl_start = css[*open + 1..pos].rfind([';', '{']).map(|i| open + 1 + i + 1).unwrap_or(open + 1);
+        let Some((prop, _)) = css[decl_start..pos].split_once(':') else { continue };
+        let prop = strip_comments(prop, decl_start, &comments).trim().to_string();
+        if prop.is_empty() || prop.starts_with('@') { continue; }
+
+        let (rgb, alpha) = if let Some(hex) = m.get(1) {
+            let Some(rgb) = hex_to_rgb(&format!("#{}", hex.as_str())) else { continue };
+            let alpha = match hex.as_str().len() {
+                8 => u8::from_str_radix(&hex.as_str()[6..], 16).map(|a| a as f64 / 255.0).unwrap_or(1.0),
+                4 => u8::from_str_radix(&hex.as_str()[3..].repeat(2), 16).map(|a| a as f64 / 255.0).unwrap_or(1.0),
+                _ => 1.0,
+            };
+            (rgb, alpha)
+        } else {
+            let ch = |i: usize| m.get(i).and_then(|v| v.as_str().parse::<u16>().ok()).map(|v| v.min(255) as u8).unwrap_or(0);
+            let alpha = m.get(5).and_then(|a| a.as_str().parse::<f64>().ok()).unwrap_or(1.0).clamp(0.0, 1.0);
+            ((ch(2), ch(3), ch(4)), alpha)
+        };
+        out.push(ColorUse { start: pos, end: whole.end(), rgb, alpha, selector: selector.clone(), prop });
+    }
+    out
+}
+
+/// The palette role a color most likely plays, from where it is used.
+pub fn role_for(selector: &str, prop: &str) -> Option<&'static str> {
+    let is_bg = prop.starts_with("background");
+    let is_fg = prop == "color";
+    if prop.starts_with("border") || prop == "outline-color" { return Some("border_color"); }
+    if !is_bg && !is_fg { return None; }
+    let sel = selector.to_lowercase();
+    let pick = |bg: &'static str, fg: &'static str| Some(if is_bg { bg } else { fg });
+    if sel.contains(".urgent") || sel.contains(".critical") || sel.contains("power") {
+        return pick("power", "power");
+    }
+    if sel.contains(":hover") || sel.contains(".active") || sel.contains(".focused") {
+        return pick("hover_bg", "hover_fg");
+    }
+    if sel.starts_with("window") || sel.contains("#waybar") || sel == "*" {
+        return pick("bar_bg", "bar_fg");
+    }
+    pick("module_bg", "module_fg")
+}
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> f64 {
+    let d = |x: u8, y: u8| (x as f64 - y as f64).powi(2);
+    (d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)).sqrt()
+}
+
+fn to_hex((r, g, b): (u8, u8, u8)) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+/// Groups the uses into clusters of near-identical colors and names each one: an existing
+/// palette variable with the same color, else the role it is used for most (each role
+/// going to the cluster that uses it most), else `color_N`.
+pub fn propose(css: &str, uses: &[ColorUse], existing: &IndexMap<String, String>) -> Vec<Cluster> {
+    // Most used colors first, so they become the cluster centers.
+    let mut counts: Vec<((u8, u8, u8), usize)> = Vec::new();
+    for u in uses {
+        match counts.iter_mut().find(|(c, _)| *c == u.rgb) {
+            Some((_, n)) => *n += 1,
+            None => counts.push((u.rgb, 1)),
+        }
+    }
+    counts.sort_by_key(|c| std::cmp::Reverse(c.1));
+    let mut centers: Vec<(u8, u8, u8)> = Vec::new();
+    for (c, _) in &counts {
+        if !centers.iter().any(|k| distance(*k, *c) <= CLUSTER_DISTANCE) { centers.push(*c); }
+    }
+    let mut clusters: Vec<Cluster> = centers.iter().map(|c| Cluster { color: to_hex(*c), literals: Vec::new(), uses: Vec::new(), var: String::new() }).collect();
+    for (i, u) in uses.iter().enumerate() {
+        let Some(k) = centers.iter().enumerate().min_by(|a, b| distance(*a.1, u.rgb).total_cmp(&distance(*b.1, u.rgb))).map(|(k, _)| k) else { continue };
+        let literal = css[u.start..u.end].to_string();
+        if !clusters[k].literals.contains(&literal) { clusters[k].literals.push(literal); }
+        clusters[k].uses.push(i);
+    }
+
+    let resolved = crate::palette::resolve_vars(existing);
+    let mut taken: Vec<String> = Vec::new();
+    for cluster in clusters.iter_mut() {
+        if let Some((name, _)) = resolved.iter().find(|(_, v)| v.eq_ignore_ascii_case(&cluster.color)) {
+            if !taken.contains(name) { cluster.var = name.clone(); taken.push(name.clone()); }
+        }
+    }
+
+    // (cluster, role, votes), strongest first.
+    let mut votes: Vec<(usize, &'static str, usize)> = Vec::new();
+    for (k, cluster) in clusters.iter().enumerate() {
+        for &i in &cluster.uses {
+            let Some(role) = role_for(&uses[i].selector, &uses[i].prop) else { continue };
+            match votes.iter_mut().find(|(c, r, _)| *c == k && *r == role) {
+                Some((_, _, n)) => *n += 1,
+                None => votes.push((k, role, 1)),
+            }
+        }
+    }
+    votes.sort_by_key(|v| std::cmp::Reverse(v.2));
+    for (k, role, _) in votes {
+        if !clusters[k].var.is_empty() || taken.iter().any(|t| t == role) { continue; }
+        clusters[k].var = role.to_string();
+        taken.push(role.to_string());
+    }
+
+    let mut n = 1;
+    for cluster in clusters.iter_mut().filter(|c| c.var.is_empty()) {
+        while taken.contains(&format!("color_{}", n)) || existing.contains_key(&format!("color_{}", n)) { n += 1; }
+        cluster.var = format!("color_{}", n);
+        taken.push(cluster.var.clone());
+    }
+    clusters
+}
+
+/// Replaces every use in `clusters` with its variable (`alpha(@var, a)` for translucent
+/// colors) and makes sure the palette file is imported.
+pub fn rewrite(css: &str, uses: &[ColorUse], clusters: &[Cluster]) -> String {
+    let mut edits: Vec<(usize, usize, String)> = Vec::new();
+    for cluster in clusters {
+        for &i in &cluster.uses {
+            let u = &uses[i];
+            let value = if u.alpha < 1.0 {
+                format!("alpha(@{}, {})", cluster.var, (u.alpha * 100.0).round() / 100.0)
+            } else {
+                format!("@{}", cluster.var)
+            };
+            edits.push((u.start, u.end, value));
+        }
+    }
+    edits.sort_by_key(|e| std::cmp::Reverse(e.0));
+    let mut out = css.to_string();
+    for (start, end, value) in edits {
+        out.replace_range(start..end, &value);
+    }
+    if !clusters.is_empty() && !out.contains(crate::targets::PALETTE_IMPORT) {
+        out.insert_str(0, &format!("@import \"{}\";\n\n", crate::targets::PALETTE_IMPORT));
+    }
+    out
+}
@@ -0,0 +1,193 @@
+use gtk4::{gio, glib};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Where `build.rs` compiles the bundled `presets/` directories in.
+const RESOURCE_PREFIX: &str = "/com/github/waybarconf/presets";
+
+/// Where a preset file comes from, lowest precedence first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Layer {
+    /// Compiled into the binary.
+    Builtin,
+    /// `$XDG_DATA_DIRS/waybarconf`, for distribution packages.
+    System,
+    /// `$XDG_DATA_HOME/waybarconf`.
+    User,
+}
+
+#[derive(Debug, Clone)]
+enum Location {
+    Resource(String),
+    File(PathBuf),
+}
+
+/// One preset (brick, layout, palette, script, template) from whichever layer wins.
+#[derive(Debug, Clone)]
+pub struct PresetFile {
+    /// Path below the preset directory, e.g. `pill.css` or `todo/todo.sh`.
+    pub name: String,
+    pub layer: Layer,
+    location: Location,
+}
+
+impl PresetFile {
+    pub fn stem(&self) -> &str {
+        let file = self.name.rsplit('/').next().unwrap_or(&self.name);
+        file.rsplit_once('.').map(|(stem, _)| stem).unwrap_or(file)
+    }
+
+    pub fn extension(&self) -> Option<&str> {
+        self.name.rsplit('/').next()?.rsplit_once('.').map(|(_, ext)| ext)
+    }
+
+    /// The file on disk; `None` for built-in presets.
+    pub fn path(&self) -> Option<&Path> {
+        match &self.location {
+            Location::File(p) => Some(p),
+            Location::Resource(_) => None,
+        }
+    }
+
+    pub fn bytes(&self) -> Option<Vec<u8>> {
+        match &self.location {
+            Location::File(p) => fs::read(p).ok(),
+            Location::Resource(r) => gio::resources_lookup_data(r, gio::ResourceLookupFlags::NONE).ok().map(|b| b.to_vec()),
+        }
+    }
+
+    pub fn read(&self) -> Option<String> {
+        String::from_utf8(self.bytes()?).ok()
+    }
+}
+
+/// Makes the compiled-in presets available; call once before any lookup.
+pub fn register() -> Result<(), glib::Error> {
+    gio::resources_register_include!("presets.gresource")
+}
+
+fn data_home() -> PathBuf {
+    std::env::var("XDG_DATA_HOME")
+        .ok()
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(std::env::var("HOME").unwrap_or_default()).join(".local/share"))
+}
+
+/// The user's own presets of one kind; also where saved presets go.
+pub fn user_dir(sub: &str) -> PathBuf {
+    data_home().join("waybarconf").join(sub)
+}
+
+/// System preset directories, most important first as in `$XDG_DATA_DIRS`.
+pub fn system_dirs(sub: &str) -> Vec<PathBuf> {
+    std::env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|d| !d.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".to_string())
+        .split(':')
+        .filter(|d| !d.is_empty())
+        .map(|d| PathBuf::from(d).join("waybarconf").join(sub))
+        .collect()
+}
+
+fn builtin(sub: &str) -> Vec<PresetFile> {
+    let dir = format!("{}/{}", RESOURCE_PREFIX, sub);
+    gio::resources_enumerate_children(&dir, gio::ResourceLookupFlags::NONE)
+        .map(|names| names.iter()
+            .filter(|n| !n.ends_with('/'))
+            .map(|n| PresetFile { name: n.to_string(), layer: Layer::Builtin, location: Location::Resource(format!("{}/{}", dir, n)) })
+            .collect())
+        .unwrap_or_default()
+}
+
+fn on_disk(dir: &Path, layer: Layer) -> Vec<PresetFile> {
+    fs::read_dir(dir)
+        .map(|entries| entries.flatten()
+            .map(|e| e.path())
+            .filter(|p| p.is_file())
+            .filter_map(|p| Some(PresetFile { name: p.file_name()?.to_str()?.to_string(), layer, location: Location::File(p) }))
+            .collect())
+        .unwrap_or_default()
+}
+
+/// Every preset of one kind (`bricks`, `layouts`, `colors`, `scripts`, `templates`), sorted
+/// by name. A file in the system data dirs replaces a built-in one of the same name, and a
+/// file in the user data dir replaces both.
+pub fn list(sub: &str) -> Vec<PresetFile> {
+    let mut files = builtin(sub);
+    let mut layers: Vec<(PathBuf, Layer)> = system_dirs(sub).into_iter().rev().map(|d| (d, Layer::System)).collect();
+    layers.push((user_dir(sub), Layer::User));
+    for (dir, layer) in layers {
+        for file in on_disk(&dir, layer) {
+            files.retain(|f| f.name != file.name);
+            files.push(file);
+        }
+    }
+    files.sort_by(|a, b| a.name.cmp(&b.name));
+    files
+}
+
+/// The winning copy of `name` (which may point into a subdirectory, `todo/todo.sh`).
+pub fn find(sub: &str, name: &str) -> Option<PresetFile> {
+    let file = |dir: PathBuf, layer| {
+        let p = dir.join(name);
+        p.is_file().then(|| PresetFile { name: name.to_string(), layer, location: Location::File(p) })
+    };
+    file(user_dir(sub), Layer::User)
+        .or_else(|| system_dirs(sub).into_iter().find_map(|d| file(d, Layer::System)))
+        .or_else(|| {
+            let path = format!("{}/{}/{}", RESOURCE_PREFIX, sub, name);
+            gio::resources_get_info(&path, gio::ResourceLookupFlags::NONE).ok()
+                .map(|_| PresetFile { name: name.to_string(), layer: Layer::Builtin, location: Location::Resource(path) })
+        })
+}
+
+fn cache_dir(sub: &str) -> PathBuf {
+    std::env::var("XDG_CACHE_HOME")
+        .ok()
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(std::env::var("HOME").unwrap_or_default()).join(".cache"))
+        .join("waybarconf")
+        .join(sub)
+}
+
+/// A script Waybar can run. Built-in scripts only exist inside the binary, so they are
+/// extracted (and made executable) into the cache dir, and rewritten whenever the built-in
+/// copy changes. The user data dir is left for real overrides.
+pub fn script_path(name: &str) -> Option<PathBuf> {
+    let file = find("scripts", name)?;
+    if let Some(p) = file.path() { return Some(p.to_path_buf()); }
+    let dest = cache_dir("scripts").join(name);
+    let bytes = file.bytes()?;
+    if fs::read(&dest).ok().as_ref() != Some(&bytes) {
+        fs::create_dir_all(dest.parent()?).ok()?;
+        fs::write(&dest, bytes).ok()?;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = fs::set_permissions(&dest, fs::Permissions::from_mode(0o755));
+    }
+    Some(dest)
+}
+
+/// Points script references in a brick's module config (`.../presets/scripts/<name>`, as
+/// written by older installs) at the script's real location.
+pub fn resolve_script_refs(value: &mut serde_json::Value) {
+    static SCRIPT_REF: OnceLock<regex::Regex> = OnceLock::new();
+    match value {
+        serde_json::Value::String(s) => {
+            let re = SCRIPT_REF.get_or_init(|| regex::Regex::new(r"\S*presets/scripts/([\w./-]+)").unwrap());
+            if !re.is_match(s) { return; }
+            *s = re.replace_all(s, |c: &regex::Captures| {
+                script_path(&c[1]).map(|p| crate::imports::display_path(&p)).unwrap_or_else(|| c[0].to_string())
+            }).to_string();
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(resolve_script_refs),
+        serde_json::Value::Object(map) => map.values_mut().for_each(resolve_script_refs),
+        _ => {}
+    }
+}
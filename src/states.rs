@@ -0,0 +1,99 @@
+use indexmap::IndexMap;
+use serde_json::{Map, Value};
+
+/// Modules that compute a state from a value, with the value Waybar compares against.
+/// Battery is the odd one out: a state applies when the capacity drops *below* it.
+pub const STATE_MODULES: &[(&str, &str)] = &[
+    ("battery", "capacity"),
+    ("backlight", "brightness"),
+    ("cpu", "usage"),
+    ("disk", "used space"),
+    ("load", "load"),
+    ("memory", "used memory"),
+    ("network", "signal strength"),
+    ("pulseaudio", "volume"),
+    ("wireplumber", "volume"),
+    ("sndio", "volume"),
+    ("temperature", "temperature"),
+    ("upower", "charge"),
+    ("custom", "percentage"),
+];
+
+/// The module type without the `#name` suffix, with all `custom/...` modules folded together.
+pub fn base(module: &str) -> &str {
+    let base = module.split('#').next().unwrap_or(module);
+    if base.starts_with("custom/") { "custom" } else { base }
+}
+
+/// What the thresholds of `module` are compared against, or None when it has no states.
+pub fn state_value(module: &str) -> Option<&'static str> {
+    let base = base(module);
+    STATE_MODULES.iter().find(|(m, _)| *m == base).map(|(_, v)| *v)
+}
+
+pub fn lower_is_worse(module: &str) -> bool {
+    matches!(base(module), "battery" | "upower")
+}
+
+/// States offered when a module has none yet.
+pub fn suggested_states(module: &str) -> &'static [(&'static str, i64)] {
+    match base(module) {
+        "battery" | "upower" => &[("good", 95), ("warning", 30), ("critical", 15)],
+        "temperature" => &[("warning", 70), ("critical", 85)],
+        "disk" => &[("warning", 80), ("critical", 90)],
+        "pulseaudio" | "wireplumber" | "sndio" => &[("loud", 80)],
+        "backlight" => &[("bright", 80)],
+        "network" => &[("strong", 60)],
+        _ => &[("warning", 70), ("critical", 90)],
+    }
+}
+
+
+/// Palette color a new state's CSS block starts with. `@power` and `@special` only come
+/// with some presets, so without them in `vars` the state falls back to `@hover_bg`.
+pub fn default_color(name: &str, vars: &IndexMap<String, String>) -> &'static str {
+    let preferred = match name {
+        "critical" | "urgent" | "danger" | "high" | "loud" => "@power",
+        "good" | "full" | "ok" | "strong" => "@special",
+        _ => "@hover_bg",
+    };
+    if vars.contains_key(&preferred[1..]) { preferred } else { "@hover_bg" }
+}
+
+/// The state Waybar applies for `value`: the tightest threshold the value has reached
+/// (at or below it for battery, at or above it otherwise).
+pub fn active_state(states: &Map<String, Value>, value: i64, lower_is_worse: bool) -> Option<String> {
+    let mut sorted: Vec<(&String, i64)> = states.iter().filter_map(|(k, v)| v.as_i64().map(|t| (k, t))).collect();
+    if lower_is_worse {
+        sorted.sort_by_key(|(_, t)| *t);
+        sorted.into_iter().find(|(_, t)| value <= *t).map(|(k, _)| k.clone())
+    } else {
+        sorted.sort_by_key(|(_, t)| -*t);
+        sorted.into_iter().find(|(_, t)| value >= *t).map(|(k, _)| k.clone())
+    }
+}
+
+/// Classes Waybar toggles on a module by itself (not through `states`), as (class, meaning).
+/// The first element is the child widget carrying them: `" button"` for workspace and tag
+/// buttons, `" label"` for keyboard-state, `""` for the module itself.
+pub fn status_classes(module: &str) -> (&'static str, &'static [(&'static str, &'static str)]) {
+    match base(module) {
+        "pulseaudio" => ("", &[("muted", "Output muted"), ("source-muted", "Microphone muted"), ("bluetooth", "Bluetooth sink")]),
+        "wireplumber" => ("", &[("muted", "Muted")]),
+        "battery" => ("", &[("charging", "Charging"), ("discharging", "Discharging"), ("plugged", "Plugged in, not charging"), ("full", "Fully charged"), ("not-charging", "Charging held back")]),
+        "network" => ("", &[("disconnected", "No connection"), ("disabled", "Networking off"), ("linked", "Cable in, no IP"), ("ethernet", "Wired connection"), ("wifi", "Wireless connection")]),
+        "bluetooth" => ("", &[("off", "Adapter off"), ("disabled", "Adapter disabled"), ("on", "On, nothing connected"), ("connected", "Device connected"), ("discoverable", "Discoverable"), ("discovering", "Scanning")]),
+        "idle_inhibitor" => ("", &[("activated", "Inhibiting idle"), ("deactivated", "Not inhibiting")]),
+        "temperature" => ("", &[("critical", "Above critical-threshold")]),
+        "mpris" | "mpd" => ("", &[("playing", "Playing"), ("paused", "Paused"), ("stopped", "Stopped")]),
+        "power-profiles-daemon" => ("", &[("performance", "Performance profile"), ("balanced", "Balanced profile"), ("power-saver", "Power saver profile")]),
+        "systemd-failed-units" => ("", &[("ok", "No failed units"), ("degraded", "Some units failed")]),
+        "keyboard-state" => (" label", &[("locked", "Lock key on")]),
+        "hyprland/workspaces" => (" button", &[("active", "Focused workspace"), ("urgent", "Urgent window"), ("empty", "No windows"), ("visible", "Shown on a monitor"), ("persistent", "Persistent workspace"), ("special", "Special workspace")]),
+        "sway/workspaces" => (" button", &[("focused", "Focused workspace"), ("urgent", "Urgent window"), ("visible", "Shown on a monitor"), ("persistent", "Persistent workspace"), ("current_output", "On this bar's output")]),
+        "niri/workspaces" => (" button", &[("focused", "Focused workspace"), ("active", "Active on its output"), ("urgent", "Urgent window"), ("empty", "No windows"), ("current_output", "On this bar's output")]),
+        "river/tags" | "dwl/tags" => (" button", &[("focused", "Focused tag"), ("occupied", "Has windows"), ("urgent", "Urgent window")]),
+        "wlr/taskbar" => (" button", &[("active", "Focused window"), ("minimized", "Minimized"), ("maximized", "Maximized"), ("fullscreen", "Fullscreen")]),
+        _ => ("", &[]),
+    }
+}
@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use indexmap::IndexMap;
+use crate::export::expand_home;
+use crate::imports;
+
+/// The palette import the bundled layouts and the color conversion write into `style.css`.
+pub const PALETTE_IMPORT: &str = "colors/wallpaper.css";
+
+/// `$XDG_CONFIG_HOME/waybar`, falling back to `~/.config/waybar`.
+pub fn waybar_dir() -> PathBuf {
+    std::env::var("XDG_CONFIG_HOME")
+        .ok()
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(std::env::var("HOME").unwrap_or_default()).join(".config"))
+        .join("waybar")
+}
+
+/// The config file Waybar would pick up, if there is one. Waybar tries `config` before
+/// `config.jsonc`.
+pub fn find_config() -> Option<PathBuf> {
+    let dir = waybar_dir();
+    [dir.join("config"), dir.join("config.jsonc")].into_iter().find(|p| p.exists())
+}
+
+/// Where Apply writes, saved with each profile. Unset paths resolve the way Waybar looks
+/// for its files, so by default the files that were loaded are the ones written.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TargetPaths {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub config: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub style: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub palette: Option<PathBuf>,
+}
+
+impl TargetPaths {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn default_config() -> PathBuf {
+        find_config().unwrap_or_else(|| waybar_dir().join("config.jsonc"))
+    }
+
+    pub fn default_style() -> PathBuf {
+        waybar_dir().join("style.css")
+    }
+
+    pub fn default_palette() -> PathBuf {
+        Self::palette_for(&Self::default_style())
+    }
+
+    /// The file `style` takes its colors from: of the sheets it imports, the one defining
+    /// most of the color variables in effect, else `colors/wallpaper.css` beside it.
+    pub fn palette_for(style: &Path) -> PathBuf {
+        let mut counts: IndexMap<PathBuf, usize> = IndexMap::new();
+        for (_, (_, src)) in imports::definitions(&imports::build_tree(style)) {
+            if src != style { *counts.entry(src).or_default() += 1; }
+        }
+        let mut best: Option<(PathBuf, usize)> = None;
+        for (path, n) in counts {
+            if best.as_ref().is_none_or(|(_, m)| n > *m) { best = Some((path, n)); }
+        }
+        best.map(|(path, _)| path)
+            .unwrap_or_else(|| style.parent().map(Path::to_path_buf).unwrap_or_else(waybar_dir).join(PALETTE_IMPORT))
+    }
+
+    pub fn config(&self) -> PathBuf {
+        self.config.clone().unwrap_or_else(Self::default_config)
+    }
+
+    pub fn style(&self) -> PathBuf {
+        self.style.clone().unwrap_or_else(Self::default_style)
+    }
+
+    pub fn palette(&self) -> PathBuf {
+        self.palette.clone().unwrap_or_else(|| Self::palette_for(&self.style()))
+    }
+
+    /// Directory of the style sheet, which its relative imports (`layouts/…`) resolve against.
+    pub fn style_dir(&self) -> PathBuf {
+        self.style().parent().map(Path::to_path_buf).unwrap_or_else(waybar_dir)
+    }
+
+    /// How `style.css` should import the palette: relative when it sits below the style
+    /// sheet's directory, absolute otherwise.
+    pub fn palette_import(&self) -> String {
+        let palette = self.palette();
+        match palette.strip_prefix(self.style_dir()) {
+            Ok(rel) => rel.display().to_string(),
+            Err(_) => palette.display().to_string(),
+        }
+    }
+
+    /// Parses a path typed by the user; empty means "use the default".
+    pub fn parse(text: &str) -> Option<PathBuf> {
+        let text = text.trim();
+        if text.is_empty() { None } else { Some(expand_home(text)) }
+    }
+}
+
+/// `css` with the bundled palette import pointed at `import` instead.
+pub fn retarget_palette_import(css: &str, import: &str) -> String {
+    if import == PALETTE_IMPORT { return css.to_string(); }
+    css.replace(&format!("@import \"{}\";", PALETTE_IMPORT), &format!("@import \"{}\";", import))
+}
@@ -0,0 +1,56 @@
+use crate::states::base;
+
+/// `tooltip-format` keys a module reads: the plain one first, then the per-status
+/// variants Waybar picks over it.
+pub fn format_keys(module: &str) -> &'static [&'static str] {
+    match base(module) {
+        "battery" => &["tooltip-format", "tooltip-format-charging", "tooltip-format-discharging", "tooltip-format-plugged", "tooltip-format-full"],
+        "network" => &["tooltip-format", "tooltip-format-wifi", "tooltip-format-ethernet", "tooltip-format-linked", "tooltip-format-disconnected", "tooltip-format-disabled"],
+        "bluetooth" => &["tooltip-format", "tooltip-format-on", "tooltip-format-off", "tooltip-format-disabled", "tooltip-format-connected", "tooltip-format-connected-battery", "tooltip-format-enumerate-connected", "tooltip-format-enumerate-connected-battery"],
+        "idle_inhibitor" => &["tooltip-format-activated", "tooltip-format-deactivated"],
+        "mpris" => &["tooltip-format", "tooltip-format-playing", "tooltip-format-paused", "tooltip-format-stopped"],
+        "mpd" => &["tooltip-format", "tooltip-format-disconnected"],
+        _ => &["tooltip-format"],
+    }
+}
+
+/// Made-up values for the placeholders modules fill in, so a preview reads like the real
+/// tooltip.
+const SAMPLES: &[(&str, &str)] = &[
+    // battery / upower
+    ("capacity", "76"), ("power", "12.4"), ("time", "3 h 12 min"), ("timeTo", "3 h 12 min until empty"), ("cycles", "231"), ("health", "97"),
+    // network
+    ("essid", "HomeWiFi"), ("signalStrength", "72"), ("signaldBm", "-58"), ("frequency", "5.2"), ("ifname", "wlan0"),
+    ("ipaddr", "192.168.1.20"), ("gwaddr", "192.168.1.1"), ("cidr", "24"), ("netmask", "255.255.255.0"),
+    ("bandwidthUpBits", "1.2Mb/s"), ("bandwidthDownBits", "24.5Mb/s"), ("bandwidthTotalBits", "25.7Mb/s"),
+    ("bandwidthUpBytes", "150kB/s"), ("bandwidthDownBytes", "3.1MB/s"), ("bandwidthTotalBytes", "3.2MB/s"),
+    // audio
+    ("volume", "55"), ("desc", "Built-in Speakers"), ("source_volume", "40"), ("source_desc", "Built-in Microphone"), ("node_name", "Speakers"),
+    // cpu / memory / disk / temperature / backlight
+    ("usage", "23"), ("load", "0.42"), ("avg_frequency", "2.4"), ("max_frequency", "4.2"), ("min_frequency", "0.8"),
+    ("used", "6.2"), ("total", "15.5"), ("avail", "9.3"), ("percentage", "40"), ("swapUsed", "0.0"), ("swapTotal", "8.0"), ("swapPercentage", "0"),
+    ("path", "/"), ("free", "120 GiB"), ("percentage_free", "63"), ("percentage_used", "37"),
+    ("temperatureC", "52"), ("temperatureF", "126"), ("temperatureK", "325"), ("percent", "80"),
+    // bluetooth
+    ("controller_alias", "hci0"), ("controller_address", "00:1A:7D:DA:71:13"), ("status", "connected"), ("num_connections", "1"),
+    ("device_alias", "WH-1000XM4"), ("device_address", "38:18:4C:12:34:56"), ("device_battery_percentage", "80"), ("device_enumerate", "WH-1000XM4"),
+    // media
+    ("player", "spotify"), ("artist", "Daft Punk"), ("album", "Discovery"), ("title", "One More Time"), ("length", "5:20"), ("position", "1:05"),
+    ("dynamic", "Daft Punk - One More Time"), ("stateIcon", "▶"), ("elapsedTime", "1:05"), ("totalTime", "5:20"),
+    // misc
+    ("profile", "balanced"), ("driver", "platform_profile"), ("name", "firefox"), ("app_id", "firefox"), ("class", "firefox"),
+];
+
+pub fn sample(placeholder: &str) -> Option<&'static str> {
+    SAMPLES.iter().find(|(k, _)| *k == placeholder).map(|(_, v)| *v)
+}
+
+/// `format` with every known `{name}` / `{name:spec}` placeholder replaced by a sample
+/// value and `{icon}` by `icon`. Unknown placeholders are left as they are.
+pub fn render_sample(format: &str, icon: &str) -> String {
+    let re = regex::Regex::new(r"\{(\w+)(?::[^}]*)?\}").unwrap();
+    re.replace_all(format, |c: &regex::Captures| {
+        if &c[1] == "icon" { return icon.to_string(); }
+        sample(&c[1]).map(|s| s.to_string()).unwrap_or_else(|| c[0].to_string())
+    }).to_string()
+}
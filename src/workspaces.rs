@@ -0,0 +1,108 @@
+use regex::Regex;
+use serde_json::{Map, Value};
+
+/// Keys of `format-icons` that name a workspace state rather than a workspace.
+pub const STATE_ICON_KEYS: &[&str] = &["active", "default", "urgent", "empty", "persistent", "special", "focused", "visible"];
+
+/// One `window-rewrite` key. Waybar reads the whole key as a regex and searches it,
+/// unanchored, in the window's representation (see `window_repr`), so `firefox` matches
+/// `class<org.mozilla.firefox>` and `class<kitty>` only a window whose class is `kitty`.
+#[derive(Debug)]
+pub struct RewriteRule {
+    re: Regex,
+    /// Rules naming both fields are tried first, then title-only, class-only and bare ones.
+    priority: u8,
+}
+
+pub fn parse_rule(key: &str) -> Result<RewriteRule, String> {
+    let re = Regex::new(key).map_err(|e| e.to_string())?;
+    let priority = match (key.contains("class"), key.contains("title")) {
+        (true, true) => 3,
+        (false, true) => 2,
+        (true, false) => 1,
+        (false, false) => 0,
+    };
+    Ok(RewriteRule { re, priority })
+}
+
+impl RewriteRule {
+    pub fn matches(&self, repr: &str) -> bool {
+        self.re.is_match(repr)
+    }
+}
+
+/// What the rules are matched against: `class<CLASS> title<TITLE>`, or only
+/// `class<CLASS>` when no rule mentions the title.
+pub fn window_repr(rules: &Map<String, Value>, class: &str, title: &str) -> String {
+    if rules.keys().any(|k| k.contains("title")) {
+        format!("class<{}> title<{}>", class, title)
+    } else {
+        format!("class<{}>", class)
+    }
+}
+
+/// Result of running a sample window through the `window-rewrite` rules.
+#[derive(Debug, PartialEq)]
+pub enum RewriteOutcome {
+    Rule { key: String, icon: String },
+    Default(String),
+    NoMatch,
+}
+
+/// The first matching rule by priority (then config order), else `window-rewrite-default`.
+/// Rules with invalid regexes are skipped; see `rule_errors`.
+pub fn rewrite(rules: &Map<String, Value>, default: Option<&str>, class: &str, title: &str) -> RewriteOutcome {
+    let repr = window_repr(rules, class, title);
+    let mut parsed: Vec<(&String, &Value, RewriteRule)> = rules.iter()
+        .filter_map(|(key, icon)| parse_rule(key).ok().map(|r| (key, icon, r)))
+        .collect();
+    parsed.sort_by_key(|(_, _, r)| std::cmp::Reverse(r.priority));
+    if let Some((key, icon, _)) = parsed.into_iter().find(|(_, _, r)| r.matches(&repr)) {
+        return RewriteOutcome::Rule { key: key.clone(), icon: icon.as_str().unwrap_or("").to_string() };
+    }
+    match default {
+        Some(d) => RewriteOutcome::Default(d.to_string()),
+        None => RewriteOutcome::NoMatch,
+    }
+}
+
+pub fn rule_errors(rules: &Map<String, Value>) -> Vec<(String, String)> {
+    rules.keys().filter_map(|k| parse_rule(k).err().map(|e| (k.clone(), e))).collect()
+}
+
+/// `persistent-workspaces` value as shown in the editor: a count, or a comma-separated list.
+pub fn persistent_text(value: &Value) -> String {
+    match value {
+        Value::Array(items) => items.iter()
+            .map(|v| v.as_str().map(|s| s.to_string()).unwrap_or_else(|| v.to_string()))
+            .collect::<Vec<_>>().join(", "),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Parses editor text back. Hyprland maps a monitor to a workspace count or a list of
+/// workspace ids; Sway maps a workspace to the outputs it is created on (empty = all).
+pub fn parse_persistent(text: &str, hyprland: bool) -> Value {
+    let items: Vec<&str> = text.split(',').map(|s| s.trim()).filter(|s| !s.is_empty()).collect();
+    if hyprland {
+        if let [single] = items.as_slice() {
+            if let Ok(n) = single.parse::<i64>() { return Value::from(n); }
+        }
+        Value::Array(items.iter().map(|s| s.parse::<i64>().map(Value::from).unwrap_or_else(|_| Value::from(*s))).collect())
+    } else {
+        Value::Array(items.iter().map(|s| Value::from(*s)).collect())
+    }
+}
+
+/// Renames a key in place, keeping its position. Returns false (and changes nothing) when
+/// another entry already uses `new`.
+pub fn rename_key(map: &mut Map<String, Value>, old: &str, new: &str) -> bool {
+    if old == new { return true; }
+    if map.contains_key(new) { return false; }
+    let entries: Vec<(String, Value)> = std::mem::take(map).into_iter().collect();
+    for (k, v) in entries {
+        map.insert(if k == old { new.to_string() } else { k }, v);
+    }
+    true
+}